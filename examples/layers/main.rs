@@ -1,5 +1,3 @@
-use opentelemetry::global;
-use opentelemetry_sdk::propagation::TraceContextPropagator;
 use tracing::{info, instrument};
 use tracing_subscriber::Registry;
 use tracing_subscriber::{prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt};
@@ -22,6 +20,7 @@ fn setup_tracing(tags: &[(&'static str, &'static str)]) -> Result<(), tracing_ax
     let tag_iter = tags.iter().copied();
     let axiom_layer = tracing_axiom::builder("layers")
         .with_tags(tag_iter)
+        .with_trace_context_propagation()
         .build()?;
 
     // Setup our multi-layered tracing subscriber
@@ -30,8 +29,6 @@ fn setup_tracing(tags: &[(&'static str, &'static str)]) -> Result<(), tracing_ax
         .with(cloudwatch_layer)
         .init();
 
-    global::set_text_map_propagator(TraceContextPropagator::new());
-
     Ok(())
 }
 
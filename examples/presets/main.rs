@@ -0,0 +1,19 @@
+use tracing::{info, instrument};
+
+#[instrument]
+fn say_hi(id: u64, name: impl Into<String> + std::fmt::Debug) {
+    info!(?id, "Hello, {}!", name.into());
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Installs a pretty fmt layer and the Axiom layer in one call
+    tracing_axiom::presets::fmt_and_axiom(tracing_axiom::builder_with_env("presets")?)?;
+
+    say_hi(42, "world");
+
+    // Ensure that the tracing provider is shutdown correctly
+    opentelemetry::global::shutdown_tracer_provider();
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+//! [`actix-web`](https://docs.rs/actix-web) middleware creating a server span for
+//! every HTTP request. Enable with the `actix-web` feature.
+//!
+//! ```rust,ignore
+//! use actix_web::{App, HttpServer};
+//! use tracing_axiom::actix::HttpServerTracing;
+//!
+//! HttpServer::new(|| App::new().wrap(HttpServerTracing).service(get_user))
+//! ```
+
+use crate::spans;
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderMap, HeaderName},
+    Error,
+};
+use opentelemetry::{global, propagation::Extractor};
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+};
+use tracing::Instrument as _;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+/// Middleware that creates a server span for every request, continuing the trace
+/// of the caller if the request carries trace context headers.
+///
+/// The span is named after the matched route pattern (e.g. `GET /users/{id}`) and
+/// records the `http.request.method`, `http.route`, `url.path` and
+/// `http.response.status_code` attributes, the request latency is the span
+/// duration. Server errors and handler errors mark the span as failed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpServerTracing;
+
+impl<S, B> Transform<S, ServiceRequest> for HttpServerTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = HttpServerTracingService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HttpServerTracingService { service }))
+    }
+}
+
+/// The service created by [`HttpServerTracing`].
+#[derive(Debug)]
+pub struct HttpServerTracingService<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for HttpServerTracingService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let route = request.match_pattern();
+        let span =
+            spans::http_server_span(request.method().as_str(), route.as_deref(), request.path());
+        span.set_parent(global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(request.headers()))
+        }));
+        let future = span.in_scope(|| self.service.call(request));
+        Box::pin(
+            async move {
+                let span = tracing::Span::current();
+                let result = future.await;
+                match &result {
+                    Ok(response) => {
+                        spans::record_http_status(&span, response.status().as_u16());
+                        if let Some(error) = response.response().error() {
+                            spans::record_error(&span, error);
+                        }
+                    }
+                    Err(error) => spans::record_error(&span, error),
+                }
+                result
+            }
+            .instrument(span),
+        )
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::attribute;
+    use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+    use opentelemetry::{
+        trace::{SpanId, Status, TraceId},
+        Value,
+    };
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[tokio::test]
+    async fn test_records_server_spans() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let (layer, spans) = crate::builder("actix").build_for_test();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+        let app = test::init_service(
+            App::new()
+                .wrap(HttpServerTracing)
+                .route("/users/{id}", web::get().to(HttpResponse::Ok))
+                .route(
+                    "/fail",
+                    web::get().to(|| async {
+                        Err::<HttpResponse, _>(actix_web::error::ErrorInternalServerError("boom"))
+                    }),
+                ),
+        )
+        .await;
+
+        let response =
+            test::call_service(&app, test::TestRequest::get().uri("/users/1").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = test::TestRequest::get()
+            .uri("/fail")
+            .insert_header((
+                "traceparent",
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            ))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let span = spans.span("GET /users/{id}").expect("span was recorded");
+        assert_eq!(
+            attribute(&span, "http.request.method"),
+            Some(&Value::from("GET"))
+        );
+        assert_eq!(
+            attribute(&span, "http.route"),
+            Some(&Value::from("/users/{id}"))
+        );
+        assert_eq!(attribute(&span, "url.path"), Some(&Value::from("/users/1")));
+        assert_eq!(
+            attribute(&span, "http.response.status_code"),
+            Some(&Value::I64(200))
+        );
+        assert!(!matches!(span.status, Status::Error { .. }));
+        assert_eq!(span.parent_span_id, SpanId::INVALID);
+
+        let span = spans.span("GET /fail").expect("span was recorded");
+        assert_eq!(
+            attribute(&span, "http.response.status_code"),
+            Some(&Value::I64(500))
+        );
+        assert!(matches!(span.status, Status::Error { .. }));
+        assert_eq!(
+            span.span_context.trace_id(),
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").expect("valid trace id")
+        );
+        assert_eq!(
+            span.parent_span_id,
+            SpanId::from_hex("b7ad6b7169203331").expect("valid span id")
+        );
+    }
+}
@@ -1,24 +1,132 @@
-use crate::Error;
-use opentelemetry::{Key, KeyValue, Value};
+use crate::{
+    collapse::Collapse,
+    diagnostics::{self, Diagnostics},
+    export::{
+        AttributeTransformer, BatchSplitter, BoxedSpanProcessor, CircuitBreaker, DatasetRouter,
+        EventEnricher, ExportPipeline, Failover, FieldLimits, OtlpJsonExporter, RouteDataset,
+        SharedDataset, SpanNameMapper, Transforms,
+    },
+    fields::{BurstSuppression, FieldsLayer},
+    filter::AxiomFilter,
+    health::ExportHealth,
+    ingest::Ingester,
+    min_duration::MinDuration,
+    propagation::{BaggageProcessor, XrayIdGenerator},
+    queue::{Counters, Queue},
+    redaction::Redaction,
+    reload::{Authorize, SharedToken},
+    runtime::{BatchRuntime, FixedEndpoint, SharedHttpClient, SuppressTracing},
+    sampling::ForcedSampling,
+    tail::{TailLimits, TailPolicy, TailSampler},
+    testing::CapturedSpans,
+    Backpressure, Encoding, Error, ExportedEvent, Health, InternalLog, Propagation, QueueStats,
+    ReloadHandle,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{reload::RemoteSampling, runtime::BackgroundRuntime, stdout::StdoutExporter};
+use http::Uri;
+use opentelemetry::{
+    global,
+    trace::{TraceId, TracerProvider as _},
+    InstrumentationLibrary, Key, KeyValue, Value,
+};
+use opentelemetry_http::HttpClient;
 use opentelemetry_otlp::WithExportConfig;
+#[cfg(not(target_arch = "wasm32"))]
+use opentelemetry_sdk::trace::SimpleSpanProcessor;
 use opentelemetry_sdk::{
-    trace::{Config as TraceConfig, Tracer},
+    export::trace::SpanExporter,
+    runtime::RuntimeChannel,
+    trace::{
+        Config as TraceConfig, IdGenerator, RandomIdGenerator, Sampler, SpanProcessor, Tracer,
+        TracerProvider,
+    },
     Resource,
 };
+#[cfg(not(target_arch = "wasm32"))]
+use opentelemetry_semantic_conventions::resource::CONTAINER_ID;
 use opentelemetry_semantic_conventions::resource::{
-    SERVICE_NAME, TELEMETRY_SDK_LANGUAGE, TELEMETRY_SDK_NAME, TELEMETRY_SDK_VERSION,
+    DEPLOYMENT_ENVIRONMENT, SERVICE_INSTANCE_ID, SERVICE_NAME, SERVICE_VERSION,
+    TELEMETRY_SDK_LANGUAGE, TELEMETRY_SDK_NAME, TELEMETRY_SDK_VERSION,
 };
 use reqwest::Url;
 use std::{
+    borrow::Cow,
     collections::HashMap,
     env::{self, VarError},
+    sync::{Arc, OnceLock},
     time::Duration,
 };
+use tracing_core::Level;
 use tracing_core::Subscriber;
 use tracing_opentelemetry::OpenTelemetryLayer;
-use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{
+    filter::{Filtered, LevelFilter},
+    layer::Layered,
+    registry::LookupSpan,
+    Layer as _,
+};
+
+/// The URL of Axiom cloud.
+pub(crate) const CLOUD_URL: &str = "https://api.axiom.co";
+/// The OTLP/HTTP path for traces.
+const TRACES_PATH: &str = "/v1/traces";
+const BUILD_GIT_SHA: &str = "build.git_sha";
+const BUILD_TIMESTAMP: &str = "build.timestamp";
+
+/// How finished spans are handed to the exporter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Processor {
+    /// Spans are batched and exported periodically by a background task.
+    #[default]
+    Batch,
+    /// Spans are exported one by one as soon as they end.
+    #[cfg(not(target_arch = "wasm32"))]
+    Simple,
+}
+
+/// Settings of the default HTTP clients.
+#[derive(Debug, Default, Clone, Copy)]
+struct ClientOptions {
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    tcp_keepalive: Option<Duration>,
+}
 
-const CLOUD_URL: &str = "https://api.axiom.co";
+#[cfg(not(target_arch = "wasm32"))]
+impl ClientOptions {
+    /// The async client, with `timeout` unless the timeouts were set separately.
+    fn client(self, timeout: Duration) -> reqwest::Result<reqwest::Client> {
+        let mut client = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout.unwrap_or(timeout))
+            .timeout(self.request_timeout.unwrap_or(timeout))
+            .tcp_keepalive(self.tcp_keepalive);
+        if let Some(pool_idle_timeout) = self.pool_idle_timeout {
+            client = client.pool_idle_timeout(pool_idle_timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            client = client.pool_max_idle_per_host(max);
+        }
+        client.build()
+    }
+
+    /// The blocking client, which must not be created within a tokio runtime.
+    fn blocking_client(self, timeout: Duration) -> reqwest::Result<reqwest::blocking::Client> {
+        let mut client = reqwest::blocking::Client::builder()
+            .connect_timeout(self.connect_timeout.unwrap_or(timeout))
+            .timeout(self.request_timeout.unwrap_or(timeout))
+            .tcp_keepalive(self.tcp_keepalive);
+        if let Some(pool_idle_timeout) = self.pool_idle_timeout {
+            client = client.pool_idle_timeout(pool_idle_timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            client = client.pool_max_idle_per_host(max);
+        }
+        client.build()
+    }
+}
 
 /// Builder for creating a tracing tracer, a layer or a subscriber that sends traces to
 /// Axiom via the `OpenTelemetry` protocol. The API token is read from the `AXIOM_TOKEN`
@@ -26,18 +134,161 @@ const CLOUD_URL: &str = "https://api.axiom.co";
 /// variable. The URL defaults to Axiom Cloud whose URL is `https://cloud.axiom.co` but
 /// can be overridden by setting the `AXIOM_URL` environment variable for testing purposes
 ///
+/// Spans with an `axiom.sample = true` field keep their whole trace and ones with
+/// `axiom.sample = false` drop it, overriding the sampler and
+/// [`Builder::errors_only`], e.g. to guarantee capture for a debug session. The field
+/// must be set when the span is created or recorded before its first child is, and
+/// the decision is passed on to downstream services in the `tracestate` header.
+///
+/// ```rust
+/// tracing::info_span!("checkout", axiom.sample = true);
+/// ```
 #[derive(Debug, Default)]
 pub struct Builder {
     dataset_name: Option<String>,
     token: Option<String>,
     url: Option<Url>,
+    failover_urls: Vec<Url>,
+    traces_path: Option<String>,
+    encoding: Encoding,
     tags: Vec<KeyValue>,
+    resource: Option<Resource>,
+    ingest_resource_prefix: String,
     trace_config: Option<TraceConfig>,
     service_name: Option<String>,
+    service_version: Option<String>,
+    instrumentation_scope: Option<InstrumentationLibrary>,
+    git_sha: Option<String>,
+    build_timestamp: Option<String>,
+    service_instance_id: Option<String>,
+    environment: Option<String>,
+    env_prefix: Option<String>,
     timeout: Option<Duration>,
+    client_options: ClientOptions,
+    max_batch_bytes: Option<usize>,
+    propagation: Option<Propagation>,
+    baggage: bool,
+    user_agent_suffix: Option<String>,
+    id_generator: Option<Box<dyn IdGenerator>>,
+    processor: Processor,
+    runtime: Option<BatchRuntime>,
+    queue: Queue,
+    #[cfg(feature = "prometheus")]
+    metrics: Option<crate::metrics::ExportMetrics>,
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    metrics_dataset: Option<String>,
+    #[cfg(all(feature = "sysinfo", not(target_arch = "wasm32")))]
+    system_metrics: Option<(String, Duration)>,
+    http_client: Option<SharedHttpClient>,
+    #[cfg(not(target_arch = "wasm32"))]
+    dry_run: bool,
+    local_collector: bool,
+    lenient: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    eager_connect: bool,
+    disabled: bool,
+    filter: AxiomFilter,
+    export_tracing: bool,
+    transforms: Transforms,
+    fields: FieldsLayer,
+    location: Option<bool>,
+    threads: Option<bool>,
+    max_attributes_per_span: Option<u32>,
+    max_events_per_span: Option<u32>,
+    max_links_per_span: Option<u32>,
+    span_processors: Vec<BoxedSpanProcessor>,
+    exporter: Option<Box<dyn SpanExporter>>,
+    secondary_endpoint: Option<(Url, HashMap<String, String>)>,
+    circuit_breaker: Option<CircuitBreaker>,
+    fallback: Option<Box<dyn SpanExporter>>,
+    internal_logging: Option<Level>,
+    internal_log: Option<InternalLog>,
+    reload: Option<ReloadHandle>,
+    #[cfg(not(target_arch = "wasm32"))]
+    remote_sampling: Option<RemoteSampling>,
+    tail_policy: Option<TailPolicy>,
+    tail_limits: Option<TailLimits>,
+    min_span_duration: Option<Duration>,
+    span_collapsing: Option<usize>,
+}
+
+/// Owns the tracer and meter providers of a layer built with
+/// [`Builder::build_isolated`], which shut down when the guard is dropped.
+#[derive(Debug)]
+#[must_use = "the layer stops recording when the guard is dropped"]
+pub struct ProviderGuard {
+    provider: TracerProvider,
+    state: PipelineState,
+}
+
+impl ProviderGuard {
+    /// The tracer provider, e.g. to create more tracers.
+    #[must_use]
+    pub fn provider(&self) -> &TracerProvider {
+        &self.provider
+    }
+
+    /// The health of the export of this pipeline, like [`health`](crate::health)
+    /// reports it for the most recently built global one.
+    #[must_use]
+    pub fn health(&self) -> Health {
+        self.state.health.snapshot(self.queue_stats())
+    }
+
+    /// The export queue of this pipeline, like [`queue_stats`](crate::queue_stats)
+    /// reports it for the most recently built global one. `None` if spans are
+    /// not exported in batches.
+    #[must_use]
+    pub fn queue_stats(&self) -> Option<QueueStats> {
+        self.state
+            .counters
+            .as_ref()
+            .map(|counters| counters.stats())
+    }
+
+    /// Exports all spans that have ended but were not exported yet and waits
+    /// for the export to finish. The metrics of the layer are exported as well.
+    ///
+    /// # Errors
+    ///
+    /// Errors if exporting any of the spans or metrics failed.
+    pub fn force_flush(&self) -> Result<(), Error> {
+        for result in self.provider.force_flush() {
+            result?;
+        }
+        #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+        for provider in &self.state.meter_providers {
+            provider.force_flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+impl Drop for ProviderGuard {
+    fn drop(&mut self) {
+        for provider in &self.state.meter_providers {
+            if let Err(error) = provider.shutdown() {
+                diagnostics::report(Level::ERROR, &error.to_string());
+            }
+        }
+    }
+}
+
+/// The export health and queue counters of a built tracer provider, and the
+/// meter providers built with it.
+#[derive(Debug, Default)]
+struct PipelineState {
+    health: Arc<ExportHealth>,
+    counters: Option<Arc<Counters>>,
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    meter_providers: Vec<opentelemetry_sdk::metrics::SdkMeterProvider>,
 }
 
-fn get_env(env_var_name: &'static str) -> Result<Option<String>, Error> {
+pub(crate) type AxiomLayer<S> =
+    Filtered<Layered<FieldsLayer, OpenTelemetryLayer<S, Tracer>, S>, AxiomFilter, S>;
+
+fn get_env(env_var_name: &str) -> Result<Option<String>, Error> {
     match env::var(env_var_name) {
         Ok(maybe_ok_var) => Ok(Some(maybe_ok_var)),
         Err(VarError::NotPresent) => Ok(None),
@@ -45,11 +296,74 @@ fn get_env(env_var_name: &'static str) -> Result<Option<String>, Error> {
     }
 }
 
+/// The token if it is an Axiom API token.
+pub(crate) fn validate_token(token: String) -> Result<String, Error> {
+    if token.is_empty() {
+        Err(Error::EmptyToken)
+    } else if !token.starts_with("xaat-") {
+        Err(Error::InvalidToken)
+    } else {
+        Ok(token)
+    }
+}
+
+/// Parses a comma separated list of `key=value` pairs as used by `AXIOM_TAGS`.
+fn parse_tags(tags: &str) -> Result<Vec<KeyValue>, Error> {
+    tags.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| match tag.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok(KeyValue::new(
+                key.trim().to_string(),
+                value.trim().to_string(),
+            )),
+            _ => Err(Error::InvalidTag(tag.to_string())),
+        })
+        .collect()
+}
+
+/// A random UUID (version 4) that stays the same for the lifetime of the
+/// process, the default `service.instance.id`.
+fn process_instance_id() -> &'static str {
+    static INSTANCE_ID: OnceLock<String> = OnceLock::new();
+    INSTANCE_ID.get_or_init(|| {
+        let mut bytes = RandomIdGenerator::default().new_trace_id().to_bytes();
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex = TraceId::from_bytes(bytes).to_string();
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    })
+}
+
 impl Builder {
     /// Set the Axiom dataset name to use. The dataset name is the name of the
     /// persistent dataset in Axiom cloud that will store the traces and make
     /// them available for querying using APL, the Axiom SDK or the Axiom CLI.
     ///
+    /// The name can contain placeholders which are resolved when the layer is
+    /// built: `{env}` with the environment, `{service}` with the service
+    /// name, `{version}` with the service version, and any other `{NAME}` with
+    /// the `NAME` environment variable, e.g. `traces-{env}-{service}`.
+    ///
+    /// Spans with an `axiom.dataset` field are sent to that dataset instead,
+    /// e.g. to keep the data of tenants apart. To route a whole trace, add the
+    /// field to its root span with [`scope`](crate::scope) so all spans within
+    /// it inherit it:
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn charge(invoice_id: u64) {
+    ///     tracing_axiom::scope().with_field("axiom.dataset", "billing");
+    /// }
+    /// ```
+    ///
     /// # Errors
     /// If the dataset name is empty.
     pub fn with_dataset(mut self, dataset_name: impl Into<String>) -> Result<Self, Error> {
@@ -67,15 +381,8 @@ impl Builder {
     /// # Errors
     /// If the token is empty or does not start with `xaat-` (aka is not a api token).
     pub fn with_token(mut self, token: impl Into<String>) -> Result<Self, Error> {
-        let token: String = token.into();
-        if token.is_empty() {
-            Err(Error::EmptyToken)
-        } else if !token.starts_with("xaat-") {
-            Err(Error::InvalidToken)
-        } else {
-            self.token = Some(token);
-            Ok(self)
-        }
+        self.token = Some(validate_token(token.into())?);
+        Ok(self)
     }
 
     /// Set the Axiom API URL to use. Defaults to Axiom Cloud. When not set Axiom Cloud is used.
@@ -87,6 +394,147 @@ impl Builder {
         Ok(self)
     }
 
+    /// Send spans to an OpenTelemetry collector or Vector on the same host at
+    /// `http://localhost:{port}`, e.g. a sidecar that forwards them to Axiom,
+    /// usually on port 4318. No token or dataset is required, if they are set
+    /// they are still sent along.
+    #[must_use]
+    pub fn with_local_collector(mut self, port: u16) -> Self {
+        self.url = Some(
+            format!("http://localhost:{port}")
+                .parse()
+                .expect("this is a valid URL"),
+        );
+        self.local_collector = true;
+        self
+    }
+
+    /// Add a URL to fail over to when exporting to the Axiom URL and the
+    /// previously added ones fails, e.g. another region or a collector on the
+    /// host. URLs are tried in the order they were added with the same token
+    /// and dataset. A URL that failed is skipped for 30 seconds, after which
+    /// exports fail back to it. Has no effect with [`Builder::with_exporter`].
+    ///
+    /// # Errors
+    /// If the URL is not a valid URL.
+    pub fn with_failover_url(mut self, url: &str) -> Result<Self, Error> {
+        self.failover_urls.push(url.parse()?);
+        Ok(self)
+    }
+
+    /// Fetch sampling rules from the URL every `interval` and apply them, to
+    /// control the ingest volume of many services in one place. Until the
+    /// first fetch succeeds the sampler of the trace config is used, and if a
+    /// later one fails the previous rules stay in place.
+    ///
+    /// The URL must respond to `GET` requests with rules like
+    /// `{"ratio": 0.1, "targets": {"my_app::db": 0.01}, "errors": 1.0}`:
+    /// - `ratio`: the ratio of new traces to sample, `1.0` if omitted
+    /// - `targets`: ratios for spans in a module and its submodules, the most
+    ///   specific one applies, requires [`Builder::with_location`]
+    /// - `errors`: the ratio for spans that recorded an error before they were
+    ///   sampled, which is when their first child is created or they close
+    ///
+    /// Spans with a parent follow the sampling decision of the parent. The
+    /// rules replace the sampler of a [`ReloadHandle`]. The rules stop being
+    /// fetched once the tracer provider is dropped, e.g. on shutdown.
+    ///
+    /// # Errors
+    /// If the URL is not a valid URL.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_remote_sampling(mut self, url: &str, interval: Duration) -> Result<Self, Error> {
+        self.remote_sampling = Some(RemoteSampling {
+            url: url.parse()?,
+            interval,
+        });
+        Ok(self)
+    }
+
+    /// Only export traces that contain a failed span, e.g. with an `error!`
+    /// event or an `otel.status_code` of `ERROR`, to keep error forensics at
+    /// a fraction of the ingest volume. The spans of a trace are held back
+    /// until its root span in this process ends and are then exported or
+    /// dropped together. The sampler of the trace config still applies first.
+    #[must_use]
+    pub fn errors_only(mut self) -> Self {
+        self.tail_policy
+            .get_or_insert_with(TailPolicy::default)
+            .errors = true;
+        self
+    }
+
+    /// Always export traces whose root span in this process took at least
+    /// `threshold`, and `ratio` of the remaining ones, e.g. `0.01` for 1%.
+    /// Slow outliers are kept, which uniform sampling mostly drops. Like with
+    /// [`Builder::errors_only`], the spans of a trace are held back until its
+    /// root span ends, and both can be combined.
+    #[must_use]
+    pub fn with_latency_sampling(mut self, threshold: Duration, ratio: f64) -> Self {
+        let policy = self.tail_policy.get_or_insert_with(TailPolicy::default);
+        policy.min_duration = Some(threshold);
+        policy.ratio = ratio;
+        self
+    }
+
+    /// Export the spans of a trace together: they are held back until its
+    /// root span in this process ends, also without [`Builder::errors_only`]
+    /// or [`Builder::with_latency_sampling`]. To not leak memory for traces
+    /// that never end, e.g. of a long-lived worker or a crashed client, a
+    /// trace that buffered `max_spans` spans, or whose root span is still
+    /// running after `max_wait`, is decided with the spans that ended so far,
+    /// and its later spans are held back again. Defaults to 5 minutes and
+    /// 10000 spans.
+    #[must_use]
+    pub fn with_tail_sampling_limits(mut self, max_wait: Duration, max_spans: usize) -> Self {
+        self.tail_limits = Some(TailLimits {
+            max_wait,
+            max_spans: max_spans.max(1),
+        });
+        self
+    }
+
+    /// Drop spans that took less than `min_duration`, to cut the noise of
+    /// micro-spans like cache lookups. Spans are only dropped if the trace
+    /// stays intact, so root spans, spans with children and failed spans are
+    /// exported however short they are.
+    #[must_use]
+    pub fn with_min_span_duration(mut self, min_duration: Duration) -> Self {
+        self.min_span_duration = Some(min_duration);
+        self
+    }
+
+    /// Collapse runs of at least `min_run` consecutive child spans with the
+    /// same name under one parent, like the thousands of `row_decode` spans
+    /// an ORM records per query, into a single span. It spans the whole run
+    /// and gets the attributes `collapsed.count`, `collapsed.duration_ms.total`
+    /// and `collapsed.duration_ms.max`. Failed spans and spans with children
+    /// are never collapsed and end the run.
+    #[must_use]
+    pub fn with_span_collapsing(mut self, min_run: usize) -> Self {
+        self.span_collapsing = Some(min_run);
+        self
+    }
+
+    /// Send spans to the path under the URL instead of the OTLP default
+    /// `/v1/traces`, e.g. to a gateway that forwards `/custom/v1/traces` to
+    /// Axiom. Applies to the Axiom URL and the failover URLs.
+    #[must_use]
+    pub fn with_traces_path(mut self, path: impl Into<String>) -> Self {
+        self.traces_path = Some(path.into());
+        self
+    }
+
+    /// Set how spans are encoded when they are sent over HTTP, by default
+    /// [`Encoding::Protobuf`]. [`Encoding::Json`] is larger, but can be
+    /// inspected by proxies and firewalls and is accepted by collectors
+    /// without protobuf support. Applies to the Axiom URL, the failover URLs
+    /// and the secondary endpoint.
+    #[must_use]
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
     /// Set the trace config.
     #[must_use]
     pub fn with_trace_config(mut self, trace_config: impl Into<TraceConfig>) -> Self {
@@ -94,6 +542,30 @@ impl Builder {
         self
     }
 
+    /// Set the maximum number of attributes per span, more are dropped. The
+    /// default is 128.
+    #[must_use]
+    pub fn with_max_attributes_per_span(mut self, max: u32) -> Self {
+        self.max_attributes_per_span = Some(max);
+        self
+    }
+
+    /// Set the maximum number of events per span, more are dropped. The default
+    /// is 128, verbose spans with many `tracing` events may need more.
+    #[must_use]
+    pub fn with_max_events_per_span(mut self, max: u32) -> Self {
+        self.max_events_per_span = Some(max);
+        self
+    }
+
+    /// Set the maximum number of links per span, more are dropped. The default
+    /// is 128.
+    #[must_use]
+    pub fn with_max_links_per_span(mut self, max: u32) -> Self {
+        self.max_links_per_span = Some(max);
+        self
+    }
+
     /// Set the service name. It will be set as a resource attribute with the
     /// name `service_name`.
     #[must_use]
@@ -102,8 +574,75 @@ impl Builder {
         self
     }
 
-    /// Set the resource tags for the open telemetry tracer that publishes to Axiom.
-    /// These tags will be added to all spans.
+    /// Set the service version, usually `env!("CARGO_PKG_VERSION")`. It will be
+    /// set as a resource attribute with the name `service.version`.
+    #[must_use]
+    pub fn with_service_version(mut self, service_version: impl Into<String>) -> Self {
+        self.service_version = Some(service_version.into());
+        self
+    }
+
+    /// Set the instrumentation scope of the spans recorded by the layer,
+    /// `tracing-axiom` and its version by default. Give libraries sharing a
+    /// tracer provider their own layer with their own scope to tell their
+    /// spans apart in Axiom.
+    #[must_use]
+    pub fn with_instrumentation_scope(
+        mut self,
+        name: impl Into<String>,
+        version: Option<impl Into<String>>,
+        schema_url: Option<impl Into<String>>,
+    ) -> Self {
+        self.instrumentation_scope = Some(InstrumentationLibrary::new(
+            name.into(),
+            version.map(Into::into),
+            schema_url.map(Into::into),
+            None,
+        ));
+        self
+    }
+
+    /// Set build information as resource attributes: the git commit SHA as
+    /// `build.git_sha` and the build timestamp as `build.timestamp`. Both are
+    /// optional so they can be fed straight from `option_env!`:
+    ///
+    /// ```rust
+    /// let builder = tracing_axiom::builder("my-service")
+    ///     .with_service_version(env!("CARGO_PKG_VERSION"))
+    ///     .with_build_info(option_env!("GIT_SHA"), option_env!("BUILD_TIMESTAMP"));
+    /// ```
+    #[must_use]
+    pub fn with_build_info(
+        mut self,
+        git_sha: Option<impl Into<String>>,
+        build_timestamp: Option<impl Into<String>>,
+    ) -> Self {
+        self.git_sha = git_sha.map(Into::into);
+        self.build_timestamp = build_timestamp.map(Into::into);
+        self
+    }
+
+    /// Set the ID of this instance of the service, to tell replicas apart. It
+    /// will be set as a resource attribute with the name `service.instance.id`.
+    /// Defaults to a random UUID generated once per process, unless the
+    /// resource or tags already set one.
+    #[must_use]
+    pub fn with_service_instance_id(mut self, instance_id: impl Into<String>) -> Self {
+        self.service_instance_id = Some(instance_id.into());
+        self
+    }
+
+    /// Set the deployment environment (e.g. `prod`, `staging` or `dev`). It will be
+    /// set as a resource attribute with the name `deployment.environment`.
+    #[must_use]
+    pub fn with_environment(mut self, environment: impl Into<String>) -> Self {
+        self.environment = Some(environment.into());
+        self
+    }
+
+    /// Add resource tags for the open telemetry tracer that publishes to Axiom.
+    /// These tags will be added to all spans. Calling this multiple times adds
+    /// to the previously set tags.
     #[must_use]
     pub fn with_tags<T, K, V>(mut self, tags: T) -> Self
     where
@@ -111,105 +650,1799 @@ impl Builder {
         V: Into<Value>,
         T: Iterator<Item = (K, V)>,
     {
-        self.tags = tags.map(|(k, v)| KeyValue::new(k, v)).collect::<Vec<_>>();
+        self.tags.extend(tags.map(|(k, v)| KeyValue::new(k, v)));
         self
     }
 
-    /// Sets the collector timeout for the OTLP exporter.
-    /// The default is 3 seconds.
-    ///
+    /// Add a single resource tag, see [`Builder::with_tags`].
     #[must_use]
-    pub fn with_timeout(mut self, timeout: Duration) -> Self {
-        self.timeout = Some(timeout);
+    pub fn add_tag(mut self, key: impl Into<Key>, value: impl Into<Value>) -> Self {
+        self.tags.push(KeyValue::new(key, value));
         self
     }
 
-    /// Load defaults from environment variables, if variables were set before this call they will not be replaced.
-    ///
-    /// The following environment variables are used:
-    /// - `AXIOM_TOKEN`
-    /// - `AXIOM_DATASET`
-    /// - `AXIOM_URL`
+    /// Set a pre-built [`Resource`] for the open telemetry tracer that publishes to Axiom.
+    /// It is merged with the resource generated from the builder settings, where the
+    /// generated attributes (service name, environment, tags, ...) take precedence.
+    /// Calling this multiple times merges the resources, later ones taking precedence.
     ///
-    /// # Errors
-    /// If an environment variable is not valid UTF8, or any of their values are invalid.
-    pub fn with_env(mut self) -> Result<Self, Error> {
-        if self.token.is_none() {
-            if let Some(t) = get_env("AXIOM_TOKEN")? {
-                self = self.with_token(t)?;
-            }
-        };
+    /// Detected attributes, the `service.instance.id` and the `container.id`
+    /// read from the cgroup files of the process in a container, are only
+    /// added if neither the resource nor the tags set them.
+    #[must_use]
+    pub fn with_resource(mut self, resource: Resource) -> Self {
+        self.resource = Some(match self.resource {
+            Some(existing) => existing.merge(&resource),
+            None => resource,
+        });
+        self
+    }
 
-        if self.dataset_name.is_none() {
-            if let Some(d) = get_env("AXIOM_DATASET")? {
-                self = self.with_dataset(d)?;
-            }
-        };
-        if self.url.is_none() {
-            if let Some(u) = get_env("AXIOM_URL")? {
-                self = self.with_url(&u)?;
-            }
-        };
+    /// Set the global text map propagator when building, so trace context is
+    /// extracted from and injected into requests of other services.
+    #[must_use]
+    pub fn with_propagator(mut self, propagation: Propagation) -> Self {
+        self.propagation = Some(propagation);
+        self
+    }
 
-        Ok(self)
+    /// Copy the entries of the `OpenTelemetry` baggage with these keys onto
+    /// every span as attributes, e.g. `tenant_id` to filter by tenant across
+    /// services. The baggage of the parent context is used, including the one
+    /// set with `OpenTelemetrySpanExt::set_parent`. The propagator set with
+    /// [`Builder::with_propagator`] also extracts and injects the W3C
+    /// `baggage` header then. Can be called multiple times.
+    #[must_use]
+    pub fn with_baggage_keys<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<Key>,
+    {
+        let keys = keys.into_iter().map(Into::into).collect();
+        self.span_processors
+            .push(BoxedSpanProcessor(Box::new(BaggageProcessor::new(keys))));
+        self.baggage = true;
+        self
     }
 
-    /// Create a layer which sends traces to Axiom that can be added to the tracing layers.
+    /// Use [W3C Trace Context](https://www.w3.org/TR/trace-context/) propagation,
+    /// a shorthand for `with_propagator(Propagation::TraceContext)`.
+    #[must_use]
+    pub fn with_trace_context_propagation(self) -> Self {
+        self.with_propagator(Propagation::TraceContext)
+    }
+
+    /// Make trace ids and propagation compatible with AWS X-Ray, so traces entering
+    /// via an ALB or other X-Ray instrumented services are continued. This uses
+    /// the `X-Amzn-Trace-Id` header for propagation and generates trace ids that
+    /// start with the current unix time.
+    #[must_use]
+    pub fn with_xray_compat(mut self) -> Self {
+        self.id_generator = Some(Box::<XrayIdGenerator>::default());
+        self.with_propagator(Propagation::XRay)
+    }
+
+    /// Set the generator for trace and span ids, e.g. a deterministic one to
+    /// correlate runs in replay or simulation environments. Overrides the one
+    /// set by [`Builder::with_xray_compat`] and the trace config.
+    #[must_use]
+    pub fn with_id_generator(mut self, id_generator: impl IdGenerator + 'static) -> Self {
+        self.id_generator = Some(Box::new(id_generator));
+        self
+    }
+
+    /// Add a span processor that runs alongside the one exporting to Axiom, e.g.
+    /// to enrich spans when they start or to send them to a second exporter.
+    /// Can be called multiple times, processors run in the order they were
+    /// added and before the Axiom exporter. Finished spans are passed to them
+    /// as recorded, without [`Builder::with_redaction`] applied.
+    #[must_use]
+    pub fn with_span_processor(mut self, processor: impl SpanProcessor + 'static) -> Self {
+        self.span_processors
+            .push(BoxedSpanProcessor(Box::new(processor)));
+        self
+    }
+
+    /// Replace the exporter that sends spans to Axiom, e.g. to wrap it with
+    /// encryption before the data leaves the host. The resource, tags,
+    /// redaction and processor settings still apply, no token or dataset is
+    /// required.
+    #[must_use]
+    pub fn with_exporter(mut self, exporter: impl SpanExporter + 'static) -> Self {
+        self.exporter = Some(Box::new(exporter));
+        self
+    }
+
+    /// Also send every batch to a second OTLP/HTTP endpoint, e.g. an
+    /// OpenTelemetry collector in the cluster while migrating to or from
+    /// Axiom. The headers are sent with every request to that endpoint, the
+    /// Axiom token and dataset are not. Spans are redacted and truncated the
+    /// same way for both. Failures of the second endpoint don't fail the
+    /// export to Axiom, they are printed to stderr or reported to the internal
+    /// log if one is set.
     ///
     /// # Errors
-    ///
-    /// Returns an error if any of the settings are not valid
-    pub fn build<S>(self) -> Result<OpenTelemetryLayer<S, Tracer>, Error>
+    /// If the URL is not a valid URL.
+    pub fn with_secondary_endpoint<K, V>(
+        mut self,
+        url: &str,
+        headers: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self, Error>
     where
-        S: Subscriber + for<'span> LookupSpan<'span>,
+        K: Into<String>,
+        V: Into<String>,
     {
-        Ok(tracing_opentelemetry::layer().with_tracer(self.tracer()?))
+        let headers = headers
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+        self.secondary_endpoint = Some((url.parse()?, headers));
+        Ok(self)
     }
 
-    fn tracer(self) -> Result<Tracer, Error> {
-        let token = self.token.ok_or(Error::MissingToken)?;
-        let dataset_name = self.dataset_name.ok_or(Error::MissingDataset)?;
-        let url = self
-            .url
-            .unwrap_or_else(|| CLOUD_URL.to_string().parse().expect("this is a valid URL"));
+    /// Stop exporting to Axiom after `failures` consecutive exports failed, and
+    /// only retry with a single batch every `cooldown` until one succeeds.
+    /// Batches ending while the breaker is open are dropped, or sent to the
+    /// exporter set with [`Builder::with_circuit_breaker_fallback`]. Keeps a
+    /// long outage from burning CPU and connections on retries.
+    #[must_use]
+    pub fn with_circuit_breaker(mut self, failures: u32, cooldown: Duration) -> Self {
+        self.circuit_breaker = Some(CircuitBreaker::new(failures, cooldown));
+        self
+    }
+
+    /// Send batches to this exporter while the circuit breaker is open, e.g. a
+    /// [`FileExporter`](crate::file::FileExporter) to replay them later. Has no
+    /// effect without [`Builder::with_circuit_breaker`].
+    #[must_use]
+    pub fn with_circuit_breaker_fallback(mut self, exporter: impl SpanExporter + 'static) -> Self {
+        self.fallback = Some(Box::new(exporter));
+        self
+    }
+
+    /// Sets the collector timeout for the OTLP exporter, for both connecting
+    /// and the whole request unless set separately with
+    /// [`Builder::with_connect_timeout`] and [`Builder::with_request_timeout`].
+    /// The default is 3 seconds.
+    ///
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Prefix the names of the resource attributes (service name, environment,
+    /// tags) that [`ingest`](crate::ingest) adds to every event, e.g.
+    /// `resource.` to send `resource.service.name`. By default they are added
+    /// as top-level fields, as events sent that way have no resource of their
+    /// own.
+    #[must_use]
+    pub fn with_ingest_resource_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.ingest_resource_prefix = prefix.into();
+        self
+    }
+
+    /// Sets how long connecting to the collector may take, including DNS
+    /// resolution and the TLS handshake, so a slow connection set up fails
+    /// fast without limiting how long large batches may take to send. Only
+    /// applies to the default HTTP client.
+    #[must_use]
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.client_options.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long an export request may take from connecting until the
+    /// response was received. Only applies to the default HTTP client.
+    #[must_use]
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.client_options.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long idle connections to the collector are kept open to be
+    /// reused, by default 90 seconds. Set it below the idle timeout of NAT
+    /// gateways or proxies on the way, which drop connections silently and
+    /// fail the next export. Only applies to the default HTTP client.
+    #[must_use]
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.client_options.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how many idle connections to the collector are kept open, by
+    /// default without a limit. Only applies to the default HTTP client.
+    #[must_use]
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.client_options.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Sends TCP keep-alive probes on connections to the collector at the
+    /// interval, so NAT gateways keep idle connections open. Off by default.
+    /// Only applies to the default HTTP client.
+    #[must_use]
+    pub fn with_tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.client_options.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Split batches into several requests of at most about `max_bytes`, so
+    /// spans with large attributes don't make the whole batch too large to be
+    /// accepted. The size is estimated from the names and attributes of the
+    /// spans before they are encoded, a single span larger than the limit is
+    /// sent on its own. By default every batch is sent in one request.
+    #[must_use]
+    pub fn with_max_batch_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_batch_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Set the async runtime that drives the batch exporter in the background.
+    /// Defaults to [`Tokio`](opentelemetry_sdk::runtime::Tokio) when called
+    /// within a tokio runtime, otherwise a private current-thread runtime is
+    /// started on a background thread.
+    ///
+    /// The default HTTP client only works within a tokio runtime, with other
+    /// runtimes set a matching client with [`Builder::with_http_client`].
+    #[must_use]
+    pub fn with_runtime<R: RuntimeChannel>(mut self, runtime: R) -> Self {
+        self.runtime = Some(BatchRuntime::new(runtime));
+        self
+    }
+
+    /// Set what happens to spans that end while the export queue is full
+    /// because exports can't keep up, see [`Backpressure`]. By default the
+    /// span is dropped. Has no effect with [`Builder::serverless`], which
+    /// exports spans as they end.
+    #[must_use]
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.queue = std::mem::take(&mut self.queue).with_backpressure(backpressure);
+        self
+    }
+
+    /// Append the suffix to the `tracing-axiom/{version}` User-Agent of export
+    /// requests, e.g. `checkout/1.4.2`, so they can be told apart at egress
+    /// proxies and in the request logs of Axiom.
+    #[must_use]
+    pub fn with_user_agent_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.user_agent_suffix = Some(suffix.into());
+        self
+    }
+
+    /// Set how often batches of spans are exported, by default every 5 seconds
+    /// or the `OTEL_BSP_SCHEDULE_DELAY` in milliseconds. A short interval like
+    /// 200ms shows spans almost in real time, a long one like 10s sends fewer
+    /// requests. Batches are exported earlier once they are full. Has no effect
+    /// with [`Builder::serverless`], which exports spans as they end.
+    #[must_use]
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.queue = std::mem::take(&mut self.queue).with_flush_interval(interval);
+        self
+    }
+
+    /// Register metrics about the export with the registry when the layer is
+    /// built, to monitor the telemetry pipeline itself:
+    ///
+    /// - `tracing_axiom_batches_sent_total`
+    /// - `tracing_axiom_export_failures_total`, by HTTP `status` code or `error`
+    /// - `tracing_axiom_bytes_sent_total`
+    /// - `tracing_axiom_queue_spans`, `tracing_axiom_queue_capacity` and
+    ///   `tracing_axiom_dropped_spans`, see [`queue_stats`](crate::queue_stats)
+    ///
+    /// Building fails if they are registered already, e.g. by another layer.
+    #[cfg(feature = "prometheus")]
+    #[must_use]
+    pub fn with_prometheus_registry(mut self, registry: &prometheus::Registry) -> Self {
+        self.metrics = Some(crate::metrics::ExportMetrics::new(registry));
+        self
+    }
+
+    /// Send metrics recorded as event fields to the dataset, like the
+    /// `MetricsLayer` of `tracing-opentelemetry`: fields prefixed with
+    /// `monotonic_counter.` are added to a counter, `counter.` to a counter
+    /// that can go down and `histogram.` to a histogram, named after the rest
+    /// of the field. They are exported every minute with the same token, URL
+    /// and resource as the spans.
+    ///
+    /// ```rust
+    /// tracing::info!(monotonic_counter.orders = 1, histogram.order_value = 42.5, "order placed");
+    /// ```
+    ///
+    /// # Errors
+    /// If the dataset name is empty.
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    pub fn with_metrics(mut self, dataset_name: impl Into<String>) -> Result<Self, Error> {
+        let dataset_name: String = dataset_name.into();
+        if dataset_name.is_empty() {
+            return Err(Error::EmptyDataset);
+        }
+        self.metrics_dataset = Some(dataset_name);
+        Ok(self)
+    }
+
+    /// Report the CPU, memory and disk usage of the host to the dataset on the
+    /// interval, as the `system.cpu.utilization`, `system.memory.usage` and
+    /// `system.filesystem.usage` gauges of the `OpenTelemetry` semantic
+    /// conventions. They are exported with the same token, URL and resource
+    /// as the spans.
+    ///
+    /// ```rust
+    /// let builder = tracing_axiom::builder("my-service")
+    ///     .with_system_metrics("host-metrics", std::time::Duration::from_secs(30))?;
+    /// # Ok::<(), tracing_axiom::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    /// If the dataset name is empty.
+    #[cfg(all(feature = "sysinfo", not(target_arch = "wasm32")))]
+    pub fn with_system_metrics(
+        mut self,
+        dataset_name: impl Into<String>,
+        interval: Duration,
+    ) -> Result<Self, Error> {
+        let dataset_name: String = dataset_name.into();
+        if dataset_name.is_empty() {
+            return Err(Error::EmptyDataset);
+        }
+        self.system_metrics = Some((dataset_name, interval));
+        Ok(self)
+    }
+
+    /// Set the HTTP client used to send spans to Axiom. Defaults to a
+    /// [`reqwest::Client`].
+    #[must_use]
+    pub fn with_http_client(mut self, http_client: impl HttpClient + 'static) -> Self {
+        self.http_client = Some(SharedHttpClient(Arc::new(http_client)));
+        self
+    }
+
+    /// Redact sensitive attributes before spans are sent to Axiom, see
+    /// [`redaction`](crate::redaction).
+    #[must_use]
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.transforms.redaction = Some(redaction);
+        self
+    }
+
+    /// Rename spans before they are sent to Axiom, e.g. to normalize names
+    /// containing ids like `GET /users/42` into route templates like
+    /// `GET /users/:id` and keep the number of span names low. Returning the
+    /// name as [`Cow::Borrowed`] keeps it without an allocation.
+    ///
+    /// ```rust
+    /// use std::borrow::Cow;
+    ///
+    /// let builder = tracing_axiom::builder("my-service").with_span_name_mapper(|name| {
+    ///     match name.strip_prefix("GET /users/") {
+    ///         Some(_) => Cow::Borrowed("GET /users/:id"),
+    ///         None => Cow::Borrowed(name),
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_span_name_mapper<F>(mut self, mapper: F) -> Self
+    where
+        F: Fn(&str) -> Cow<'_, str> + Send + Sync + 'static,
+    {
+        self.transforms.span_name_mapper = Some(SpanNameMapper(Arc::new(mapper)));
+        self
+    }
+
+    /// Transform every attribute of spans and their events before they are
+    /// sent to Axiom, to rename it, convert its value, e.g. parse numbers
+    /// recorded as strings, or drop it by returning `None`. Runs before
+    /// [`with_redaction`](Self::with_redaction) so renamed attributes are
+    /// still redacted.
+    ///
+    /// ```rust
+    /// use opentelemetry::{Key, Value};
+    ///
+    /// let builder = tracing_axiom::builder("my-service").with_attribute_transformer(|key, value| {
+    ///     match key.as_str() {
+    ///         "internal.debug" => None,
+    ///         "latency_ms" => {
+    ///             let parsed = value.as_str().parse::<f64>().ok();
+    ///             Some((key, parsed.map_or(value, Value::F64)))
+    ///         }
+    ///         _ => Some((key, value)),
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_attribute_transformer<F>(mut self, transformer: F) -> Self
+    where
+        F: Fn(Key, Value) -> Option<(Key, Value)> + Send + Sync + 'static,
+    {
+        self.transforms.attribute_transformer = Some(AttributeTransformer(Arc::new(transformer)));
+        self
+    }
+
+    /// Add fields to every event before it is sent to Axiom, computed from the
+    /// fields of the event and its span, e.g. the location of a client IP
+    /// address or the state of a feature flag. Runs after
+    /// [`with_attribute_transformer`](Self::with_attribute_transformer) and
+    /// before redaction, truncation and field limits, which also apply to the
+    /// added fields.
+    ///
+    /// ```rust
+    /// let builder = tracing_axiom::builder("my-service").with_event_enricher(|event| {
+    ///     if event.span_attribute("user.id").is_some() {
+    ///         event.set_attribute("user.authenticated", true);
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_event_enricher<F>(mut self, enricher: F) -> Self
+    where
+        F: Fn(&mut ExportedEvent<'_>) + Send + Sync + 'static,
+    {
+        self.transforms.event_enricher = Some(EventEnricher(Arc::new(enricher)));
+        self
+    }
+
+    /// Truncate string attribute values and event messages longer than
+    /// `max_len` bytes before they are sent to Axiom. Truncated values end with
+    /// an ellipsis (`…`) and their keys are listed in the `truncated_fields`
+    /// attribute of the span, event or link, with `name` for an event
+    /// message. Useful to keep large payload dumps from slowing down
+    /// exports and hitting Axiom's field size limits.
+    #[must_use]
+    pub fn with_max_attribute_length(mut self, max_len: usize) -> Self {
+        self.transforms.max_attribute_length = Some(max_len);
+        self
+    }
+
+    /// Keep at most `max_fields` attributes per span and event, with names
+    /// nesting at most `max_depth` levels deep at their dots, to stay within
+    /// the field limits of the dataset. The attributes exceeding them are
+    /// folded into a single `overflow_fields` attribute holding them as a JSON
+    /// object, counted by [`folded_fields`](crate::folded_fields).
+    #[must_use]
+    pub fn with_field_limits(mut self, max_fields: usize, max_depth: usize) -> Self {
+        self.transforms.field_limits = Some(FieldLimits::new(max_fields, max_depth));
+        self
+    }
+
+    /// Set how fields recorded with [`valuable`](https://docs.rs/valuable) are
+    /// exported, flattened to one attribute per nested field by default.
+    ///
+    /// ```rust,ignore
+    /// let user = std::collections::BTreeMap::from([("id", 42), ("team_id", 7)]);
+    /// // Exported as `user.id` and `user.team_id` or as `user` with the JSON
+    /// // string `{"id":42,"team_id":7}`
+    /// tracing::info!(user = valuable::Valuable::as_value(&user), "signed in");
+    /// ```
+    #[cfg(feature = "valuable")]
+    #[must_use]
+    pub fn with_flattening(mut self, flattening: crate::Flattening) -> Self {
+        self.fields.flattening = flattening;
+        self
+    }
+
+    /// Include the `code.filepath`, `code.lineno` and `code.namespace`
+    /// attributes on every span and event, enabled by default. Disable them to
+    /// save bytes if the span names and targets are enough to find the code.
+    #[must_use]
+    pub fn with_location(mut self, enabled: bool) -> Self {
+        self.location = Some(enabled);
+        self
+    }
+
+    /// Record the `thread.name` and `thread.id` on spans and events, and the
+    /// `tokio.task.id` inside of tokio tasks, to debug runtime contention.
+    /// By default only spans have the thread attributes, disabling removes them.
+    #[must_use]
+    pub fn with_threads(mut self, enabled: bool) -> Self {
+        self.threads = Some(enabled);
+        self.fields.threads = enabled;
+        self
+    }
+
+    /// Also add the message of events as a `message` attribute, for queries
+    /// and dashboards that expect it there. By default it is only the name of
+    /// the event.
+    #[must_use]
+    pub fn with_event_message(mut self, enabled: bool) -> Self {
+        self.fields.event_message = enabled;
+        self
+    }
+
+    /// Also export a record of every span when it starts, not only when it
+    /// ends, so dashboards can show operations that are still running. The
+    /// records have the ids, name and attributes of the span as it started,
+    /// end right away and have a `span.state` attribute set to `started`. Span
+    /// processors added with [`Builder::with_span_processor`] see them too.
+    /// [`Builder::with_min_span_duration`] keeps them, and tail sampling
+    /// exports or drops them with the rest of their trace.
+    #[must_use]
+    pub fn with_span_start_records(mut self, enabled: bool) -> Self {
+        self.fields.start_records = enabled;
+        self
+    }
+
+    /// Export events recorded outside of any span in a span of their own,
+    /// named after the target of the event and ending right away, so they
+    /// show up in the traces dataset. Otherwise such events are dropped, as
+    /// events are exported as part of their span.
+    #[must_use]
+    pub fn with_orphan_event_spans(mut self, enabled: bool) -> Self {
+        self.fields.orphan_events = enabled;
+        self
+    }
+
+    /// Aggregate the rate, error rate and duration percentiles of all spans
+    /// per name, including the ones the sampler drops, and export them every
+    /// `interval` as spans named `span.summary`. Their `summary.span_name`,
+    /// `summary.count`, `summary.errors`, `summary.rate` (per second),
+    /// `summary.error_rate` and `summary.duration_ms.p50`, `.p90`, `.p99` and
+    /// `.max` attributes keep latency dashboards accurate with aggressive
+    /// sampling. Summary spans are never sampled out.
+    ///
+    /// The summaries of a window are exported when the first span closes
+    /// after it ended, or when the spans are flushed, e.g. with
+    /// [`force_flush`](crate::force_flush) or on shutdown, so a quiet service
+    /// doesn't hold the last one back.
+    #[must_use]
+    pub fn with_span_summaries(mut self, interval: Duration) -> Self {
+        self.fields.summaries = Some(Arc::new(crate::fields::SpanSummaries::new(interval)));
+        self
+    }
+
+    /// Use the message of the first `error!` event in a span as description of
+    /// its error status. Spans with `ERROR` events are always marked as failed,
+    /// but without a description, so Axiom shows why only in the events.
+    #[must_use]
+    pub fn with_error_status_description(mut self, enabled: bool) -> Self {
+        self.fields.error_status_description = enabled;
+        self
+    }
+
+    /// Export only every `one_in`th of identical events, with the same
+    /// callsite and message, once more than `threshold` of them were recorded
+    /// within `window`. Exported events get a `suppressed_count` attribute with
+    /// the number of identical events dropped since the previous one. Keeps
+    /// hot error loops from flooding the dataset.
+    #[must_use]
+    pub fn with_burst_suppression(mut self, threshold: u32, window: Duration, one_in: u32) -> Self {
+        self.fields.burst_suppression = Some(BurstSuppression::new(threshold, window, one_in));
+        self
+    }
+
+    /// Coalesce identical events, with the same callsite and message,
+    /// recorded on a span within `window` of the first one into that one. It
+    /// gets an `occurrences` attribute with the number of times it was
+    /// recorded, e.g. to see how often a request was retried without an event
+    /// per attempt.
+    #[must_use]
+    pub fn with_event_dedup(mut self, window: Duration) -> Self {
+        self.fields.dedup_window = Some(window);
+        self
+    }
+
+    /// Trace the HTTP requests that send spans to Axiom. By default tracing is
+    /// suppressed while they are sent, so instrumented HTTP clients (e.g.
+    /// `reqwest` or `hyper` spans) don't create spans for every export, which
+    /// would in turn be exported again.
+    #[must_use]
+    pub fn with_export_tracing(mut self, enabled: bool) -> Self {
+        self.export_tracing = enabled;
+        self
+    }
+
+    /// Report the crate's own problems, like failed exports or settings it fell
+    /// back from, up to the given verbosity to the internal log, see
+    /// [`Builder::with_internal_log`]. Failed exports are reported as errors,
+    /// fallbacks as `INFO` or `DEBUG`. Replaces the global error handler of
+    /// `opentelemetry`, which prints its errors to stderr by default.
+    #[must_use]
+    pub fn with_internal_logging(mut self, level: Level) -> Self {
+        self.internal_logging = Some(level);
+        self
+    }
+
+    /// Report the crate's own problems to stderr, a `tracing` target or a
+    /// callback, see [`InternalLog`]. Only warnings and errors are reported
+    /// unless set otherwise with [`Builder::with_internal_logging`].
+    #[must_use]
+    pub fn with_internal_log(mut self, log: InternalLog) -> Self {
+        self.internal_log = Some(log);
+        self
+    }
+
+    /// Run the batch exporter on the [`async-std`](https://docs.rs/async-std)
+    /// runtime instead of tokio. Requests are sent with a blocking client on the
+    /// blocking thread pool of `async-std`.
+    #[cfg(feature = "async-std")]
+    #[must_use]
+    pub fn with_async_std(self) -> Self {
+        self.with_runtime(opentelemetry_sdk::runtime::AsyncStd)
+            .with_http_client(crate::runtime::AsyncStdClient::new())
+    }
+
+    /// Only send the spans and events that pass the filter to Axiom, e.g. an
+    /// [`EnvFilter`](tracing_subscriber::EnvFilter) or a
+    /// [`LevelFilter`](tracing_subscriber::filter::LevelFilter). The filter only
+    /// applies to the Axiom layer, other layers like a local `fmt` layer still
+    /// see everything.
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter};
+    ///
+    /// let axiom_layer = tracing_axiom::builder_with_env("my-service")?
+    ///     .with_filter(EnvFilter::new("info,my_service=debug"))
+    ///     .build()?;
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(axiom_layer)
+    ///     .init();
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_filter(mut self, filter: impl Into<AxiomFilter>) -> Self {
+        self.filter.set(filter.into());
+        self
+    }
+
+    /// Only send spans and events of these targets (and their submodules) to
+    /// Axiom. Calling this multiple times adds to the previously set targets.
+    #[must_use]
+    pub fn with_include_targets<T>(mut self, targets: T) -> Self
+    where
+        T: IntoIterator,
+        T::Item: Into<String>,
+    {
+        for target in targets {
+            self.filter.include_target(target.into());
+        }
+        self
+    }
+
+    /// Don't send spans and events of these targets (and their submodules) to
+    /// Axiom, e.g. `["hyper", "h2", "tower"]`. Calling this multiple times adds
+    /// to the previously set targets.
+    #[must_use]
+    pub fn with_exclude_targets<T>(mut self, targets: T) -> Self
+    where
+        T: IntoIterator,
+        T::Item: Into<String>,
+    {
+        for target in targets {
+            self.filter.exclude_target(target.into());
+        }
+        self
+    }
+
+    /// Only send spans and events up to the given verbosity to Axiom, e.g.
+    /// [`Level::INFO`] to skip `DEBUG` and `TRACE`. This is a shortcut for
+    /// [`Builder::with_filter`] with a [`LevelFilter`] and replaces a previously
+    /// set filter.
+    #[must_use]
+    pub fn with_max_level(self, level: Level) -> Self {
+        self.with_filter(LevelFilter::from_level(level))
+    }
+
+    /// Build a layer that records and sends nothing, e.g. for local development
+    /// and CI, while the application keeps the same setup code. No token or
+    /// dataset is required and no network calls are made. Also enabled by
+    /// setting `AXIOM_DISABLED=true` with [`Builder::with_env`].
+    #[must_use]
+    pub fn disabled(mut self) -> Self {
+        self.disabled = true;
+        self
+    }
+
+    /// Build a layer that records and sends nothing, like [`Builder::disabled`],
+    /// if the token or dataset is missing, rather than failing. A warning is
+    /// printed to stderr, or reported to the internal log if one is set. Useful
+    /// for applications where sending telemetry to Axiom is optional.
+    ///
+    /// Invalid settings, like a token that is not an API token, still fail.
+    #[must_use]
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Print the spans as JSON lines to stdout instead of sending them to Axiom,
+    /// with the attribute names as they would show up in the dashboard. This is
+    /// handy to debug why fields are nested or missing. No token or dataset is
+    /// required. Also enabled by setting `AXIOM_DRY_RUN=1` with [`Builder::with_env`].
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn with_stdout_exporter(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Check that the Axiom URL can be reached and accepts the token and
+    /// dataset when the layer is built, by sending it an empty batch, rather
+    /// than finding out from failed exports in the background later. Building
+    /// the layer then takes up to the timeout and fails with
+    /// [`Error::EndpointCheck`] if the check fails. Has no effect with
+    /// [`Builder::with_exporter`].
+    ///
+    /// The batch is sent with the client of [`Builder::with_http_client`] on
+    /// the runtime the spans are exported on, except when the layer is built
+    /// on a current-thread tokio runtime, which can't send it while the build
+    /// waits. Then a client of its own sends it from another thread.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn with_eager_connect(mut self, enabled: bool) -> Self {
+        self.eager_connect = enabled;
+        self
+    }
+
+    /// Configure the exporter for serverless environments like AWS Lambda, where the
+    /// sandbox is frozen between invocations and a periodic background export would
+    /// lose spans. Spans are exported as soon as they end with a short timeout of
+    /// one second (unless set with [`Builder::with_timeout`] afterwards), and
+    /// [`force_flush`](crate::force_flush) must be called at the end of every
+    /// invocation to wait for the export to finish.
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
+    ///
+    /// let axiom_layer = tracing_axiom::builder_with_env("lambda")?
+    ///     .serverless()
+    ///     .build()?;
+    /// tracing_subscriber::registry().with(axiom_layer).init();
+    ///
+    /// // in the handler, after the work for the invocation is done:
+    /// tracing_axiom::force_flush()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn serverless(mut self) -> Self {
+        self.processor = Processor::Simple;
+        self.timeout = Some(Duration::from_secs(1));
+        self
+    }
+
+    /// Read the environment variables of [`Builder::with_env`] with a prefix
+    /// first, e.g. `MYAPP_AXIOM_TOKEN` and `MYAPP_AXIOM_DATASET` with the
+    /// prefix `MYAPP_`, so several components in one pod can send to
+    /// different datasets. Variables without the prefix are used if the
+    /// prefixed ones are not set, e.g. a token shared by all components.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// // Reads `WORKER_AXIOM_DATASET`, falling back to `AXIOM_DATASET`
+    /// let builder = tracing_axiom::builder("worker")
+    ///     .with_env_prefix("WORKER_")
+    ///     .with_env()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_env_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Load defaults from environment variables, if variables were set before this call they will not be replaced.
+    ///
+    /// The following environment variables are used:
+    /// - `AXIOM_TOKEN`
+    /// - `AXIOM_DATASET`
+    /// - `AXIOM_URL`
+    /// - `AXIOM_ENVIRONMENT` (falling back to `ENVIRONMENT`)
+    /// - `AXIOM_TAGS` as comma separated `key=value` pairs, these are added to
+    ///   the existing tags
+    /// - `AXIOM_LOG_LEVEL` as the maximum level sent to Axiom (e.g. `info`),
+    ///   see [`Builder::with_max_level`]
+    /// - `AXIOM_DISABLED` set to `1` or `true` disables sending, see
+    ///   [`Builder::disabled`]
+    /// - `AXIOM_DRY_RUN` set to `1` or `true` prints spans instead of sending
+    ///   them, see [`Builder::with_stdout_exporter`]
+    ///
+    /// With [`Builder::with_env_prefix`] the prefixed variables are preferred.
+    ///
+    /// # Errors
+    /// If an environment variable is not valid UTF8, or any of their values are invalid.
+    pub fn with_env(mut self) -> Result<Self, Error> {
+        if self.token.is_none() {
+            if let Some(t) = self.env_var("AXIOM_TOKEN")? {
+                self = self.with_token(t)?;
+            }
+        }
+
+        if self.dataset_name.is_none() {
+            if let Some(d) = self.env_var("AXIOM_DATASET")? {
+                self = self.with_dataset(d)?;
+            }
+        }
+        if self.url.is_none() {
+            if let Some(u) = self.env_var("AXIOM_URL")? {
+                self = self.with_url(&u)?;
+            }
+        }
+        if self.environment.is_none() {
+            let environment = match self.env_var("AXIOM_ENVIRONMENT")? {
+                Some(e) => Some(e),
+                None => self.env_var("ENVIRONMENT")?,
+            };
+            if let Some(e) = environment {
+                self = self.with_environment(e);
+            }
+        }
+        if let Some(t) = self.env_var("AXIOM_TAGS")? {
+            self.tags.extend(parse_tags(&t)?);
+        }
+        if self.filter.is_default() {
+            if let Some(l) = self.env_var("AXIOM_LOG_LEVEL")? {
+                let level = l
+                    .parse::<LevelFilter>()
+                    .map_err(|_| Error::InvalidLogLevel(l))?;
+                self = self.with_filter(level);
+            }
+        }
+        if matches!(
+            self.env_var("AXIOM_DISABLED")?.as_deref(),
+            Some("1" | "true")
+        ) {
+            self.disabled = true;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if matches!(
+            self.env_var("AXIOM_DRY_RUN")?.as_deref(),
+            Some("1" | "true")
+        ) {
+            self.dry_run = true;
+        }
+
+        Ok(self)
+    }
+
+    /// The environment variable, preferring the one with the prefix.
+    fn env_var(&self, name: &str) -> Result<Option<String>, Error> {
+        if let Some(prefix) = &self.env_prefix {
+            if let Some(value) = get_env(&format!("{prefix}{name}"))? {
+                return Ok(Some(value));
+            }
+        }
+        get_env(name)
+    }
+
+    /// Create a layer which sends traces to Axiom that can be added to the tracing layers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the settings are not valid
+    pub fn build<S>(self) -> Result<AxiomLayer<S>, Error>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        Ok(self.build_with_provider()?.0)
+    }
+
+    /// Create a layer like [`Builder::build`], together with the tracer
+    /// provider it records spans with, e.g. to create more tracers or to shut
+    /// it down explicitly. The provider is installed globally as well.
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use opentelemetry::trace::TracerProvider as _;
+    /// use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
+    ///
+    /// let (axiom_layer, provider) = tracing_axiom::builder_with_env("my-service")?
+    ///     .build_with_provider()?;
+    /// tracing_subscriber::registry().with(axiom_layer).init();
+    ///
+    /// let tracer = provider.tracer("my-library");
+    /// // At the end of main, after all spans ended
+    /// provider.force_flush();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the settings are not valid
+    pub fn build_with_provider<S>(self) -> Result<(AxiomLayer<S>, TracerProvider), Error>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let (layer, provider, _) = self.build_layer(true)?;
+        Ok((layer, provider))
+    }
+
+    fn build_layer<S>(
+        mut self,
+        global: bool,
+    ) -> Result<(AxiomLayer<S>, TracerProvider, PipelineState), Error>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        self.disable_if_unconfigured();
+        let filter = self.filter();
+        self.flush_summaries();
+        let mut fields = std::mem::take(&mut self.fields);
+        let location = self.location.unwrap_or(true);
+        let threads = self.threads.unwrap_or(true);
+        let scope = self.instrumentation_scope.take();
+        #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+        let mut meter_providers = Vec::new();
+        #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+        if let Some(dataset_name) = self.metrics_dataset.take() {
+            if !self.disabled && !self.dry_run {
+                let provider = self.meter_provider(&dataset_name, Duration::from_secs(60))?;
+                meter_providers.push(provider.clone());
+                fields.metrics = Some(crate::fields::EventMetrics::new(provider));
+            }
+        }
+        #[cfg(all(feature = "sysinfo", not(target_arch = "wasm32")))]
+        if let Some((dataset_name, interval)) = self.system_metrics.take() {
+            if !self.disabled && !self.dry_run {
+                let provider = self.meter_provider(&dataset_name, interval)?;
+                crate::system::register(&provider);
+                meter_providers.push(provider.clone());
+                fields.system_metrics = Some(provider);
+            }
+        }
+        let (provider, state) = self.provider(global)?;
+        #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+        if global {
+            crate::flush::register_meter_providers(meter_providers.clone());
+        }
+        #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+        let state = PipelineState {
+            meter_providers,
+            ..state
+        };
+        let tracer = versioned_tracer(&provider, scope.as_ref());
+        if let Some(summaries) = &fields.summaries {
+            summaries.set_tracer(&tracer);
+        }
+        fields.tracer = Some(tracer.clone());
+        let layer = tracing_opentelemetry::layer()
+            .with_location(location)
+            .with_threads(threads)
+            .with_tracer(tracer)
+            .and_then(fields)
+            .with_filter(filter);
+        Ok((layer, provider, state))
+    }
+
+    /// Create a layer like [`Builder::build`] without installing anything
+    /// globally, so several pipelines can run side by side, e.g. in libraries
+    /// or test harnesses. The returned guard owns the tracer provider: the
+    /// layer records spans as long as it is alive, and dropping it exports the
+    /// remaining spans and shuts the provider down.
+    ///
+    /// Neither the tracer provider nor the propagator are installed in
+    /// [`opentelemetry::global`], and [`force_flush`](crate::force_flush) does
+    /// not flush the provider, use [`ProviderGuard::force_flush`] instead.
+    /// Likewise [`health`](crate::health) and [`queue_stats`](crate::queue_stats)
+    /// don't report the pipeline, use [`ProviderGuard::health`] and
+    /// [`ProviderGuard::queue_stats`]. [`ingest`](crate::ingest) and the
+    /// [internal log](Builder::with_internal_log) keep using the most recently
+    /// built global layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the settings are not valid
+    pub fn build_isolated<S>(self) -> Result<(AxiomLayer<S>, ProviderGuard), Error>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let (layer, provider, state) = self.build_layer(false)?;
+        Ok((layer, ProviderGuard { provider, state }))
+    }
+
+    /// Create a layer which records spans in memory instead of sending them to
+    /// Axiom, together with a handle to assert on them in unit tests. No token
+    /// or dataset is required and nothing is installed globally.
+    ///
+    /// See [`testing`](crate::testing) for an example.
+    #[must_use]
+    pub fn build_for_test<S>(mut self) -> (AxiomLayer<S>, CapturedSpans)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let trace_config = self.trace_config();
+        let filter = self.filter();
+        self.flush_summaries();
+        let spans = CapturedSpans::new(
+            provider_builder(trace_config, self.span_processors),
+            self.transforms,
+        );
+        let tracer = versioned_tracer(spans.provider(), self.instrumentation_scope.as_ref());
+        if let Some(summaries) = &self.fields.summaries {
+            summaries.set_tracer(&tracer);
+        }
+        self.fields.tracer = Some(tracer.clone());
+        let layer = tracing_opentelemetry::layer()
+            .with_location(self.location.unwrap_or(true))
+            .with_threads(self.threads.unwrap_or(true))
+            .with_tracer(tracer)
+            .and_then(self.fields)
+            .with_filter(filter);
+        (layer, spans)
+    }
+
+    /// Create a layer like [`Builder::build`], together with a handle to
+    /// change its filter, sampler and token while it is running.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the settings are not valid
+    pub fn build_with_reload<S>(mut self) -> Result<(AxiomLayer<S>, ReloadHandle), Error>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let reload = self.reload_handle();
+        Ok((self.build()?, reload))
+    }
+
+    /// Exports the span summaries whenever the provider is flushed, before
+    /// the processors that export them run.
+    fn flush_summaries(&mut self) {
+        if let Some(summaries) = &self.fields.summaries {
+            self.span_processors
+                .insert(0, BoxedSpanProcessor(Box::new(summaries.flusher())));
+        }
+    }
+
+    pub(crate) fn reload_handle(&mut self) -> ReloadHandle {
+        self.reload
+            .get_or_insert_with(ReloadHandle::default)
+            .clone()
+    }
+
+    /// The filter, shared with the reload handle if there is one.
+    fn filter(&mut self) -> AxiomFilter {
+        let filter = std::mem::take(&mut self.filter);
+        match &self.reload {
+            Some(reload) => filter.share(&reload.filter),
+            None => filter,
+        }
+    }
+
+    fn trace_config(&mut self) -> TraceConfig {
+        let mut trace_config = self
+            .trace_config
+            .take()
+            .unwrap_or_default()
+            .with_resource(self.resource());
+        if let Some(id_generator) = self.id_generator.take() {
+            trace_config.id_generator = id_generator;
+        }
+        if let Some(max) = self.max_attributes_per_span {
+            trace_config.span_limits.max_attributes_per_span = max;
+        }
+        if let Some(max) = self.max_events_per_span {
+            trace_config.span_limits.max_events_per_span = max;
+        }
+        if let Some(max) = self.max_links_per_span {
+            trace_config.span_limits.max_links_per_span = max;
+        }
+        if let Some(reload) = &self.reload {
+            let sampler =
+                std::mem::replace(&mut trace_config.sampler, Box::new(reload.sampler.clone()));
+            reload.sampler.set_boxed(sampler);
+        }
+        let sampler = std::mem::replace(&mut trace_config.sampler, Box::new(Sampler::AlwaysOn));
+        trace_config.sampler = Box::new(ForcedSampling(sampler));
+        trace_config
+    }
+
+    fn resource(&self) -> Resource {
+        let mut tags = self.tags.clone();
+        tags.extend(vec![
+            KeyValue::new(TELEMETRY_SDK_NAME, env!("CARGO_PKG_NAME").to_string()),
+            KeyValue::new(TELEMETRY_SDK_VERSION, env!("CARGO_PKG_VERSION").to_string()),
+            KeyValue::new(TELEMETRY_SDK_LANGUAGE, "rust".to_string()),
+        ]);
+
+        if let Some(service_name) = &self.service_name {
+            // TODO: Is there a way to get the name of the bin crate using this?
+            tags.push(KeyValue::new(SERVICE_NAME, service_name.clone()));
+        }
+
+        if let Some(service_version) = &self.service_version {
+            tags.push(KeyValue::new(SERVICE_VERSION, service_version.clone()));
+        }
+
+        if let Some(git_sha) = &self.git_sha {
+            tags.push(KeyValue::new(BUILD_GIT_SHA, git_sha.clone()));
+        }
+
+        if let Some(build_timestamp) = &self.build_timestamp {
+            tags.push(KeyValue::new(BUILD_TIMESTAMP, build_timestamp.clone()));
+        }
+
+        if let Some(environment) = &self.environment {
+            tags.push(KeyValue::new(DEPLOYMENT_ENVIRONMENT, environment.clone()));
+        }
+
+        // Detected attributes don't replace those of the tags or resource
+        let is_set = |tags: &[KeyValue], key: &'static str| {
+            tags.iter().any(|kv| kv.key.as_str() == key)
+                || self
+                    .resource
+                    .as_ref()
+                    .is_some_and(|resource| resource.get(Key::from_static_str(key)).is_some())
+        };
+        match &self.service_instance_id {
+            Some(instance_id) => {
+                tags.push(KeyValue::new(SERVICE_INSTANCE_ID, instance_id.clone()));
+            }
+            None if !is_set(&tags, SERVICE_INSTANCE_ID) => {
+                tags.push(KeyValue::new(SERVICE_INSTANCE_ID, process_instance_id()));
+            }
+            None => {}
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(container_id) = crate::container::container_id() {
+            if !is_set(&tags, CONTAINER_ID) {
+                tags.push(KeyValue::new(CONTAINER_ID, container_id));
+            }
+        }
+
+        match &self.resource {
+            Some(resource) => resource.merge(&Resource::new(tags)),
+            None => Resource::new(tags),
+        }
+    }
+
+    /// Disables the layer if it is lenient and the token or dataset is
+    /// missing, see [`Builder::lenient`].
+    fn disable_if_unconfigured(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let dry_run = self.dry_run;
+        #[cfg(target_arch = "wasm32")]
+        let dry_run = false;
+        let unconfigured = self.token.is_none() || self.dataset_name.is_none();
+        if !self.lenient
+            || !unconfigured
+            || self.disabled
+            || dry_run
+            || self.local_collector
+            || self.exporter.is_some()
+        {
+            return;
+        }
+        self.disabled = true;
+        // The warning is the only sign the layer does nothing, so it is also
+        // printed without an internal log and for layers that aren't global
+        Diagnostics {
+            level: self.internal_logging.unwrap_or(Level::WARN),
+            log: self.internal_log.clone().unwrap_or_default(),
+        }
+        .log(
+            Level::WARN,
+            "the Axiom token or dataset is not set, spans are not sent to Axiom",
+        );
+    }
+
+    /// Builds a tracer without installing anything globally, so tests don't
+    /// overwrite each other's global provider, health and queue counters.
+    #[cfg(test)]
+    fn tracer(self) -> Result<Tracer, Error> {
+        Ok(versioned_tracer(&self.provider(false)?.0, None))
+    }
+
+    /// Builds the tracer provider, and installs it globally if `global` is set.
+    fn provider(mut self, global: bool) -> Result<(TracerProvider, PipelineState), Error> {
+        let propagation = self.propagation.take();
+        let baggage = self.baggage;
+        let (provider, state) = self.new_provider(global)?;
+        if global {
+            install(&provider, propagation, baggage);
+        }
+        Ok((provider, state))
+    }
+
+    /// Builds the tracer provider, and registers its ingester, export health,
+    /// queue counters and internal log globally if `global` is set.
+    fn new_provider(mut self, global: bool) -> Result<(TracerProvider, PipelineState), Error> {
+        self.disable_if_unconfigured();
+        if global && (self.internal_logging.is_some() || self.internal_log.is_some()) {
+            Diagnostics {
+                level: self.internal_logging.unwrap_or(Level::WARN),
+                log: self.internal_log.take().unwrap_or_default(),
+            }
+            .install();
+        }
+        // Remote sampling rules are applied to the reloadable sampler
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.remote_sampling.is_some() {
+            self.reload_handle();
+        }
+        let trace_config = self.trace_config();
+        let span_processors = std::mem::take(&mut self.span_processors);
+        if self.disabled {
+            // Lenient layers without a token or dataset warned about it already
+            if !self.lenient || (self.token.is_some() && self.dataset_name.is_some()) {
+                diagnostics::report(Level::INFO, "disabled, spans are not sent to Axiom");
+            }
+            let provider = provider_builder(
+                trace_config.with_sampler(Sampler::AlwaysOff),
+                span_processors,
+            )
+            .build();
+            return Ok((provider, PipelineState::default()));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.dry_run {
+            diagnostics::report(Level::INFO, "dry run, printing spans to stdout");
+            let provider = provider_builder(trace_config, span_processors)
+                .with_simple_exporter(ExportPipeline::new(StdoutExporter, self.transforms))
+                .build();
+            return Ok((provider, PipelineState::default()));
+        }
+
+        #[cfg(feature = "prometheus")]
+        if let Some(metrics) = &self.metrics {
+            metrics.register(&self.queue)?;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.eager_connect && self.exporter.is_none() {
+            self.check_endpoint()?;
+        }
+        let ingester = self.ingester()?;
+        let exporter = self.export_pipeline()?;
+        let state = PipelineState {
+            health: exporter.health().clone(),
+            counters: matches!(self.processor, Processor::Batch).then(|| self.queue.counters()),
+        };
+        if global {
+            if let Some(ingester) = ingester {
+                ingester.register();
+            }
+            state.health.register();
+            if let Some(counters) = &state.counters {
+                counters.register();
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let remote_sampling = self.remote_sampling();
+
+        let queue = self.queue;
+        let processor: Box<dyn SpanProcessor> = match (self.runtime, self.processor) {
+            (Some(runtime), Processor::Batch) => runtime.processor(exporter, queue),
+            // Outside of tokio, e.g. in a plain `fn main()`, the exporter gets its own runtime.
+            #[cfg(not(target_arch = "wasm32"))]
+            (None, Processor::Batch) if tokio::runtime::Handle::try_current().is_err() => {
+                diagnostics::report(
+                    Level::DEBUG,
+                    "no tokio runtime, exporting spans from a background thread",
+                );
+                Box::new(queue.processor(exporter, BackgroundRuntime::shared()?))
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            (None, Processor::Batch) => {
+                Box::new(queue.processor(exporter, opentelemetry_sdk::runtime::Tokio))
+            }
+            #[cfg(target_arch = "wasm32")]
+            (None, Processor::Batch) => {
+                Box::new(queue.processor(exporter, crate::wasm::WasmRuntime))
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            (_, Processor::Simple) => Box::new(SimpleSpanProcessor::new(Box::new(exporter))),
+        };
+        let processor: Box<dyn SpanProcessor> = match self.span_collapsing {
+            Some(min_run) => Box::new(Collapse::new(processor, min_run)),
+            None => processor,
+        };
+        let processor: Box<dyn SpanProcessor> = match self.min_span_duration {
+            Some(min_duration) => Box::new(MinDuration::new(processor, min_duration)),
+            None => processor,
+        };
+        let processor: Box<dyn SpanProcessor> = match (self.tail_policy, self.tail_limits) {
+            (None, None) => processor,
+            (policy, limits) => Box::new(TailSampler::new(
+                processor,
+                policy,
+                limits.unwrap_or_default(),
+            )),
+        };
+        let provider = provider_builder(trace_config, span_processors)
+            .with_span_processor(BoxedSpanProcessor(processor))
+            .build();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(start) = remote_sampling {
+            start(versioned_tracer(&provider, None))?;
+        }
+        Ok((provider, state))
+    }
+
+    /// Takes the remote sampling settings, returning a function that fetches
+    /// the sampling rules in the background until the provider of the tracer
+    /// is dropped, on the current tokio runtime if there is one.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn remote_sampling(&mut self) -> Option<impl FnOnce(Tracer) -> Result<(), Error>> {
+        let (Some(remote_sampling), Some(reload)) = (self.remote_sampling.take(), &self.reload)
+        else {
+            return None;
+        };
+        let http_client: Arc<dyn HttpClient> = match &self.http_client {
+            Some(http_client) => http_client.0.clone(),
+            None => Arc::new(reqwest::Client::new()),
+        };
+        let sampler = reload.sampler.clone();
+        Some(move |tracer| {
+            let task = Box::pin(remote_sampling.run(
+                Arc::new(SuppressTracing(http_client)),
+                sampler,
+                tracer,
+            ));
+            if tokio::runtime::Handle::try_current().is_ok() {
+                drop(tokio::spawn(task));
+            } else {
+                opentelemetry_sdk::runtime::Runtime::spawn(&BackgroundRuntime::shared()?, task);
+            }
+            Ok(())
+        })
+    }
+
+    /// A meter provider exporting to the metrics dataset in the background on
+    /// the interval, see [`Builder::with_metrics`].
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    fn meter_provider(
+        &self,
+        dataset_name: &str,
+        interval: Duration,
+    ) -> Result<opentelemetry_sdk::metrics::SdkMeterProvider, Error> {
+        use opentelemetry_sdk::metrics::{
+            reader::{DefaultAggregationSelector, DefaultTemporalitySelector},
+            PeriodicReader, SdkMeterProvider,
+        };
+
+        let token = match self.token.clone() {
+            Some(token) => Some(token),
+            None if self.local_collector => None,
+            None => return Err(Error::MissingToken),
+        };
+        let url = self
+            .url
+            .clone()
+            .unwrap_or_else(|| CLOUD_URL.to_string().parse().expect("this is a valid URL"));
+        let mut headers = HashMap::from([
+            (
+                "X-Axiom-Dataset".to_string(),
+                self.resolve_placeholders(dataset_name)?,
+            ),
+            ("User-Agent".to_string(), self.user_agent()),
+        ]);
+        if let Some(token) = token {
+            headers.insert("Authorization".to_string(), format!("Bearer {token}"));
+        }
+        let timeout = self.timeout.unwrap_or(Duration::from_secs(3));
+        let http_client: Arc<dyn HttpClient> = match &self.http_client {
+            Some(http_client) => http_client.0.clone(),
+            None => Arc::new(
+                self.client_options
+                    .client(timeout)
+                    .map_err(|_| Error::HttpClient)?,
+            ),
+        };
+        let exporter = opentelemetry_otlp::new_exporter()
+            .http()
+            .with_http_client(SharedHttpClient(Arc::new(SuppressTracing(http_client))))
+            .with_endpoint(url)
+            .with_headers(headers)
+            .with_timeout(timeout)
+            .build_metrics_exporter(
+                Box::new(DefaultAggregationSelector::new()),
+                Box::new(DefaultTemporalitySelector::new()),
+            )?;
+        let reader = if tokio::runtime::Handle::try_current().is_ok() {
+            PeriodicReader::builder(exporter, opentelemetry_sdk::runtime::Tokio)
+                .with_interval(interval)
+                .build()
+        } else {
+            PeriodicReader::builder(exporter, BackgroundRuntime::shared()?)
+                .with_interval(interval)
+                .build()
+        };
+        Ok(SdkMeterProvider::builder()
+            .with_reader(reader)
+            .with_resource(self.resource())
+            .build())
+    }
+
+    /// The exporter with the transforms and the secondary endpoint applied.
+    pub(crate) fn export_pipeline(&mut self) -> Result<ExportPipeline, Error> {
+        let transforms = std::mem::take(&mut self.transforms);
+        let mut primary = match self.exporter.take() {
+            Some(exporter) => exporter,
+            None => self.otlp_exporter()?,
+        };
+        if let Some(max_bytes) = self.max_batch_bytes {
+            primary = Box::new(BatchSplitter::new(primary, max_bytes));
+        }
+        let mut exporter = ExportPipeline::boxed(primary, transforms);
+        if let Some((url, headers)) = self.secondary_endpoint.take() {
+            exporter = exporter.with_secondary(self.http_exporter(url, headers, None, None, None)?);
+        }
+        if let Some(breaker) = self.circuit_breaker.take() {
+            exporter = exporter.with_circuit_breaker(breaker, self.fallback.take());
+        }
+        Ok(exporter)
+    }
+
+    /// The exporter sending spans to Axiom, failing over to the other URLs.
+    fn otlp_exporter(&mut self) -> Result<Box<dyn SpanExporter>, Error> {
+        // A local collector adds the token and dataset itself if they are missing
+        let (token, dataset_name) = if self.local_collector {
+            (self.token.take(), self.dataset_name.take())
+        } else {
+            (
+                Some(self.token.take().ok_or(Error::MissingToken)?),
+                Some(self.dataset_name.take().ok_or(Error::MissingDataset)?),
+            )
+        };
+        let url = self
+            .url
+            .take()
+            .unwrap_or_else(|| CLOUD_URL.to_string().parse().expect("this is a valid URL"));
+
+        let mut headers = HashMap::with_capacity(3);
+        // With a reload handle the token is added to every request instead
+        let token = match (token, &self.reload) {
+            (Some(token), Some(reload)) => {
+                reload.token.set(token);
+                Some(reload.token.clone())
+            }
+            (Some(token), None) => {
+                headers.insert("Authorization".to_string(), format!("Bearer {token}"));
+                None
+            }
+            (None, _) => None,
+        };
+        if let Some(dataset_name) = dataset_name {
+            headers.insert(
+                "X-Axiom-Dataset".to_string(),
+                self.resolve_placeholders(&dataset_name)?,
+            );
+        }
+        // Spans with an `axiom.dataset` attribute are sent to that dataset
+        let routing = SharedDataset::default();
+        if self.failover_urls.is_empty() {
+            let exporter = self.http_exporter(
+                url,
+                headers,
+                token,
+                Some(&routing),
+                self.traces_path.as_deref(),
+            )?;
+            return Ok(Box::new(DatasetRouter::new(exporter, routing)));
+        }
+        let urls = std::iter::once(url).chain(std::mem::take(&mut self.failover_urls));
+        let endpoints = urls
+            .map(|url| {
+                let exporter = self.http_exporter(
+                    url.clone(),
+                    headers.clone(),
+                    token.clone(),
+                    Some(&routing),
+                    self.traces_path.as_deref(),
+                )?;
+                Ok((url.to_string(), exporter))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Box::new(DatasetRouter::new(
+            Box::new(Failover::new(endpoints)),
+            routing,
+        )))
+    }
+
+    /// The dataset name with the placeholders replaced, see [`Builder::with_dataset`].
+    fn resolve_placeholders(&self, dataset_name: &str) -> Result<String, Error> {
+        let mut resolved = String::with_capacity(dataset_name.len());
+        let mut rest = dataset_name;
+        while let Some((before, after)) = rest.split_once('{') {
+            let Some((name, after)) = after.split_once('}') else {
+                break;
+            };
+            let value = match name {
+                "env" => self.environment.clone(),
+                "service" => self.service_name.clone(),
+                "version" => self.service_version.clone(),
+                _ => get_env(name)?,
+            };
+            resolved.push_str(before);
+            resolved
+                .push_str(&value.ok_or_else(|| Error::UnresolvedPlaceholder(name.to_string()))?);
+            rest = after;
+        }
+        resolved.push_str(rest);
+        Ok(resolved)
+    }
+
+    /// `tracing-axiom/{version}`, followed by the suffix if there is one.
+    fn user_agent(&self) -> String {
+        let user_agent = format!("tracing-axiom/{}", env!("CARGO_PKG_VERSION"));
+        match &self.user_agent_suffix {
+            Some(suffix) => format!("{user_agent} {suffix}"),
+            None => user_agent,
+        }
+    }
+
+    /// The ingester of [`ingest`](crate::ingest), sending with the token, URL
+    /// and HTTP client of the exporter. `None` without a token.
+    fn ingester(&self) -> Result<Option<Ingester>, Error> {
+        let Some(token) = self.token.clone() else {
+            return Ok(None);
+        };
+        let url = self
+            .url
+            .clone()
+            .unwrap_or_else(|| CLOUD_URL.to_string().parse().expect("this is a valid URL"));
+        // The blocking client of the simple processor can't be used here
+        let http_client: Arc<dyn HttpClient> = match &self.http_client {
+            Some(http_client) => http_client.0.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            None => Arc::new(
+                self.client_options
+                    .client(self.timeout.unwrap_or(Duration::from_secs(3)))
+                    .map_err(|_| Error::HttpClient)?,
+            ),
+            #[cfg(target_arch = "wasm32")]
+            None => Arc::new(crate::wasm::FetchClient::default()),
+        };
+        let http_client = if self.export_tracing {
+            http_client
+        } else {
+            Arc::new(SuppressTracing(http_client))
+        };
+        // Follows the token of the reload handle
+        let shared_token = self
+            .reload
+            .as_ref()
+            .map_or_else(SharedToken::default, |reload| reload.token.clone());
+        shared_token.set(token);
+        Ok(Some(Ingester {
+            url,
+            user_agent: self.user_agent(),
+            client: Arc::new(Authorize {
+                client: http_client,
+                token: shared_token,
+            }),
+            resource: Ingester::resource_fields(&self.resource(), &self.ingest_resource_prefix),
+        }))
+    }
+
+    /// Sends an empty batch to the Axiom URL, see [`Builder::with_eager_connect`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn check_endpoint(&self) -> Result<(), Error> {
+        let url = self
+            .url
+            .clone()
+            .unwrap_or_else(|| CLOUD_URL.to_string().parse().expect("this is a valid URL"));
+        let path = self.traces_path.as_deref().unwrap_or(TRACES_PATH);
+        let uri = format!(
+            "{}/{}",
+            url.as_str().trim_end_matches('/'),
+            path.trim_start_matches('/')
+        );
+        // An empty protobuf message is empty
+        let (content_type, body) = match self.encoding {
+            Encoding::Json => ("application/json", b"{}".to_vec()),
+            Encoding::Protobuf => ("application/x-protobuf", Vec::new()),
+        };
+        let mut request = http::Request::post(&uri)
+            .header(http::header::CONTENT_TYPE, content_type)
+            .header(http::header::USER_AGENT, self.user_agent());
+        if let Some(token) = &self.token {
+            request = request.header(http::header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        if let Some(dataset_name) = &self.dataset_name {
+            request = request.header("X-Axiom-Dataset", self.resolve_placeholders(dataset_name)?);
+        }
+        let request = request
+            .body(body)
+            .map_err(|_| Error::InvalidTracesPath(path.to_string()))?;
+        let timeout = self.timeout.unwrap_or(Duration::from_secs(3));
+        let own_client = || -> Result<Arc<dyn HttpClient>, Error> {
+            Ok(Arc::new(
+                self.client_options
+                    .client(timeout)
+                    .map_err(|_| Error::HttpClient)?,
+            ))
+        };
+        let http_client = match &self.http_client {
+            Some(http_client) => http_client.0.clone(),
+            None => own_client()?,
+        };
+        let sent = match tokio::runtime::Handle::try_current() {
+            // Sent on the runtime the exporter uses, so the connections a
+            // client of the application pools stay usable for the export
+            Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+                send_on(&handle, http_client, request, timeout)
+            }
+            // The thread of a current-thread runtime can't wait for it, so a
+            // client of its own sends from a runtime on a thread of its own
+            Ok(_) => {
+                let http_client = own_client()?;
+                std::thread::spawn(move || {
+                    let runtime = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()?;
+                    let sent = tokio::time::timeout(timeout, http_client.send(request));
+                    Ok::<_, Error>(runtime.block_on(sent).ok())
+                })
+                .join()
+                .map_err(|_| Error::EndpointCheck("the check panicked".to_string()))??
+            }
+            Err(_) => send_on(
+                BackgroundRuntime::shared()?.handle(),
+                http_client,
+                request,
+                timeout,
+            ),
+        };
+        match sent {
+            Some(Ok(response)) if response.status().is_success() => {
+                diagnostics::report(Level::DEBUG, &format!("{uri} accepted the empty batch"));
+                Ok(())
+            }
+            Some(Ok(response)) => Err(Error::EndpointCheck(format!(
+                "{uri} responded with {}",
+                response.status()
+            ))),
+            Some(Err(err)) => Err(Error::EndpointCheck(format!("{uri}: {err}"))),
+            None => Err(Error::EndpointCheck(format!(
+                "{uri} did not respond within {timeout:?}"
+            ))),
+        }
+    }
+
+    /// An OTLP/HTTP exporter sending spans to the URL with the headers, the
+    /// current Axiom token if it can be reloaded and the dataset a
+    /// [`DatasetRouter`] routes them to. Spans are sent to the traces path
+    /// under the URL, `/v1/traces` unless another one is given.
+    fn http_exporter(
+        &self,
+        url: Url,
+        mut headers: HashMap<String, String>,
+        token: Option<SharedToken>,
+        routing: Option<&SharedDataset>,
+        traces_path: Option<&str>,
+    ) -> Result<Box<dyn SpanExporter>, Error> {
+        headers.insert("User-Agent".to_string(), self.user_agent());
+
+        let timeout = self.timeout.unwrap_or(Duration::from_secs(3));
+        #[cfg(not(target_arch = "wasm32"))]
+        let client_options = self.client_options;
+        let http_client: Arc<dyn HttpClient> = match (&self.http_client, self.processor) {
+            (Some(http_client), _) => http_client.0.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            (None, Processor::Batch) => Arc::new(
+                client_options
+                    .client(timeout)
+                    .map_err(|_| Error::HttpClient)?,
+            ),
+            #[cfg(target_arch = "wasm32")]
+            (None, Processor::Batch) => Arc::new(crate::wasm::FetchClient::default()),
+            // The simple processor exports from a plain thread without a tokio runtime,
+            // the blocking client must not be created within one either.
+            #[cfg(not(target_arch = "wasm32"))]
+            (None, Processor::Simple) => Arc::new(
+                std::thread::spawn(move || client_options.blocking_client(timeout))
+                    .join()
+                    .map_err(|_| Error::HttpClient)?
+                    .map_err(|_| Error::HttpClient)?,
+            ),
+        };
+        let http_client = if self.export_tracing {
+            http_client
+        } else {
+            Arc::new(SuppressTracing(http_client))
+        };
+        let http_client: Arc<dyn HttpClient> = match token {
+            Some(token) => Arc::new(Authorize {
+                client: http_client,
+                token,
+            }),
+            None => http_client,
+        };
+        let http_client: Arc<dyn HttpClient> = match routing {
+            Some(dataset) => Arc::new(RouteDataset {
+                client: http_client,
+                dataset: dataset.clone(),
+            }),
+            None => http_client,
+        };
+        #[cfg(feature = "prometheus")]
+        let http_client: Arc<dyn HttpClient> = match &self.metrics {
+            Some(metrics) => Arc::new(crate::metrics::MeteredClient {
+                client: http_client,
+                metrics: metrics.clone(),
+            }),
+            None => http_client,
+        };
 
-        let mut headers = HashMap::with_capacity(2);
-        headers.insert("Authorization".to_string(), format!("Bearer {token}"));
-        headers.insert("X-Axiom-Dataset".to_string(), dataset_name);
-        headers.insert(
-            "User-Agent".to_string(),
-            format!("tracing-axiom/{}", env!("CARGO_PKG_VERSION")),
-        );
+        let path = traces_path.unwrap_or(TRACES_PATH);
+        let uri = format!(
+            "{}/{}",
+            url.as_str().trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+        .parse::<Uri>()
+        .map_err(|_| Error::InvalidTracesPath(path.to_string()))?;
+        match self.encoding {
+            Encoding::Protobuf => {
+                // The OTLP exporter always appends the default path to the URL
+                let http_client: Arc<dyn HttpClient> = if traces_path.is_some() {
+                    Arc::new(FixedEndpoint {
+                        client: http_client,
+                        uri,
+                    })
+                } else {
+                    http_client
+                };
+                Ok(Box::new(
+                    opentelemetry_otlp::new_exporter()
+                        .http()
+                        .with_http_client(SharedHttpClient(http_client))
+                        .with_endpoint(url)
+                        .with_headers(headers)
+                        .with_timeout(timeout)
+                        .build_span_exporter()?,
+                ))
+            }
+            Encoding::Json => Ok(Box::new(OtlpJsonExporter::new(http_client, uri, &headers))),
+        }
+    }
+}
 
-        let mut tags = self.tags.clone();
-        tags.extend(vec![
-            KeyValue::new(TELEMETRY_SDK_NAME, env!("CARGO_PKG_NAME").to_string()),
-            KeyValue::new(TELEMETRY_SDK_VERSION, env!("CARGO_PKG_VERSION").to_string()),
-            KeyValue::new(TELEMETRY_SDK_LANGUAGE, "rust".to_string()),
-        ]);
+/// A tracer provider builder with the additional span processors, which run
+/// before the exporting processor added afterwards.
+fn provider_builder(
+    trace_config: TraceConfig,
+    span_processors: Vec<BoxedSpanProcessor>,
+) -> opentelemetry_sdk::trace::Builder {
+    span_processors.into_iter().fold(
+        TracerProvider::builder().with_config(trace_config),
+        opentelemetry_sdk::trace::Builder::with_span_processor,
+    )
+}
 
-        if let Some(service_name) = self.service_name {
-            // TODO: Is there a way to get the name of the bin crate using this?
-            tags.push(KeyValue::new(SERVICE_NAME, service_name));
-        }
+/// Sends the request on the runtime and waits for the response from the
+/// current thread, `None` if there was none within the timeout.
+#[cfg(not(target_arch = "wasm32"))]
+fn send_on(
+    runtime: &tokio::runtime::Handle,
+    http_client: Arc<dyn HttpClient>,
+    request: http::Request<Vec<u8>>,
+    timeout: Duration,
+) -> Option<Result<http::Response<bytes::Bytes>, opentelemetry_http::HttpError>> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    drop(runtime.spawn(async move {
+        let response = tokio::time::timeout(timeout, http_client.send(request)).await;
+        let _ = sender.send(response.ok());
+    }));
+    // Bounded as well in case the runtime has no other thread to send on
+    receiver.recv_timeout(timeout).ok().flatten()
+}
 
-        let trace_config = self
-            .trace_config
-            .unwrap_or_default()
-            .with_resource(Resource::new(tags));
+/// Installs the provider globally.
+fn install(provider: &TracerProvider, propagation: Option<Propagation>, baggage: bool) {
+    global::set_tracer_provider(provider.clone());
+    crate::flush::register(&versioned_tracer(provider, None));
+    if let Some(propagation) = propagation {
+        propagation.install(baggage);
+    }
+}
 
-        let pipeline = opentelemetry_otlp::new_exporter()
-            .http()
-            .with_http_client(reqwest::Client::new())
-            .with_endpoint(url)
-            .with_headers(headers)
-            .with_timeout(self.timeout.unwrap_or(Duration::from_secs(3)));
-        let tracer = opentelemetry_otlp::new_pipeline()
-            .tracing()
-            .with_exporter(pipeline)
-            .with_trace_config(trace_config)
-            .install_batch(opentelemetry_sdk::runtime::Tokio)?;
-        Ok(tracer)
+/// A tracer with the instrumentation scope, this crate unless one is given.
+fn versioned_tracer(provider: &TracerProvider, scope: Option<&InstrumentationLibrary>) -> Tracer {
+    match scope {
+        Some(scope) => provider.library_tracer(Arc::new(scope.clone())),
+        None => provider.versioned_tracer(
+            env!("CARGO_PKG_NAME"),
+            Some(env!("CARGO_PKG_VERSION")),
+            None::<&'static str>,
+            None,
+        ),
     }
 }
 
@@ -238,6 +2471,29 @@ mod tests {
         }
     }
 
+    /// Sets environment variables and restores their previous values when
+    /// dropped, also if the test fails.
+    #[derive(Default)]
+    struct EnvGuard(Vec<(&'static str, Option<std::ffi::OsString>)>);
+
+    impl EnvGuard {
+        fn set(&mut self, key: &'static str, value: impl AsRef<std::ffi::OsStr>) {
+            self.0.push((key, env::var_os(key)));
+            env::set_var(key, value);
+        }
+    }
+
+    impl Drop for EnvGuard {
+        fn drop(&mut self) {
+            for (key, value) in self.0.drain(..).rev() {
+                match value {
+                    Some(value) => env::set_var(key, value),
+                    None => env::remove_var(key),
+                }
+            }
+        }
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_no_env_skips_env_variables() -> Result<(), Error> {
         let builder = Builder::default();
@@ -342,6 +2598,639 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_environment_resource() {
+        let resource = Builder::default().with_environment("prod").resource();
+        assert_eq!(
+            resource.get(Key::from_static_str(DEPLOYMENT_ENVIRONMENT)),
+            Some(Value::from("prod"))
+        );
+
+        let resource = Builder::default().resource();
+        assert_eq!(
+            resource.get(Key::from_static_str(DEPLOYMENT_ENVIRONMENT)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dataset_placeholders() -> Result<(), Error> {
+        let mut vars = EnvGuard::default();
+        vars.set("TRACING_AXIOM_TEST_REGION", "eu");
+        let builder = Builder::default()
+            .with_environment("prod")
+            .with_service_name("api");
+        assert_eq!(
+            builder.resolve_placeholders("traces-{env}-{service}-{TRACING_AXIOM_TEST_REGION}")?,
+            "traces-prod-api-eu"
+        );
+        assert_eq!(builder.resolve_placeholders("traces")?, "traces");
+        assert!(matches!(
+            builder.resolve_placeholders("traces-{version}"),
+            Err(Error::UnresolvedPlaceholder(name)) if name == "version"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_prefix() -> Result<(), Error> {
+        let mut vars = EnvGuard::default();
+        vars.set("TRACING_AXIOM_TEST_AXIOM_DATASET", "prefixed");
+        vars.set("TRACING_AXIOM_TEST_AXIOM_ENVIRONMENT", "staging");
+        let builder = Builder::default().with_env_prefix("TRACING_AXIOM_TEST_");
+        assert_eq!(
+            builder.env_var("AXIOM_DATASET")?.as_deref(),
+            Some("prefixed")
+        );
+        let builder = builder.with_env()?;
+        assert_eq!(builder.dataset_name.as_deref(), Some("prefixed"));
+        assert_eq!(builder.environment.as_deref(), Some("staging"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_env_environment_fallback_is_lazy() -> Result<(), Error> {
+        use std::{ffi::OsString, os::unix::ffi::OsStringExt as _};
+
+        let mut vars = EnvGuard::default();
+        vars.set("TRACING_AXIOM_LAZY_AXIOM_ENVIRONMENT", "staging");
+        vars.set(
+            "TRACING_AXIOM_LAZY_ENVIRONMENT",
+            OsString::from_vec(vec![0xff]),
+        );
+        let builder = Builder::default()
+            .with_env_prefix("TRACING_AXIOM_LAZY_")
+            .with_env()?;
+        assert_eq!(builder.environment.as_deref(), Some("staging"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_service_version_and_build_info_resource() {
+        let resource = Builder::default()
+            .with_service_version("1.2.3")
+            .with_build_info(Some("abc123"), None::<&str>)
+            .resource();
+        assert_eq!(
+            resource.get(Key::from_static_str(SERVICE_VERSION)),
+            Some(Value::from("1.2.3"))
+        );
+        assert_eq!(
+            resource.get(Key::from_static_str(BUILD_GIT_SHA)),
+            Some(Value::from("abc123"))
+        );
+        assert_eq!(resource.get(Key::from_static_str(BUILD_TIMESTAMP)), None);
+    }
+
+    #[test]
+    fn test_service_instance_id_resource() {
+        let instance_id = |builder: Builder| {
+            builder
+                .resource()
+                .get(Key::from_static_str(SERVICE_INSTANCE_ID))
+                .map(|value| value.to_string())
+        };
+        let generated = instance_id(Builder::default()).expect("instance id");
+        assert_eq!(generated.len(), 36);
+        assert_eq!(generated.as_bytes()[14], b'4');
+        assert_eq!(instance_id(Builder::default()), Some(generated));
+        assert_eq!(
+            instance_id(Builder::default().with_service_instance_id("pod-7")),
+            Some("pod-7".to_string())
+        );
+        let resource = Resource::new([KeyValue::new(SERVICE_INSTANCE_ID, "from-resource")]);
+        assert_eq!(
+            instance_id(Builder::default().with_resource(resource)),
+            Some("from-resource".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_tags() -> Result<(), Error> {
+        assert_eq!(
+            parse_tags("team=payments, region = eu-west-1,")?,
+            vec![
+                KeyValue::new("team", "payments"),
+                KeyValue::new("region", "eu-west-1")
+            ]
+        );
+        assert_eq!(parse_tags("")?, vec![]);
+        assert_eq!(parse_tags("empty=")?, vec![KeyValue::new("empty", "")]);
+        assert!(matches!(parse_tags("team"), Err(Error::InvalidTag(t)) if t == "team"));
+        assert!(matches!(parse_tags("=value"), Err(Error::InvalidTag(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_resource_merges() {
+        let resource = Builder::default()
+            .with_service_name("builder")
+            .with_resource(Resource::new([
+                KeyValue::new(SERVICE_NAME, "resource"),
+                KeyValue::new("answer", 42),
+            ]))
+            .with_resource(Resource::new([KeyValue::new("answer", 43)]))
+            .resource();
+        assert_eq!(
+            resource.get(Key::from_static_str(SERVICE_NAME)),
+            Some(Value::from("builder"))
+        );
+        assert_eq!(
+            resource.get(Key::from_static_str("answer")),
+            Some(Value::I64(43))
+        );
+    }
+
+    #[test]
+    fn test_tags_are_additive() {
+        let builder = Builder::default()
+            .with_tags([("team", "payments")].iter().copied())
+            .with_tags([("region", "eu-west-1")].iter().copied())
+            .add_tag("answer", 42);
+        assert_eq!(
+            builder.tags,
+            vec![
+                KeyValue::new("team", "payments"),
+                KeyValue::new("region", "eu-west-1"),
+                KeyValue::new("answer", 42),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() -> Result<(), Box<dyn std::error::Error>> {
+        // Connections are queued, but never answered
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/v1/traces", listener.local_addr()?);
+        let builder = Builder::default()
+            .with_timeout(Duration::from_secs(30))
+            .with_request_timeout(Duration::from_millis(100));
+        let mut exporter = builder.http_exporter(url.parse()?, HashMap::new(), None, None, None)?;
+        let start = std::time::Instant::now();
+        assert!(exporter.export(Vec::new()).await.is_err());
+        assert!(start.elapsed() < Duration::from_secs(10));
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct AssertUri(&'static str);
+
+    #[async_trait::async_trait]
+    impl HttpClient for AssertUri {
+        async fn send(
+            &self,
+            request: http::Request<Vec<u8>>,
+        ) -> Result<http::Response<bytes::Bytes>, opentelemetry_http::HttpError> {
+            assert_eq!(request.uri(), self.0);
+            Ok(http::Response::new(bytes::Bytes::new()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_traces_path() -> Result<(), Box<dyn std::error::Error>> {
+        let url: Url = "https://gateway.example.com/axiom/".parse()?;
+        let builder = Builder::default().with_http_client(AssertUri(
+            "https://gateway.example.com/axiom/custom/v1/traces",
+        ));
+        let mut exporter = builder.http_exporter(
+            url.clone(),
+            HashMap::new(),
+            None,
+            None,
+            Some("/custom/v1/traces"),
+        )?;
+        exporter.export(Vec::new()).await?;
+
+        let builder = Builder::default()
+            .with_http_client(AssertUri("https://gateway.example.com/axiom/v1/traces"));
+        let mut exporter = builder.http_exporter(url.clone(), HashMap::new(), None, None, None)?;
+        exporter.export(Vec::new()).await?;
+
+        let builder = Builder::default()
+            .with_encoding(Encoding::Json)
+            .with_http_client(AssertUri("https://gateway.example.com/axiom/v1/traces"));
+        let mut exporter = builder.http_exporter(url, HashMap::new(), None, None, None)?;
+        exporter.export(Vec::new()).await?;
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct RespondWith(u16);
+
+    #[async_trait::async_trait]
+    impl HttpClient for RespondWith {
+        async fn send(
+            &self,
+            request: http::Request<Vec<u8>>,
+        ) -> Result<http::Response<bytes::Bytes>, opentelemetry_http::HttpError> {
+            assert_eq!(request.headers()["X-Axiom-Dataset"], "test");
+            let mut response = http::Response::new(bytes::Bytes::new());
+            *response.status_mut() = http::StatusCode::from_u16(self.0)?;
+            Ok(response)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_eager_connect() -> Result<(), Error> {
+        let builder = Builder::default()
+            .with_token("xaat-123456789")?
+            .with_dataset("test")?
+            .with_eager_connect(true);
+        assert!(builder
+            .with_http_client(RespondWith(200))
+            .check_endpoint()
+            .is_ok());
+
+        let builder = Builder::default()
+            .with_token("xaat-123456789")?
+            .with_dataset("test")?
+            .with_eager_connect(true)
+            .with_http_client(RespondWith(403));
+        assert!(matches!(
+            builder.tracer(),
+            Err(Error::EndpointCheck(message)) if message.contains("403")
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_options() {
+        let builder = Builder::default()
+            .with_pool_idle_timeout(Duration::from_secs(30))
+            .with_pool_max_idle_per_host(4)
+            .with_tcp_keepalive(Duration::from_secs(60));
+        let options = builder.client_options;
+        assert_eq!(options.pool_idle_timeout, Some(Duration::from_secs(30)));
+        assert_eq!(options.pool_max_idle_per_host, Some(4));
+        assert_eq!(options.tcp_keepalive, Some(Duration::from_secs(60)));
+        assert!(options.client(Duration::from_secs(3)).is_ok());
+        assert!(options.blocking_client(Duration::from_secs(3)).is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_build_with_provider() -> Result<(), Error> {
+        use opentelemetry::trace::{Span as _, Tracer as _};
+
+        let (_layer, provider) = Builder::default()
+            .with_dataset("test")?
+            .with_token("xaat-123456789")?
+            .build_with_provider::<Registry>()?;
+        let span = provider.tracer("other").start("span");
+        assert!(span.span_context().is_sampled());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_build_isolated() -> Result<(), Error> {
+        use crate::testing::Recording;
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        let (first, second) = (Recording::default(), Recording::default());
+        let (layer, guard) = Builder::default()
+            .with_exporter(first.clone())
+            .build_isolated::<Registry>()?;
+        let (other_layer, other_guard) = Builder::default()
+            .with_exporter(second.clone())
+            .build_isolated::<Registry>()?;
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            tracing::info_span!("first").in_scope(|| {});
+        });
+        tracing::subscriber::with_default(Registry::default().with(other_layer), || {
+            tracing::info_span!("second").in_scope(|| {});
+        });
+        guard.force_flush()?;
+        assert_eq!(first.names(), ["first"]);
+        // Dropping the guard exports the remaining spans
+        drop(other_guard);
+        assert_eq!(second.names(), ["second"]);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_isolated_stats() -> Result<(), Error> {
+        use crate::testing::Recording;
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        let (layer, guard) = Builder::default()
+            .with_exporter(Recording::default())
+            .build_isolated::<Registry>()?;
+        let (_other_layer, other_guard) = Builder::default()
+            .with_exporter(Recording::default())
+            .build_isolated::<Registry>()?;
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            tracing::info_span!("first").in_scope(|| {});
+        });
+        guard.force_flush()?;
+        assert!(guard.health().last_export.is_some());
+        assert_eq!(other_guard.health().last_export, None);
+        assert!(guard.queue_stats().is_some());
+        assert_eq!(other_guard.queue_stats().map(|stats| stats.len), Some(0));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_latency_sampling() -> Result<(), Error> {
+        use crate::testing::Recording;
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        let recording = Recording::default();
+        let (layer, guard) = Builder::default()
+            .with_exporter(recording.clone())
+            .with_latency_sampling(Duration::from_millis(50), 0.0)
+            .build_isolated::<Registry>()?;
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            tracing::info_span!("fast").in_scope(|| {});
+            tracing::info_span!("slow").in_scope(|| {
+                tracing::info_span!("slow child").in_scope(|| {});
+                std::thread::sleep(Duration::from_millis(60));
+            });
+        });
+        guard.force_flush()?;
+        assert_eq!(recording.names(), ["slow", "slow child"]);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serverless() -> Result<(), Error> {
+        let builder = Builder::default()
+            .with_dataset("test")?
+            .with_token("xaat-123456789")?
+            .serverless();
+        assert_eq!(builder.processor, Processor::Simple);
+        assert_eq!(builder.timeout, Some(Duration::from_secs(1)));
+        assert!(builder.tracer().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_id_generator() {
+        use opentelemetry::trace::{SpanId, TraceId};
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        #[derive(Debug, Default)]
+        struct Sequential {
+            trace_ids: AtomicU64,
+            span_ids: AtomicU64,
+        }
+
+        impl IdGenerator for Sequential {
+            fn new_trace_id(&self) -> TraceId {
+                let id = self.trace_ids.fetch_add(1, Ordering::Relaxed) + 1;
+                TraceId::from_bytes(u128::from(id).to_be_bytes())
+            }
+
+            fn new_span_id(&self) -> SpanId {
+                let id = self.span_ids.fetch_add(1, Ordering::Relaxed) + 1;
+                SpanId::from_bytes(id.to_be_bytes())
+            }
+        }
+
+        let (layer, spans) = Builder::default()
+            .with_id_generator(Sequential::default())
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("replayed").in_scope(|| {});
+        });
+
+        let span = spans.span("replayed").expect("replayed span");
+        assert_eq!(
+            span.span_context.trace_id(),
+            TraceId::from_bytes(1u128.to_be_bytes())
+        );
+        assert_eq!(
+            span.span_context.span_id(),
+            SpanId::from_bytes(1u64.to_be_bytes())
+        );
+    }
+
+    #[test]
+    fn test_span_processor() {
+        use opentelemetry::{trace::Span as _, Context};
+        use opentelemetry_sdk::{export::trace::SpanData, trace::Span};
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        #[derive(Debug)]
+        struct Enrich;
+
+        impl SpanProcessor for Enrich {
+            fn on_start(&self, span: &mut Span, _cx: &Context) {
+                span.set_attribute(KeyValue::new("enriched", true));
+            }
+
+            fn on_end(&self, _span: SpanData) {}
+
+            fn force_flush(&self) -> opentelemetry::trace::TraceResult<()> {
+                Ok(())
+            }
+
+            fn shutdown(&mut self) -> opentelemetry::trace::TraceResult<()> {
+                Ok(())
+            }
+        }
+
+        let (layer, spans) = Builder::default()
+            .with_span_processor(Enrich)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("enriched").in_scope(|| {});
+        });
+
+        let span = spans.span("enriched").expect("enriched span");
+        assert_eq!(
+            crate::testing::attribute(&span, "enriched"),
+            Some(&Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn test_instrumentation_scope() {
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        let scope = |builder: Builder| {
+            let (layer, spans) = builder.build_for_test();
+            let subscriber = tracing_subscriber::registry().with(layer);
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info_span!("scoped").in_scope(|| {});
+            });
+            spans
+                .span("scoped")
+                .expect("scoped span")
+                .instrumentation_lib
+        };
+
+        let default = scope(Builder::default());
+        assert_eq!(default.name, env!("CARGO_PKG_NAME"));
+        assert_eq!(default.version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        let custom = scope(Builder::default().with_instrumentation_scope(
+            "billing",
+            Some("2.0.0"),
+            Some("https://opentelemetry.io/schemas/1.24.0"),
+        ));
+        assert_eq!(custom.name, "billing");
+        assert_eq!(custom.version.as_deref(), Some("2.0.0"));
+        assert_eq!(
+            custom.schema_url.as_deref(),
+            Some("https://opentelemetry.io/schemas/1.24.0")
+        );
+    }
+
+    #[test]
+    fn test_span_limits() {
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        let (layer, spans) = Builder::default()
+            .with_max_events_per_span(200)
+            .with_max_attributes_per_span(1)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("verbose", a = 1, b = 2).in_scope(|| {
+                for i in 0..150 {
+                    tracing::info!(i);
+                }
+            });
+        });
+
+        let span = spans.span("verbose").expect("verbose span");
+        assert_eq!(span.events.len(), 150);
+        assert_eq!(span.attributes.len(), 1);
+    }
+
+    #[test]
+    fn test_location() {
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        let has_location = |builder: Builder| {
+            let (layer, spans) = builder.build_for_test();
+            let subscriber = tracing_subscriber::registry().with(layer);
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info_span!("located").in_scope(|| {});
+            });
+            let span = spans.span("located").expect("located span");
+            crate::testing::attribute(&span, "code.lineno").is_some()
+        };
+
+        assert!(has_location(Builder::default()));
+        assert!(has_location(Builder::default().with_location(true)));
+        assert!(!has_location(Builder::default().with_location(false)));
+    }
+
+    #[test]
+    fn test_disabled_without_token() {
+        use opentelemetry::trace::{Span as _, Tracer as _};
+
+        let tracer = Builder::default().disabled().tracer().expect("tracer");
+        assert!(!tracer.start("noop").is_recording());
+    }
+
+    #[test]
+    fn test_lenient_without_token() {
+        use opentelemetry::trace::{Span as _, Tracer as _};
+
+        let tracer = Builder::default().lenient().tracer().expect("tracer");
+        assert!(!tracer.start("noop").is_recording());
+        assert!(matches!(
+            Builder::default().lenient().with_token("invalid"),
+            Err(Error::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn test_lenient_warns_once() {
+        let messages = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = messages.clone();
+        let builder = Builder::default()
+            .lenient()
+            .with_internal_log(InternalLog::callback(move |level, message| {
+                recorded
+                    .lock()
+                    .expect("not poisoned")
+                    .push(format!("{level} {message}"));
+            }));
+        // Also without installing anything globally
+        assert!(builder.build_isolated::<Registry>().is_ok());
+        assert_eq!(
+            *messages.lock().expect("not poisoned"),
+            ["WARN the Axiom token or dataset is not set, spans are not sent to Axiom"]
+        );
+    }
+
+    #[test]
+    fn test_stdout_exporter_without_token() {
+        let builder = Builder::default().with_stdout_exporter();
+        assert!(builder.tracer().is_ok());
+    }
+
+    #[test]
+    fn test_custom_exporter_without_token() {
+        let builder = Builder::default().with_exporter(StdoutExporter);
+        assert!(builder.tracer().is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_collector_without_token() {
+        let builder = Builder::default().with_local_collector(4318);
+        assert_eq!(
+            builder.url.as_ref().map(Url::as_str),
+            Some("http://localhost:4318/")
+        );
+        assert!(builder.tracer().is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_secondary_endpoint() -> Result<(), Error> {
+        assert!(matches!(
+            Builder::default().with_secondary_endpoint("<invalid>", [("a", "b")]),
+            Err(Error::InvalidUrl(_))
+        ));
+        let builder = Builder::default()
+            .with_exporter(StdoutExporter)
+            .with_secondary_endpoint("http://localhost:4318", [("x-scope-orgid", "tempo")])?;
+        let (url, headers) = builder.secondary_endpoint.as_ref().expect("endpoint");
+        assert_eq!(url.as_str(), "http://localhost:4318/");
+        assert_eq!(headers["x-scope-orgid"], "tempo");
+        assert!(builder.tracer().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_background_runtime() -> Result<(), Error> {
+        // No tokio runtime here, like in a plain `fn main()`
+        let builder = Builder::default()
+            .with_dataset("test")?
+            .with_token("xaat-123456789")?;
+        assert!(builder.tracer().is_ok());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_custom_runtime() -> Result<(), Error> {
+        let builder = Builder::default()
+            .with_dataset("test")?
+            .with_token("xaat-123456789")?
+            .with_runtime(opentelemetry_sdk::runtime::Tokio)
+            .with_http_client(reqwest::Client::new());
+        assert!(builder.tracer().is_ok());
+        Ok(())
+    }
+
+    #[cfg(feature = "async-std")]
+    #[test]
+    fn test_async_std() -> Result<(), Error> {
+        async_std::task::block_on(async {
+            let builder = Builder::default()
+                .with_dataset("test")?
+                .with_token("xaat-123456789")?
+                .with_async_std();
+            assert!(builder.tracer().is_ok());
+            Ok(())
+        })
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_valid_token_env() -> Result<(), Error> {
         // Note that we can't test the init/try_init funcs here because OTEL
@@ -362,4 +3251,19 @@ mod tests {
         assert!(result.is_ok(), "{:?}", result.err());
         Ok(())
     }
+
+    #[test]
+    fn test_user_agent_suffix() {
+        let version = env!("CARGO_PKG_VERSION");
+        assert_eq!(
+            Builder::default().user_agent(),
+            format!("tracing-axiom/{version}")
+        );
+        assert_eq!(
+            Builder::default()
+                .with_user_agent_suffix("checkout/1.4.2")
+                .user_agent(),
+            format!("tracing-axiom/{version} checkout/1.4.2")
+        );
+    }
 }
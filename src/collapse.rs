@@ -0,0 +1,238 @@
+use opentelemetry::{
+    trace::{Span as _, SpanId, Status, TraceContextExt as _, TraceResult},
+    Context, KeyValue,
+};
+use opentelemetry_sdk::{
+    export::trace::SpanData,
+    trace::{Span, SpanProcessor},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+/// Collapses runs of consecutive child spans with the same name under one
+/// parent into a single span, see
+/// [`Builder::with_span_collapsing`](crate::Builder::with_span_collapsing).
+///
+/// Failed spans and spans with children end a run and are exported as they
+/// are, so no span loses its parent.
+#[derive(Debug)]
+pub(crate) struct Collapse {
+    processor: Box<dyn SpanProcessor>,
+    min_run: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The current run of children per parent span.
+    runs: HashMap<SpanId, Run>,
+    /// The running spans that have children.
+    parents: HashSet<SpanId>,
+}
+
+#[derive(Debug)]
+struct Run {
+    /// The spans of the run until it is long enough to collapse, then only
+    /// the first one, which becomes the collapsed span.
+    spans: Vec<SpanData>,
+    count: usize,
+    total: Duration,
+    max: Duration,
+}
+
+impl Run {
+    fn new(span: SpanData) -> Self {
+        let duration = duration(&span);
+        Self {
+            spans: vec![span],
+            count: 1,
+            total: duration,
+            max: duration,
+        }
+    }
+
+    fn push(&mut self, span: SpanData, min_run: usize) {
+        let duration = duration(&span);
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+        if self.count >= min_run {
+            self.spans.truncate(1);
+            if let Some(first) = self.spans.first_mut() {
+                first.end_time = span.end_time;
+            }
+        } else {
+            self.spans.push(span);
+        }
+    }
+
+    /// The spans to export, the collapsed span if the run is long enough.
+    fn finish(mut self, min_run: usize) -> Vec<SpanData> {
+        if self.count < min_run {
+            return self.spans;
+        }
+        if let Some(first) = self.spans.first_mut() {
+            first.events.events.clear();
+            first.attributes.extend([
+                KeyValue::new(
+                    "collapsed.count",
+                    i64::try_from(self.count).unwrap_or(i64::MAX),
+                ),
+                KeyValue::new("collapsed.duration_ms.total", millis(self.total)),
+                KeyValue::new("collapsed.duration_ms.max", millis(self.max)),
+            ]);
+        }
+        self.spans
+    }
+}
+
+fn duration(span: &SpanData) -> Duration {
+    span.end_time
+        .duration_since(span.start_time)
+        .unwrap_or_default()
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl Collapse {
+    pub(crate) fn new(processor: Box<dyn SpanProcessor>, min_run: usize) -> Self {
+        Self {
+            processor,
+            min_run: min_run.max(2),
+            state: Mutex::default(),
+        }
+    }
+
+    fn export(&self, spans: Vec<SpanData>) {
+        for span in spans {
+            self.processor.on_end(span);
+        }
+    }
+
+    fn finish_all(&self) {
+        let runs = std::mem::take(
+            &mut self
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .runs,
+        );
+        for run in runs.into_values() {
+            self.export(run.finish(self.min_run));
+        }
+    }
+}
+
+impl SpanProcessor for Collapse {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        let parent = cx.span();
+        let parent = parent.span_context();
+        if parent.is_valid() && !parent.is_remote() {
+            self.state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .parents
+                .insert(parent.span_id());
+        }
+        self.processor.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        let mut guard = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = &mut *guard;
+        let span_id = span.span_context.span_id();
+        let has_children = state.parents.remove(&span_id);
+        // The children of the span ended, and so did their run
+        let mut finished: Vec<Run> = state.runs.remove(&span_id).into_iter().collect();
+        let parent_id = span.parent_span_id;
+        // Only children of running local spans, so every run ends with its parent
+        let collapsible = state.parents.contains(&parent_id)
+            && !has_children
+            && !matches!(span.status, Status::Error { .. });
+        let uncollapsed = if collapsible {
+            match state.runs.get_mut(&parent_id) {
+                Some(run) if run.spans.first().is_some_and(|s| s.name == span.name) => {
+                    run.push(span, self.min_run);
+                }
+                _ => finished.extend(state.runs.insert(parent_id, Run::new(span))),
+            }
+            None
+        } else {
+            finished.extend(state.runs.remove(&parent_id));
+            Some(span)
+        };
+        drop(guard);
+        for run in finished {
+            self.export(run.finish(self.min_run));
+        }
+        if let Some(span) = uncollapsed {
+            self.processor.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        self.finish_all();
+        self.processor.force_flush()
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        self.finish_all();
+        self.processor.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Recording;
+    use opentelemetry::Value;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_collapse() {
+        let recording = Recording::default();
+        let collapse = Collapse::new(Box::new(recording.clone()), 3);
+        let (layer, _spans) = crate::builder("collapse")
+            .with_span_processor(collapse)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request").in_scope(|| {
+                for _ in 0..5 {
+                    tracing::info_span!("row_decode").in_scope(|| {});
+                }
+                tracing::info_span!("row_decode").in_scope(|| tracing::error!("invalid row"));
+                for _ in 0..2 {
+                    tracing::info_span!("row_decode").in_scope(|| {});
+                }
+                tracing::info_span!("render").in_scope(|| {});
+            });
+        });
+
+        let spans = recording.spans();
+        let names = spans
+            .iter()
+            .map(|span| span.name.as_ref())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "row_decode",
+                "row_decode",
+                "row_decode",
+                "row_decode",
+                "render",
+                "request"
+            ]
+        );
+        let count = |span: &SpanData| crate::testing::attribute(span, "collapsed.count").cloned();
+        assert_eq!(count(&spans[0]), Some(Value::I64(5)));
+        assert_eq!(count(&spans[1]), None);
+        assert_eq!(count(&spans[2]), None);
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::OnceLock;
+
+/// The cgroups of the process, with the container ID in their path with
+/// cgroup v1.
+const CGROUP: &str = "/proc/self/cgroup";
+/// The mounts of the process, with the container ID in the path of e.g. the
+/// `/etc/hostname` mount with cgroup v2.
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+/// The ID of the container the process runs in, if it can be read from the
+/// cgroup files. Read once per process.
+pub(crate) fn container_id() -> Option<&'static str> {
+    static CONTAINER_ID: OnceLock<Option<String>> = OnceLock::new();
+    CONTAINER_ID
+        .get_or_init(|| {
+            let read = |path| std::fs::read_to_string(path).ok();
+            read(CGROUP)
+                .and_then(|cgroup| from_cgroup(&cgroup))
+                .or_else(|| read(MOUNTINFO).and_then(|mountinfo| from_mountinfo(&mountinfo)))
+        })
+        .as_deref()
+}
+
+/// The container ID at the end of a cgroup path, e.g.
+/// `/docker/<id>`, `/kubepods/burstable/pod<uid>/<id>` or
+/// `/system.slice/docker-<id>.scope`.
+fn from_cgroup(cgroup: &str) -> Option<String> {
+    cgroup.lines().find_map(|line| {
+        let segment = line.rsplit('/').next()?;
+        let segment = segment.strip_suffix(".scope").unwrap_or(segment);
+        let id = segment.rsplit(['-', ':']).next()?;
+        is_container_id(id).then(|| id.to_string())
+    })
+}
+
+/// The container ID in the path of a mount below a `containers` directory,
+/// e.g. `/var/lib/docker/containers/<id>/hostname`.
+fn from_mountinfo(mountinfo: &str) -> Option<String> {
+    mountinfo.lines().find_map(|line| {
+        let mut segments = line.split_whitespace().flat_map(|field| field.split('/'));
+        segments.find(|segment| *segment == "containers")?;
+        segments
+            .next()
+            .filter(|id| is_container_id(id))
+            .map(ToString::to_string)
+    })
+}
+
+/// Whether the ID looks like one of Docker, containerd or CRI-O, 64
+/// hexadecimal characters.
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "7be92808767a667f35c8505cbf40d14e931ef6db5b0210329cf193b15ba9d605";
+
+    #[test]
+    fn test_from_cgroup() {
+        for path in [
+            format!("/docker/{ID}"),
+            format!("/kubepods/burstable/pod2c48913c-b29f-11e7-9350-020968147796/{ID}"),
+            format!("/system.slice/docker-{ID}.scope"),
+            format!("/kubepods.slice/kubepods-besteffort.slice/cri-containerd:{ID}"),
+        ] {
+            let cgroup = format!("12:pids:/user.slice\n1:name=systemd:{path}\n");
+            assert_eq!(from_cgroup(&cgroup).as_deref(), Some(ID), "{path}");
+        }
+        assert_eq!(
+            from_cgroup("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_from_mountinfo() {
+        let mountinfo = format!(
+            "1 0 0:1 / / rw - overlay overlay rw\n\
+             2 1 8:1 /var/lib/docker/containers/{ID}/hostname /etc/hostname rw - ext4 /dev/sda1 rw\n"
+        );
+        assert_eq!(from_mountinfo(&mountinfo).as_deref(), Some(ID));
+        assert_eq!(
+            from_mountinfo("1 0 0:1 / / rw - overlay overlay rw\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    fmt,
+    sync::{Arc, PoisonError, RwLock},
+};
+use tracing_core::Level;
+
+/// The target of events recorded with [`InternalLog::Tracing`].
+const TARGET: &str = "tracing_axiom";
+
+/// Where the crate reports its own problems, like failed exports or settings
+/// it fell back from, see
+/// [`Builder::with_internal_log`](crate::Builder::with_internal_log).
+#[derive(Clone, Default)]
+pub enum InternalLog {
+    /// Print the messages to stderr.
+    #[default]
+    Stderr,
+    /// Record the messages as `tracing` events with the `tracing_axiom`
+    /// target, e.g. to show them with the other logs of the application.
+    /// Exclude the target with
+    /// [`Builder::with_exclude_targets`](crate::Builder::with_exclude_targets)
+    /// to keep them from being sent to Axiom as well.
+    Tracing,
+    /// Call the function with the level and the message of every message.
+    #[allow(clippy::type_complexity)]
+    Callback(Arc<dyn Fn(Level, &str) + Send + Sync>),
+}
+
+impl InternalLog {
+    /// Report messages to the function.
+    pub fn callback(f: impl Fn(Level, &str) + Send + Sync + 'static) -> Self {
+        Self::Callback(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for InternalLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stderr => f.write_str("Stderr"),
+            Self::Tracing => f.write_str("Tracing"),
+            Self::Callback(_) => f.write_str("Callback(..)"),
+        }
+    }
+}
+
+/// The internal log of the most recently built layer that configured one.
+static DIAGNOSTICS: RwLock<Option<Diagnostics>> = RwLock::new(None);
+
+#[derive(Debug, Clone)]
+pub(crate) struct Diagnostics {
+    pub(crate) level: Level,
+    pub(crate) log: InternalLog,
+}
+
+impl Diagnostics {
+    /// Reports the crate's own messages and the errors `opentelemetry` would
+    /// otherwise print with its global error handler to the log.
+    pub(crate) fn install(self) {
+        *DIAGNOSTICS.write().unwrap_or_else(PoisonError::into_inner) = Some(self);
+        // Errors only if the lock is poisoned, then the default handler stays.
+        let _ = opentelemetry::global::set_error_handler(|error| {
+            report(Level::ERROR, &error.to_string());
+        });
+    }
+
+    pub(crate) fn log(&self, level: Level, message: &str) {
+        if level > self.level {
+            return;
+        }
+        match &self.log {
+            InternalLog::Stderr => eprintln!("tracing-axiom {level}: {message}"),
+            InternalLog::Tracing => match level {
+                Level::ERROR => tracing::error!(target: TARGET, "{message}"),
+                Level::WARN => tracing::warn!(target: TARGET, "{message}"),
+                Level::INFO => tracing::info!(target: TARGET, "{message}"),
+                Level::DEBUG => tracing::debug!(target: TARGET, "{message}"),
+                Level::TRACE => tracing::trace!(target: TARGET, "{message}"),
+            },
+            InternalLog::Callback(callback) => callback(level, message),
+        }
+    }
+}
+
+/// Reports a message to the internal log, if one is configured.
+pub(crate) fn report(level: Level, message: &str) {
+    let diagnostics = DIAGNOSTICS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    if let Some(diagnostics) = diagnostics {
+        diagnostics.log(level, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_callback() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let recorded = messages.clone();
+        let diagnostics = Diagnostics {
+            level: Level::INFO,
+            log: InternalLog::callback(move |level, message| {
+                recorded
+                    .lock()
+                    .expect("not poisoned")
+                    .push(format!("{level} {message}"));
+            }),
+        };
+        diagnostics.log(Level::WARN, "warned");
+        diagnostics.log(Level::DEBUG, "too verbose");
+        diagnostics.log(Level::INFO, "informed");
+        assert_eq!(
+            *messages.lock().expect("not poisoned"),
+            ["WARN warned", "INFO informed"]
+        );
+    }
+}
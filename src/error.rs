@@ -1,3 +1,4 @@
+#[cfg(not(feature = "noop"))]
 use opentelemetry::trace;
 use tracing_subscriber::util::TryInitError;
 
@@ -5,6 +6,7 @@ use tracing_subscriber::util::TryInitError;
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     /// Failed to configure the tracer.
+    #[cfg(not(feature = "noop"))]
     #[error("Failed to configure tracer: {0}")]
     TraceError(#[from] trace::TraceError),
 
@@ -32,10 +34,76 @@ pub enum Error {
     #[error("Dataset is empty")]
     EmptyDataset,
 
+    /// A placeholder in the dataset name has no value.
+    #[error("Dataset placeholder {{{0}}} has no value")]
+    UnresolvedPlaceholder(String),
+
     /// The required Axiom dataset name is invalid.
     #[error("Invalid URL: {0}")]
     InvalidUrl(#[from] url::ParseError),
 
+    /// The traces path does not form a valid URL with the Axiom URL.
+    #[error("Invalid traces path {0:?}")]
+    InvalidTracesPath(String),
+
+    /// A tag is not in the `key=value` format.
+    #[error("Invalid tag {0:?} (expected key=value)")]
+    InvalidTag(String),
+
+    /// The log level is not one of `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    #[error("Invalid log level {0:?}")]
+    InvalidLogLevel(String),
+
+    /// A redaction pattern is not a valid regular expression.
+    #[cfg(not(feature = "noop"))]
+    #[error("Invalid redaction pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+
+    /// The HTTP client for the exporter could not be created.
+    #[error("Failed to create HTTP client")]
+    HttpClient,
+
+    /// The endpoint could not be reached or rejected the token or dataset
+    /// when checked with
+    /// [`Builder::with_eager_connect`](crate::Builder::with_eager_connect).
+    #[error("Endpoint check failed: {0}")]
+    EndpointCheck(String),
+
+    /// Axiom could not be queried, see
+    /// [`testing::wait_for_trace`](crate::testing::wait_for_trace).
+    #[error("Failed to query Axiom: {0}")]
+    Query(String),
+
+    /// The background thread driving the exporter could not be started.
+    #[error("Failed to start background runtime: {0}")]
+    Runtime(#[from] std::io::Error),
+
+    /// The signal handlers of
+    /// [`flush_on_shutdown`](crate::flush_on_shutdown) could not be
+    /// registered.
+    #[error("Failed to register signal handler: {0}")]
+    Signal(std::io::Error),
+
+    /// A span file could not be read or written.
+    #[error("Failed to access span file: {0}")]
+    File(std::io::Error),
+
+    /// A line of a span file is not a span as written by the file exporter.
+    #[error("Invalid span in line {0}: {1}")]
+    InvalidSpan(usize, String),
+
+    /// The export metrics could not be registered, e.g. because they already
+    /// are.
+    #[cfg(all(feature = "prometheus", not(feature = "noop")))]
+    #[error("Failed to register metrics: {0}")]
+    Metrics(#[from] prometheus::Error),
+
+    /// The exporter of the metrics could not be created, or exporting them
+    /// failed.
+    #[cfg(all(feature = "metrics", not(feature = "noop")))]
+    #[error("Failed to configure metrics: {0}")]
+    MetricsExporter(#[from] opentelemetry::metrics::MetricsError),
+
     /// The environment variable is malformed unicode.
     #[error("Environment variable {0} contains invalid non Unciode ( UTF-8 ) content")]
     EnvVarNotUnicode(String),
@@ -43,4 +111,30 @@ pub enum Error {
     /// The environment variable is not present.
     #[error("Environment variable {0} is required but missing")]
     EnvVarMissing(&'static str),
+
+    /// Events could not be sent with [`ingest`](crate::ingest).
+    #[error("Failed to ingest events: {0}")]
+    Ingest(String),
+}
+
+/// The error returned by [`shutdown_with_timeout`](crate::shutdown_with_timeout).
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum ShutdownError {
+    /// The export did not finish before the deadline.
+    #[error("Export did not finish before the deadline, {pending} spans were still queued")]
+    Timeout {
+        /// The number of spans that were still waiting in the export queue.
+        pending: usize,
+    },
+
+    /// Exporting some of the spans failed.
+    #[cfg(not(feature = "noop"))]
+    #[error("Failed to export spans: {0}")]
+    Export(#[from] trace::TraceError),
+
+    /// Exporting the remaining metrics failed.
+    #[cfg(all(feature = "metrics", not(feature = "noop")))]
+    #[error("Failed to export metrics: {0}")]
+    Metrics(#[from] opentelemetry::metrics::MetricsError),
 }
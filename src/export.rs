@@ -0,0 +1,637 @@
+mod breaker;
+mod enrich;
+mod failover;
+mod limits;
+mod otlp_json;
+mod route;
+mod split;
+
+use crate::{health::ExportHealth, redaction::Redaction};
+pub(crate) use breaker::CircuitBreaker;
+pub(crate) use enrich::EventEnricher;
+pub use enrich::ExportedEvent;
+pub(crate) use failover::Failover;
+pub use limits::folded_fields;
+pub(crate) use limits::FieldLimits;
+use opentelemetry::{
+    trace::{TraceError, TraceResult},
+    Context,
+};
+use opentelemetry::{Array, Key, KeyValue, StringValue, Value};
+use opentelemetry_sdk::{
+    export::trace::{ExportResult, SpanData, SpanExporter},
+    trace::{Span, SpanProcessor},
+};
+pub use otlp_json::Encoding;
+pub(crate) use otlp_json::OtlpJsonExporter;
+pub(crate) use route::{DatasetRouter, RouteDataset, SharedDataset};
+use serde_json::json;
+pub(crate) use split::BatchSplitter;
+use std::{
+    borrow::Cow,
+    fmt,
+    future::Future,
+    pin::{pin, Pin},
+    sync::Arc,
+    task::Poll,
+};
+
+/// Appended to truncated values.
+const ELLIPSIS: &str = "…";
+/// The keys of the truncated values of a span, event or link.
+const TRUNCATED_FIELDS: &str = "truncated_fields";
+
+/// Changes applied to every span before it is exported.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Transforms {
+    pub(crate) span_name_mapper: Option<SpanNameMapper>,
+    pub(crate) attribute_transformer: Option<AttributeTransformer>,
+    pub(crate) event_enricher: Option<EventEnricher>,
+    pub(crate) redaction: Option<Redaction>,
+    pub(crate) max_attribute_length: Option<usize>,
+    pub(crate) field_limits: Option<FieldLimits>,
+}
+
+impl Transforms {
+    pub(crate) fn apply(&self, span: &mut SpanData) {
+        if let Some(mapper) = &self.span_name_mapper {
+            let name = (mapper.0)(&span.name);
+            if name != span.name {
+                span.name = Cow::Owned(name.into_owned());
+            }
+        }
+        if let Some(transformer) = &self.attribute_transformer {
+            transformer.apply(span);
+        }
+        if let Some(enricher) = &self.event_enricher {
+            enricher.apply(span);
+        }
+        if let Some(redaction) = &self.redaction {
+            redaction.apply(span);
+        }
+        if let Some(max_len) = self.max_attribute_length {
+            truncate_span(span, max_len);
+        }
+        if let Some(field_limits) = self.field_limits {
+            field_limits.apply(span);
+        }
+    }
+}
+
+/// Renames spans before they are exported, see
+/// [`Builder::with_span_name_mapper`](crate::Builder::with_span_name_mapper).
+#[derive(Clone)]
+pub(crate) struct SpanNameMapper(pub(crate) Arc<dyn Fn(&str) -> Cow<'_, str> + Send + Sync>);
+
+impl fmt::Debug for SpanNameMapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SpanNameMapper(..)")
+    }
+}
+
+/// Renames, converts or drops the attributes of spans and their events before
+/// they are exported, see
+/// [`Builder::with_attribute_transformer`](crate::Builder::with_attribute_transformer).
+#[derive(Clone)]
+pub(crate) struct AttributeTransformer(
+    pub(crate) Arc<dyn Fn(Key, Value) -> Option<(Key, Value)> + Send + Sync>,
+);
+
+impl AttributeTransformer {
+    fn apply(&self, span: &mut SpanData) {
+        span.attributes = self.transform(std::mem::take(&mut span.attributes));
+        for event in &mut span.events.events {
+            event.attributes = self.transform(std::mem::take(&mut event.attributes));
+        }
+    }
+
+    fn transform(&self, attributes: Vec<KeyValue>) -> Vec<KeyValue> {
+        attributes
+            .into_iter()
+            .filter_map(|kv| (self.0)(kv.key, kv.value))
+            .map(|(key, value)| KeyValue::new(key, value))
+            .collect()
+    }
+}
+
+impl fmt::Debug for AttributeTransformer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AttributeTransformer(..)")
+    }
+}
+
+fn truncate_span(span: &mut SpanData, max_len: usize) {
+    truncate_attributes(&mut span.attributes, max_len, Vec::new());
+    for event in &mut span.events.events {
+        let mut truncated = Vec::new();
+        if let Some(name) = truncate(&event.name, max_len) {
+            event.name = Cow::Owned(name);
+            truncated.push(StringValue::from("name"));
+        }
+        truncate_attributes(&mut event.attributes, max_len, truncated);
+    }
+    for link in &mut span.links.links {
+        truncate_attributes(&mut link.attributes, max_len, Vec::new());
+    }
+}
+
+/// Truncates the string values and lists their keys after the ones already
+/// truncated in a `truncated_fields` attribute, if there are any.
+fn truncate_attributes(
+    attributes: &mut Vec<KeyValue>,
+    max_len: usize,
+    mut truncated: Vec<StringValue>,
+) {
+    let cut = |s: &mut StringValue| match truncate(s.as_str(), max_len) {
+        Some(value) => {
+            *s = StringValue::from(value);
+            true
+        }
+        None => false,
+    };
+    for kv in attributes.iter_mut() {
+        let was_cut = match &mut kv.value {
+            Value::String(s) => cut(s),
+            Value::Array(Array::String(values)) => values
+                .iter_mut()
+                .fold(false, |was_cut, s| cut(s) || was_cut),
+            _ => false,
+        };
+        if was_cut {
+            truncated.push(StringValue::from(kv.key.as_str().to_owned()));
+        }
+    }
+    if !truncated.is_empty() {
+        attributes.push(KeyValue::new(
+            TRUNCATED_FIELDS,
+            Value::Array(Array::String(truncated)),
+        ));
+    }
+}
+
+/// The value cut to at most `max_len` bytes on a char boundary followed by an
+/// ellipsis, if it is longer.
+fn truncate(value: &str, max_len: usize) -> Option<String> {
+    if value.len() <= max_len {
+        return None;
+    }
+    let end = (0..=max_len)
+        .rev()
+        .find(|&i| value.is_char_boundary(i))
+        .unwrap_or_default();
+    Some(format!("{}{ELLIPSIS}", &value[..end]))
+}
+
+/// The value as it is shown in Axiom.
+pub(crate) fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Bool(b) => json!(b),
+        Value::I64(i) => json!(i),
+        Value::F64(f) => json!(f),
+        Value::String(s) => json!(s.as_str()),
+        Value::Array(Array::Bool(a)) => json!(a),
+        Value::Array(Array::I64(a)) => json!(a),
+        Value::Array(Array::F64(a)) => json!(a),
+        Value::Array(Array::String(a)) => {
+            json!(a.iter().map(StringValue::as_str).collect::<Vec<_>>())
+        }
+    }
+}
+
+/// Applies the [`Transforms`] to every batch before handing it to the exporter,
+/// and to the secondary exporter if there is one. The results of the primary
+/// exporter are recorded for [`health`](crate::health), and the circuit breaker
+/// diverts batches to the fallback exporter while it is open. Failures of the
+/// secondary exporter are passed to the global error handler of
+/// `opentelemetry`, which prints them to stderr unless an internal log is set.
+#[derive(Debug)]
+pub(crate) struct ExportPipeline {
+    exporter: Box<dyn SpanExporter>,
+    secondary: Option<Box<dyn SpanExporter>>,
+    transforms: Transforms,
+    health: Arc<ExportHealth>,
+    breaker: Option<CircuitBreaker>,
+    fallback: Option<Box<dyn SpanExporter>>,
+}
+
+impl ExportPipeline {
+    // Only the stdout and file exporters are not boxed, which wasm doesn't have
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) fn new(exporter: impl SpanExporter + 'static, transforms: Transforms) -> Self {
+        Self::boxed(Box::new(exporter), transforms)
+    }
+
+    pub(crate) fn boxed(exporter: Box<dyn SpanExporter>, transforms: Transforms) -> Self {
+        Self {
+            exporter,
+            secondary: None,
+            transforms,
+            health: Arc::default(),
+            breaker: None,
+            fallback: None,
+        }
+    }
+
+    pub(crate) fn health(&self) -> &Arc<ExportHealth> {
+        &self.health
+    }
+
+    /// Also send every batch to the secondary exporter.
+    pub(crate) fn with_secondary(mut self, exporter: Box<dyn SpanExporter>) -> Self {
+        self.secondary = Some(exporter);
+        self
+    }
+
+    /// Stop sending batches to the exporter while the breaker is open, and send
+    /// them to the fallback exporter instead if there is one.
+    pub(crate) fn with_circuit_breaker(
+        mut self,
+        breaker: CircuitBreaker,
+        fallback: Option<Box<dyn SpanExporter>>,
+    ) -> Self {
+        self.breaker = Some(breaker);
+        self.fallback = fallback;
+        self
+    }
+}
+
+impl SpanExporter for ExportPipeline {
+    fn export(
+        &mut self,
+        mut batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        for span in &mut batch {
+            self.transforms.apply(span);
+        }
+        let secondary = self
+            .secondary
+            .as_mut()
+            .map(|secondary| secondary.export(batch.clone()));
+        let (primary, fallback) = if self.breaker.as_ref().map_or(true, CircuitBreaker::allow) {
+            (Some(self.exporter.export(batch)), None)
+        } else {
+            (
+                None,
+                self.fallback
+                    .as_mut()
+                    .map(|fallback| fallback.export(batch)),
+            )
+        };
+        let health = self.health.clone();
+        let breaker = self.breaker.clone();
+        let primary = async move {
+            if let Some(primary) = primary {
+                let result = primary.await;
+                health.record(&result);
+                if let Some(breaker) = breaker {
+                    breaker.record(&result);
+                }
+                result
+            } else if let Some(fallback) = fallback {
+                fallback.await
+            } else {
+                Err(TraceError::from(
+                    "the circuit breaker is open, the batch was dropped",
+                ))
+            }
+        };
+        match secondary {
+            Some(secondary) => Box::pin(with_secondary(primary, secondary, "export")),
+            None => Box::pin(primary),
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.exporter.shutdown();
+        if let Some(secondary) = &mut self.secondary {
+            secondary.shutdown();
+        }
+        if let Some(fallback) = &mut self.fallback {
+            fallback.shutdown();
+        }
+    }
+
+    fn force_flush(&mut self) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        let secondary = self
+            .secondary
+            .as_mut()
+            .map(|secondary| secondary.force_flush());
+        let fallback = self
+            .fallback
+            .as_mut()
+            .map(|fallback| fallback.force_flush());
+        let primary = self.exporter.force_flush();
+        // The fallback holds the batches diverted while the breaker was open
+        let primary = async move {
+            let result = primary.await;
+            match fallback {
+                Some(fallback) => result.and(fallback.await),
+                None => result,
+            }
+        };
+        match secondary {
+            Some(secondary) => Box::pin(with_secondary(primary, secondary, "flush")),
+            None => Box::pin(primary),
+        }
+    }
+}
+
+/// Awaits the primary and secondary export together, so neither waits for
+/// the other, and returns the result of the primary one. Failures of the
+/// secondary one are only reported, as they must not fail the export to Axiom.
+async fn with_secondary(
+    primary: impl Future<Output = ExportResult>,
+    secondary: impl Future<Output = ExportResult>,
+    action: &'static str,
+) -> ExportResult {
+    let (mut primary_export, mut secondary_export) = (pin!(primary), pin!(secondary));
+    let (mut primary_result, mut secondary_result) = (None, None);
+    let (primary, secondary) = std::future::poll_fn(|cx| {
+        if primary_result.is_none() {
+            if let Poll::Ready(result) = primary_export.as_mut().poll(cx) {
+                primary_result = Some(result);
+            }
+        }
+        if secondary_result.is_none() {
+            if let Poll::Ready(result) = secondary_export.as_mut().poll(cx) {
+                secondary_result = Some(result);
+            }
+        }
+        match (primary_result.take(), secondary_result.take()) {
+            (Some(primary), Some(secondary)) => Poll::Ready((primary, secondary)),
+            (primary, secondary) => {
+                primary_result = primary;
+                secondary_result = secondary;
+                Poll::Pending
+            }
+        }
+    })
+    .await;
+    if let Err(error) = secondary {
+        // Not only to the internal log, which is off by default, as the
+        // secondary exporter may be the only copy of the spans
+        opentelemetry::global::handle_error(TraceError::from(format!(
+            "the secondary {action} failed: {error}"
+        )));
+    }
+    primary
+}
+
+/// A span processor added with
+/// [`Builder::with_span_processor`](crate::Builder::with_span_processor).
+#[derive(Debug)]
+pub(crate) struct BoxedSpanProcessor(pub(crate) Box<dyn SpanProcessor>);
+
+impl SpanProcessor for BoxedSpanProcessor {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.0.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        self.0.on_end(span);
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        self.0.force_flush()
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        self.0.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Recording;
+    use std::{
+        sync::{Mutex, PoisonError},
+        time::Duration,
+    };
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    fn event_names(recording: &Recording) -> Vec<String> {
+        recording
+            .spans()
+            .iter()
+            .flat_map(|span| span.events.iter().map(|event| event.name.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_secondary_exporter() {
+        let (layer, spans) = crate::builder("export").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("first").in_scope(|| tracing::info!("hello"));
+            tracing::info_span!("second").in_scope(|| tracing::info!("world"));
+        });
+
+        let (primary, secondary) = (Recording::default(), Recording::default());
+        let transforms = Transforms {
+            max_attribute_length: Some(3),
+            ..Transforms::default()
+        };
+        let mut pipeline = ExportPipeline::new(primary.clone(), transforms)
+            .with_secondary(Box::new(secondary.clone()));
+        let result = pipeline.export(spans.finished_spans()).await;
+        assert!(result.is_ok());
+        // Both get the transformed spans
+        assert_eq!(event_names(&primary), ["hel…", "wor…"]);
+        assert_eq!(event_names(&secondary), event_names(&primary));
+    }
+
+    #[test]
+    fn test_truncated_fields() {
+        let (layer, spans) = crate::builder("export").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let long = "x".repeat(100);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("span", payload = %long, id = 1).in_scope(|| {
+                tracing::info!(body = %long, short = "ok", "hello");
+            });
+        });
+
+        let transforms = Transforms {
+            max_attribute_length: Some(64),
+            ..Transforms::default()
+        };
+        let mut span = spans.finished_spans().remove(0);
+        transforms.apply(&mut span);
+        let truncated = |attributes: &[KeyValue]| {
+            attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == TRUNCATED_FIELDS)
+                .map(|kv| kv.value.clone())
+        };
+        let keys = |keys: &[&'static str]| {
+            Value::Array(Array::String(keys.iter().map(|&key| key.into()).collect()))
+        };
+        assert_eq!(truncated(&span.attributes), Some(keys(&["payload"])));
+        assert_eq!(
+            truncated(&span.events.events[0].attributes),
+            Some(keys(&["body"]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failing_secondary_exporter() {
+        static ERRORS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        opentelemetry::global::set_error_handler(|error| {
+            ERRORS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(error.to_string());
+        })
+        .expect("error handler");
+
+        let primary = Recording::default();
+        let mut pipeline = ExportPipeline::new(primary.clone(), Transforms::default())
+            .with_secondary(Box::new(Failing));
+        assert!(pipeline.export(Vec::new()).await.is_ok());
+        assert_eq!(pipeline.health().consecutive_failures(), 0);
+        // Reported even without an internal log
+        assert!(ERRORS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .any(|error| error.contains("the secondary export failed: unavailable")));
+
+        let mut pipeline =
+            ExportPipeline::new(Failing, Transforms::default()).with_secondary(Box::new(primary));
+        assert!(pipeline.export(Vec::new()).await.is_err());
+    }
+
+    #[derive(Debug)]
+    struct Failing;
+
+    impl SpanExporter for Failing {
+        fn export(
+            &mut self,
+            _batch: Vec<SpanData>,
+        ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+            Box::pin(async { Err("unavailable".into()) })
+        }
+
+        fn force_flush(&mut self) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+            Box::pin(async { Err("unavailable".into()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        let mut pipeline = ExportPipeline::new(Failing, Transforms::default());
+        for _ in 0..2 {
+            assert!(pipeline.export(Vec::new()).await.is_err());
+        }
+        assert_eq!(pipeline.health().consecutive_failures(), 2);
+
+        // A successful export resets the failures
+        pipeline.exporter = Box::new(Recording::default());
+        assert!(pipeline.export(Vec::new()).await.is_ok());
+        assert_eq!(pipeline.health().consecutive_failures(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_fallback() {
+        let fallback = Recording::default();
+        let mut pipeline = ExportPipeline::new(Failing, Transforms::default())
+            .with_circuit_breaker(
+                CircuitBreaker::new(1, Duration::from_secs(3600)),
+                Some(Box::new(fallback.clone())),
+            );
+        let (layer, spans) = crate::builder("export").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("span").in_scope(|| tracing::info!("hello"));
+        });
+
+        assert!(pipeline.export(spans.finished_spans()).await.is_err());
+        assert!(event_names(&fallback).is_empty());
+        // The breaker is open, the batch goes to the fallback instead
+        assert!(pipeline.export(spans.finished_spans()).await.is_ok());
+        assert_eq!(event_names(&fallback), ["hello"]);
+        assert_eq!(pipeline.health().consecutive_failures(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_flush_circuit_breaker_fallback() {
+        let mut pipeline = ExportPipeline::new(Recording::default(), Transforms::default());
+        assert!(pipeline.force_flush().await.is_ok());
+
+        let mut pipeline = ExportPipeline::new(Recording::default(), Transforms::default())
+            .with_circuit_breaker(
+                CircuitBreaker::new(1, Duration::from_secs(3600)),
+                Some(Box::new(Failing)),
+            );
+        assert!(pipeline.force_flush().await.is_err());
+    }
+
+    #[test]
+    fn test_span_name_mapper() {
+        let (layer, spans) = crate::builder("export")
+            .with_span_name_mapper(|name| match name.strip_prefix("GET /users/") {
+                Some(_) => Cow::Borrowed("GET /users/:id"),
+                None => Cow::Borrowed(name),
+            })
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", otel.name = "GET /users/42").in_scope(|| {});
+            tracing::info_span!("health").in_scope(|| {});
+        });
+
+        assert!(spans.span("GET /users/:id").is_some());
+        assert!(spans.span("health").is_some());
+    }
+
+    #[test]
+    fn test_attribute_transformer() {
+        let (layer, spans) = crate::builder("export")
+            .with_attribute_transformer(|key, value| match key.as_str() {
+                "password" => None,
+                "user" => Some((Key::from_static_str("user.id"), value)),
+                "size_kb" => {
+                    let kb = value.as_str().parse::<i64>().ok()?;
+                    Some((Key::from_static_str("size_bytes"), Value::I64(kb * 1024)))
+                }
+                _ => Some((key, value)),
+            })
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("upload", user = "jane", size_kb = "2").in_scope(|| {
+                tracing::info!(password = "hunter2", user = "jane", "login");
+            });
+        });
+
+        let span = spans.span("upload").expect("span was recorded");
+        assert_eq!(
+            crate::testing::attribute(&span, "user.id"),
+            Some(&Value::from("jane"))
+        );
+        assert_eq!(crate::testing::attribute(&span, "user"), None);
+        assert_eq!(
+            crate::testing::attribute(&span, "size_bytes"),
+            Some(&Value::I64(2048))
+        );
+        let event = &span.events.events[0];
+        assert!(event
+            .attributes
+            .iter()
+            .all(|kv| kv.key.as_str() != "password" && kv.key.as_str() != "user"));
+        assert!(event
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "user.id"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 5), None);
+        assert_eq!(truncate("longer", 4).as_deref(), Some("long…"));
+        // Never splits a multi-byte character
+        assert_eq!(truncate("aé", 2).as_deref(), Some("a…"));
+        assert_eq!(truncate("abc", 0).as_deref(), Some("…"));
+    }
+}
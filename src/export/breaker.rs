@@ -0,0 +1,109 @@
+use crate::diagnostics;
+use opentelemetry_sdk::export::trace::ExportResult;
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+use tracing_core::Level;
+
+/// Stops exporting after a number of consecutive failures and only sends a
+/// probe once the cooldown passed, see
+/// [`Builder::with_circuit_breaker`](crate::Builder::with_circuit_breaker).
+#[derive(Debug, Clone)]
+pub(crate) struct CircuitBreaker {
+    failures: u32,
+    cooldown: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    consecutive_failures: u32,
+    /// When the breaker opened or the last probe failed.
+    opened: Option<SystemTime>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(failures: u32, cooldown: Duration) -> Self {
+        Self {
+            failures: failures.max(1),
+            cooldown,
+            state: Arc::default(),
+        }
+    }
+
+    /// Whether a batch should be exported, either because the breaker is
+    /// closed or as a probe after the cooldown.
+    pub(crate) fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(opened) = state.opened else {
+            return true;
+        };
+        let now = opentelemetry::time::now();
+        let cooled_down = now
+            .duration_since(opened)
+            .map_or(true, |elapsed| elapsed >= self.cooldown);
+        if cooled_down {
+            // Restart the cooldown, so only one probe is sent at a time
+            state.opened = Some(now);
+        }
+        cooled_down
+    }
+
+    pub(crate) fn record(&self, result: &ExportResult) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if result.is_ok() {
+            if state.opened.take().is_some() {
+                diagnostics::report(Level::INFO, "export recovered, closing circuit breaker");
+            }
+            state.consecutive_failures = 0;
+            return;
+        }
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if state.consecutive_failures >= self.failures {
+            if state.opened.is_none() {
+                diagnostics::report(
+                    Level::WARN,
+                    &format!(
+                        "{} consecutive exports failed, pausing exports for {:?}",
+                        state.consecutive_failures, self.cooldown
+                    ),
+                );
+            }
+            state.opened = Some(opentelemetry::time::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed() -> ExportResult {
+        Err("unavailable".into())
+    }
+
+    #[test]
+    fn test_open_after_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(3600));
+        assert!(breaker.allow());
+        breaker.record(&failed());
+        assert!(breaker.allow());
+        breaker.record(&failed());
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn test_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.record(&failed());
+        // The cooldown passed, so a probe is sent
+        assert!(breaker.allow());
+        breaker.record(&Ok(()));
+        assert!(breaker.state.lock().expect("not poisoned").opened.is_none());
+
+        breaker.record(&Ok(()));
+        breaker.record(&failed());
+        assert!(breaker.state.lock().expect("not poisoned").opened.is_some());
+    }
+}
@@ -0,0 +1,120 @@
+use opentelemetry::{trace::Event, Key, KeyValue, Value};
+use opentelemetry_sdk::export::trace::SpanData;
+use std::{fmt, sync::Arc};
+
+/// An event about to be exported, handed to the callback set with
+/// [`Builder::with_event_enricher`](crate::Builder::with_event_enricher) to add
+/// fields to it.
+#[derive(Debug)]
+pub struct ExportedEvent<'a> {
+    event: &'a mut Event,
+    span_name: &'a str,
+    span_attributes: &'a [KeyValue],
+}
+
+impl ExportedEvent<'_> {
+    /// The name of the event, its message for events recorded with `tracing`.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.event.name
+    }
+
+    /// The value of the event field `key`, if it has one.
+    #[must_use]
+    pub fn attribute(&self, key: &str) -> Option<&Value> {
+        find(&self.event.attributes, key)
+    }
+
+    /// The name of the span the event was recorded in.
+    #[must_use]
+    pub fn span_name(&self) -> &str {
+        self.span_name
+    }
+
+    /// The value of the field `key` of the span the event was recorded in, if
+    /// it has one.
+    #[must_use]
+    pub fn span_attribute(&self, key: &str) -> Option<&Value> {
+        find(self.span_attributes, key)
+    }
+
+    /// Sets the event field `key`, replacing its value if it has one.
+    pub fn set_attribute(&mut self, key: impl Into<Key>, value: impl Into<Value>) {
+        let kv = KeyValue::new(key, value);
+        match self.event.attributes.iter_mut().find(|a| a.key == kv.key) {
+            Some(existing) => *existing = kv,
+            None => self.event.attributes.push(kv),
+        }
+    }
+}
+
+fn find<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a Value> {
+    attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == key)
+        .map(|kv| &kv.value)
+}
+
+/// Adds fields to every event before it is exported, see
+/// [`Builder::with_event_enricher`](crate::Builder::with_event_enricher).
+#[derive(Clone)]
+pub(crate) struct EventEnricher(pub(crate) Arc<dyn Fn(&mut ExportedEvent<'_>) + Send + Sync>);
+
+impl EventEnricher {
+    pub(crate) fn apply(&self, span: &mut SpanData) {
+        for event in &mut span.events.events {
+            (self.0)(&mut ExportedEvent {
+                event,
+                span_name: &span.name,
+                span_attributes: &span.attributes,
+            });
+        }
+    }
+}
+
+impl fmt::Debug for EventEnricher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventEnricher(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_event_enricher() {
+        let (layer, spans) = crate::builder("enrich")
+            .with_event_enricher(|event| {
+                if let Some(ip) = event.span_attribute("client.address") {
+                    let internal = ip.as_str().starts_with("10.");
+                    event.set_attribute("client.internal", internal);
+                }
+                event.set_attribute("level", "overridden");
+            })
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", client.address = "10.0.0.7").in_scope(|| {
+                tracing::info!("handled");
+            });
+        });
+
+        let span = spans.span("request").expect("span was recorded");
+        let event = &span.events.events[0];
+        assert_eq!(
+            find(&event.attributes, "client.internal"),
+            Some(&Value::Bool(true))
+        );
+        assert_eq!(
+            event
+                .attributes
+                .iter()
+                .filter(|kv| kv.key.as_str() == "level")
+                .map(|kv| &kv.value)
+                .collect::<Vec<_>>(),
+            [&Value::from("overridden")]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use crate::diagnostics;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+use tracing_core::Level;
+
+/// How long an endpoint is skipped after an export to it failed, before the
+/// export fails back to it.
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Sends every batch to the first of its endpoints that accepts it, see
+/// [`Builder::with_failover_url`](crate::Builder::with_failover_url).
+#[derive(Debug)]
+pub(crate) struct Failover {
+    endpoints: Arc<[Endpoint]>,
+}
+
+struct Endpoint {
+    name: String,
+    exporter: Mutex<Box<dyn SpanExporter>>,
+    failed: Mutex<Option<SystemTime>>,
+}
+
+impl fmt::Debug for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Endpoint")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Endpoint {
+    /// Whether the last export to the endpoint failed recently.
+    fn is_recovering(&self, now: SystemTime) -> bool {
+        self.failed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some_and(|failed| {
+                now.duration_since(failed)
+                    .is_ok_and(|elapsed| elapsed < RECOVERY_INTERVAL)
+            })
+    }
+
+    fn export(&self, batch: Vec<SpanData>) -> Pin<Box<dyn Future<Output = ExportResult> + Send>> {
+        self.exporter
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .export(batch)
+    }
+}
+
+impl Failover {
+    /// The endpoints by name in the order of preference.
+    pub(crate) fn new(
+        endpoints: impl IntoIterator<Item = (String, Box<dyn SpanExporter>)>,
+    ) -> Self {
+        Self {
+            endpoints: endpoints
+                .into_iter()
+                .map(|(name, exporter)| Endpoint {
+                    name,
+                    exporter: Mutex::new(exporter),
+                    failed: Mutex::new(None),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl SpanExporter for Failover {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        let endpoints = self.endpoints.clone();
+        Box::pin(async move {
+            let now = opentelemetry::time::now();
+            let mut available = endpoints
+                .iter()
+                .filter(|endpoint| !endpoint.is_recovering(now))
+                .collect::<Vec<_>>();
+            // Try all of them again rather than dropping the batch
+            if available.is_empty() {
+                available = endpoints.iter().collect();
+            }
+            let mut result = Ok(());
+            for (i, endpoint) in available.iter().enumerate() {
+                // The exporters only encode the batch before the future is awaited
+                result = endpoint.export(batch.clone()).await;
+                let mut failed = endpoint
+                    .failed
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                if result.is_ok() {
+                    *failed = None;
+                    return result;
+                }
+                *failed = Some(opentelemetry::time::now());
+                if let Some(next) = available.get(i + 1) {
+                    diagnostics::report(
+                        Level::WARN,
+                        &format!(
+                            "export to {} failed, failing over to {}",
+                            endpoint.name, next.name
+                        ),
+                    );
+                }
+            }
+            result
+        })
+    }
+
+    fn shutdown(&mut self) {
+        for endpoint in self.endpoints.iter() {
+            endpoint
+                .exporter
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .shutdown();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts the batches sent to it and fails while `failing` is set.
+    #[derive(Debug, Clone, Default)]
+    struct Flaky {
+        failing: Arc<Mutex<bool>>,
+        batches: Arc<AtomicUsize>,
+    }
+
+    impl SpanExporter for Flaky {
+        fn export(
+            &mut self,
+            _batch: Vec<SpanData>,
+        ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+            let failing = *self.failing.lock().expect("not poisoned");
+            self.batches.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async move {
+                if failing {
+                    Err("unavailable".into())
+                } else {
+                    Ok(())
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let (primary, backup) = (Flaky::default(), Flaky::default());
+        let mut failover = Failover::new([
+            ("primary".to_string(), Box::new(primary.clone()) as _),
+            ("backup".to_string(), Box::new(backup.clone()) as _),
+        ]);
+        assert!(failover.export(Vec::new()).await.is_ok());
+        assert_eq!(primary.batches.load(Ordering::Relaxed), 1);
+        assert_eq!(backup.batches.load(Ordering::Relaxed), 0);
+
+        *primary.failing.lock().expect("not poisoned") = true;
+        assert!(failover.export(Vec::new()).await.is_ok());
+        assert_eq!(backup.batches.load(Ordering::Relaxed), 1);
+        // The primary is skipped while it recovers
+        *primary.failing.lock().expect("not poisoned") = false;
+        assert!(failover.export(Vec::new()).await.is_ok());
+        assert_eq!(primary.batches.load(Ordering::Relaxed), 2);
+        assert_eq!(backup.batches.load(Ordering::Relaxed), 2);
+
+        // Once the recovery interval passed, the export fails back
+        *failover.endpoints[0].failed.lock().expect("not poisoned") =
+            opentelemetry::time::now().checked_sub(RECOVERY_INTERVAL);
+        assert!(failover.export(Vec::new()).await.is_ok());
+        assert_eq!(primary.batches.load(Ordering::Relaxed), 3);
+        assert_eq!(backup.batches.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_all_failing() {
+        let primary = Flaky::default();
+        *primary.failing.lock().expect("not poisoned") = true;
+        let mut failover = Failover::new([("primary".to_string(), Box::new(primary.clone()) as _)]);
+        assert!(failover.export(Vec::new()).await.is_err());
+        // Tried again although it failed recently
+        assert!(failover.export(Vec::new()).await.is_err());
+        assert_eq!(primary.batches.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,115 @@
+use super::json_value;
+use crate::diagnostics;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::export::trace::SpanData;
+use serde_json::Map;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing_core::Level;
+
+/// The attribute holding the fields that exceed the limits as a JSON object.
+const OVERFLOW_FIELDS: &str = "overflow_fields";
+
+static FOLDED: AtomicU64 = AtomicU64::new(0);
+
+/// The number of fields folded into the `overflow_fields` attribute because
+/// they exceeded the limits set with
+/// [`Builder::with_field_limits`](crate::Builder::with_field_limits).
+#[must_use]
+pub fn folded_fields() -> u64 {
+    FOLDED.load(Ordering::Relaxed)
+}
+
+/// Caps the number of fields of spans and events and how deeply they nest,
+/// see [`Builder::with_field_limits`](crate::Builder::with_field_limits).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FieldLimits {
+    max_fields: usize,
+    max_depth: usize,
+}
+
+impl FieldLimits {
+    pub(crate) fn new(max_fields: usize, max_depth: usize) -> Self {
+        Self {
+            max_fields: max_fields.max(1),
+            max_depth: max_depth.max(1),
+        }
+    }
+
+    pub(crate) fn apply(self, span: &mut SpanData) {
+        self.fold(&mut span.attributes);
+        for event in &mut span.events.events {
+            self.fold(&mut event.attributes);
+        }
+    }
+
+    fn fold(self, attributes: &mut Vec<KeyValue>) {
+        // Axiom nests fields at every dot of their name
+        let too_deep = |kv: &KeyValue| kv.key.as_str().split('.').count() > self.max_depth;
+        if attributes.len() <= self.max_fields && !attributes.iter().any(too_deep) {
+            return;
+        }
+        let mut overflow = Map::new();
+        let mut kept = Vec::with_capacity(self.max_fields);
+        for kv in attributes.drain(..) {
+            // Leave room for the overflow attribute
+            if too_deep(&kv) || kept.len() + 1 >= self.max_fields {
+                overflow.insert(kv.key.to_string(), json_value(&kv.value));
+            } else {
+                kept.push(kv);
+            }
+        }
+        let folded = u64::try_from(overflow.len()).unwrap_or(u64::MAX);
+        if FOLDED.fetch_add(folded, Ordering::Relaxed) == 0 {
+            diagnostics::report(
+                Level::WARN,
+                &format!("fields exceeding the limits are folded into `{OVERFLOW_FIELDS}`"),
+            );
+        }
+        kept.push(KeyValue::new(
+            OVERFLOW_FIELDS,
+            serde_json::Value::Object(overflow).to_string(),
+        ));
+        *attributes = kept;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(attributes: &[KeyValue]) -> Vec<&str> {
+        attributes.iter().map(|kv| kv.key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_max_fields() {
+        let limits = FieldLimits::new(3, 10);
+        let mut attributes = vec![
+            KeyValue::new("a", 1),
+            KeyValue::new("b", "two"),
+            KeyValue::new("c", true),
+            KeyValue::new("d", 4.5),
+        ];
+        limits.fold(&mut attributes);
+        assert_eq!(keys(&attributes), ["a", "b", OVERFLOW_FIELDS]);
+        assert_eq!(attributes[2].value.as_str(), r#"{"c":true,"d":4.5}"#);
+
+        // Within the limits nothing changes
+        let mut attributes = vec![KeyValue::new("a", 1), KeyValue::new("b", 2)];
+        limits.fold(&mut attributes);
+        assert_eq!(keys(&attributes), ["a", "b"]);
+        assert!(folded_fields() >= 2);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let limits = FieldLimits::new(10, 2);
+        let mut attributes = vec![
+            KeyValue::new("http.method", "GET"),
+            KeyValue::new("a.b.c", 1),
+        ];
+        limits.fold(&mut attributes);
+        assert_eq!(keys(&attributes), ["http.method", OVERFLOW_FIELDS]);
+        assert_eq!(attributes[1].value.as_str(), r#"{"a.b.c":1}"#);
+    }
+}
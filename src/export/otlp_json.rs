@@ -0,0 +1,282 @@
+use http::{header::CONTENT_TYPE, HeaderName, HeaderValue, Request, Uri};
+use opentelemetry::{
+    trace::{SpanId, SpanKind, Status, TraceError},
+    Array, KeyValue, StringValue, Value,
+};
+use opentelemetry_http::HttpClient;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// How spans are encoded when they are sent to Axiom over HTTP.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// Binary protobuf, the smallest and fastest to encode.
+    #[default]
+    Protobuf,
+    /// The JSON encoding of OTLP, which proxies and firewalls can inspect.
+    Json,
+}
+
+/// Sends spans to an OTLP/HTTP endpoint in the JSON encoding, see
+/// [`Builder::with_encoding`](crate::Builder::with_encoding).
+#[derive(Debug)]
+pub(crate) struct OtlpJsonExporter {
+    client: Arc<dyn HttpClient>,
+    uri: Uri,
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl OtlpJsonExporter {
+    /// Headers that are not valid are skipped, like the protobuf exporter does.
+    pub(crate) fn new(
+        client: Arc<dyn HttpClient>,
+        uri: Uri,
+        headers: &HashMap<String, String>,
+    ) -> Self {
+        let headers = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((
+                    HeaderName::try_from(name).ok()?,
+                    HeaderValue::try_from(value).ok()?,
+                ))
+            })
+            .collect();
+        Self {
+            client,
+            uri,
+            headers,
+        }
+    }
+
+    fn request(&self, batch: &[SpanData]) -> Result<Request<Vec<u8>>, TraceError> {
+        let body =
+            serde_json::to_vec(&encode(batch)).map_err(|e| TraceError::Other(Box::new(e)))?;
+        let mut request = Request::post(self.uri.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .map_err(|e| TraceError::Other(Box::new(e)))?;
+        for (name, value) in &self.headers {
+            request.headers_mut().insert(name.clone(), value.clone());
+        }
+        Ok(request)
+    }
+}
+
+impl SpanExporter for OtlpJsonExporter {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        let request = self.request(&batch);
+        let client = self.client.clone();
+        Box::pin(async move {
+            let response = client.send(request?).await.map_err(TraceError::Other)?;
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(format!("the export failed with status {}", response.status()).into())
+            }
+        })
+    }
+}
+
+/// The batch as OTLP `ExportTraceServiceRequest` in the JSON encoding, with
+/// one resource and scope per span like the protobuf exporter sends them.
+fn encode(batch: &[SpanData]) -> serde_json::Value {
+    let resource_spans = batch
+        .iter()
+        .map(|span| {
+            json!({
+                "resource": {
+                    "attributes": span
+                        .resource
+                        .iter()
+                        .map(|(key, value)| key_value(key.as_str(), value))
+                        .collect::<Vec<_>>(),
+                },
+                "scopeSpans": [{
+                    "scope": {
+                        "name": span.instrumentation_lib.name,
+                        "version": span.instrumentation_lib.version,
+                    },
+                    "schemaUrl": span.instrumentation_lib.schema_url,
+                    "spans": [encode_span(span)],
+                }],
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({ "resourceSpans": resource_spans })
+}
+
+fn encode_span(span: &SpanData) -> serde_json::Value {
+    let (code, message) = match &span.status {
+        Status::Unset => (0, ""),
+        Status::Ok => (1, ""),
+        Status::Error { description } => (2, description.as_ref()),
+    };
+    json!({
+        "traceId": span.span_context.trace_id().to_string(),
+        "spanId": span.span_context.span_id().to_string(),
+        "traceState": span.span_context.trace_state().header(),
+        // Empty for root spans
+        "parentSpanId": if span.parent_span_id == SpanId::INVALID {
+            String::new()
+        } else {
+            span.parent_span_id.to_string()
+        },
+        "name": span.name,
+        "kind": kind(&span.span_kind),
+        "startTimeUnixNano": unix_nanos(span.start_time),
+        "endTimeUnixNano": unix_nanos(span.end_time),
+        "attributes": attributes(&span.attributes),
+        "droppedAttributesCount": span.dropped_attributes_count,
+        "events": span.events.iter().map(|event| json!({
+            "timeUnixNano": unix_nanos(event.timestamp),
+            "name": event.name,
+            "attributes": attributes(&event.attributes),
+            "droppedAttributesCount": event.dropped_attributes_count,
+        })).collect::<Vec<_>>(),
+        "droppedEventsCount": span.events.dropped_count,
+        "links": span.links.iter().map(|link| json!({
+            "traceId": link.span_context.trace_id().to_string(),
+            "spanId": link.span_context.span_id().to_string(),
+            "traceState": link.span_context.trace_state().header(),
+            "attributes": attributes(&link.attributes),
+            "droppedAttributesCount": link.dropped_attributes_count,
+        })).collect::<Vec<_>>(),
+        "droppedLinksCount": span.links.dropped_count,
+        "status": { "code": code, "message": message },
+    })
+}
+
+/// The `SpanKind` enum value of the protocol.
+fn kind(kind: &SpanKind) -> u8 {
+    match kind {
+        SpanKind::Internal => 1,
+        SpanKind::Server => 2,
+        SpanKind::Client => 3,
+        SpanKind::Producer => 4,
+        SpanKind::Consumer => 5,
+    }
+}
+
+/// 64 bit integers are strings in the JSON encoding.
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn attributes(attributes: &[KeyValue]) -> Vec<serde_json::Value> {
+    attributes
+        .iter()
+        .map(|kv| key_value(kv.key.as_str(), &kv.value))
+        .collect()
+}
+
+fn key_value(key: &str, value: &Value) -> serde_json::Value {
+    json!({ "key": key, "value": any_value(value) })
+}
+
+fn any_value(value: &Value) -> serde_json::Value {
+    let array = |values: Vec<serde_json::Value>| json!({ "arrayValue": { "values": values } });
+    match value {
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::I64(i) => json!({ "intValue": i.to_string() }),
+        Value::F64(f) => json!({ "doubleValue": f }),
+        Value::String(s) => json!({ "stringValue": s.as_str() }),
+        Value::Array(Array::Bool(a)) => {
+            array(a.iter().map(|b| json!({ "boolValue": b })).collect())
+        }
+        Value::Array(Array::I64(a)) => array(
+            a.iter()
+                .map(|i| json!({ "intValue": i.to_string() }))
+                .collect(),
+        ),
+        Value::Array(Array::F64(a)) => {
+            array(a.iter().map(|f| json!({ "doubleValue": f })).collect())
+        }
+        Value::Array(Array::String(a)) => array(
+            a.iter()
+                .map(|s| json!({ "stringValue": StringValue::as_str(s) }))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use http::Response;
+    use opentelemetry_http::HttpError;
+    use std::sync::Mutex;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[derive(Debug, Default, Clone)]
+    struct Capture(Arc<Mutex<Option<Request<Vec<u8>>>>>);
+
+    #[async_trait]
+    impl HttpClient for Capture {
+        async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+            *self.0.lock().expect("not poisoned") = Some(request);
+            Ok(Response::new(Bytes::new()))
+        }
+    }
+
+    #[test]
+    fn test_any_value() {
+        assert_eq!(any_value(&Value::I64(42)), json!({ "intValue": "42" }));
+        assert_eq!(
+            any_value(&Value::Array(Array::String(vec!["a".into()]))),
+            json!({ "arrayValue": { "values": [{ "stringValue": "a" }] } })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export() -> Result<(), Box<dyn std::error::Error>> {
+        let (layer, spans) = crate::builder("json").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", user_id = 7).in_scope(|| {});
+        });
+        let capture = Capture::default();
+        let headers = HashMap::from([("X-Axiom-Dataset".to_string(), "traces".to_string())]);
+        let mut exporter = OtlpJsonExporter::new(
+            Arc::new(capture.clone()),
+            "https://api.axiom.co/v1/traces".parse()?,
+            &headers,
+        );
+        exporter.export(spans.finished_spans()).await?;
+
+        let request = capture
+            .0
+            .lock()
+            .expect("not poisoned")
+            .take()
+            .expect("sent");
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(request.headers()["X-Axiom-Dataset"], "traces");
+        let body: serde_json::Value = serde_json::from_slice(request.body())?;
+        let span = &body["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["name"], "request");
+        assert_eq!(span["kind"], 1);
+        assert_eq!(span["traceId"].as_str().map(str::len), Some(32));
+        assert!(span["attributes"]
+            .as_array()
+            .is_some_and(|attributes| attributes
+                .contains(&json!({ "key": "user_id", "value": { "intValue": "7" } }))));
+        Ok(())
+    }
+}
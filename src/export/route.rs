@@ -0,0 +1,201 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{HeaderValue, Request, Response};
+use opentelemetry::Value;
+use opentelemetry_http::{HttpClient, HttpError};
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// The span attribute that routes a span to another dataset.
+const DATASET: &str = "axiom.dataset";
+/// The header with the dataset of a request.
+const DATASET_HEADER: &str = "X-Axiom-Dataset";
+
+/// The dataset of the spans that are being sent, if they don't go to the
+/// default one.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SharedDataset(Arc<Mutex<Option<HeaderValue>>>);
+
+impl SharedDataset {
+    fn set(&self, dataset: Option<HeaderValue>) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = dataset;
+    }
+
+    fn get(&self) -> Option<HeaderValue> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// The dataset a span is routed to with the `axiom.dataset` attribute, spans
+/// with an invalid name go to the default dataset.
+fn dataset(span: &SpanData) -> Option<HeaderValue> {
+    span.attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == DATASET)
+        .and_then(|kv| match &kv.value {
+            Value::String(dataset) => HeaderValue::from_str(dataset.as_str()).ok(),
+            _ => None,
+        })
+}
+
+/// Sends the spans with an `axiom.dataset` attribute to that dataset, with a
+/// request per dataset. The requests are sent one after another, with the
+/// dataset set for the [`RouteDataset`] client of the exporter while each one
+/// is sent.
+#[derive(Debug)]
+pub(crate) struct DatasetRouter {
+    exporter: Box<dyn SpanExporter>,
+    dataset: SharedDataset,
+}
+
+impl DatasetRouter {
+    pub(crate) fn new(exporter: Box<dyn SpanExporter>, dataset: SharedDataset) -> Self {
+        Self { exporter, dataset }
+    }
+}
+
+impl SpanExporter for DatasetRouter {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        let mut groups: Vec<(Option<HeaderValue>, Vec<SpanData>)> = Vec::new();
+        for span in batch {
+            let dataset = dataset(&span);
+            match groups.iter_mut().find(|(d, _)| *d == dataset) {
+                Some((_, spans)) => spans.push(span),
+                None => groups.push((dataset, vec![span])),
+            }
+        }
+        // The exporters only encode the batch before the future is awaited
+        let exports = groups
+            .into_iter()
+            .map(|(dataset, spans)| (dataset, self.exporter.export(spans)))
+            .collect::<Vec<_>>();
+        let shared = self.dataset.clone();
+        Box::pin(async move {
+            let mut result = Ok(());
+            for (dataset, export) in exports {
+                shared.set(dataset);
+                result = result.and(export.await);
+            }
+            shared.set(None);
+            result
+        })
+    }
+
+    fn shutdown(&mut self) {
+        self.exporter.shutdown();
+    }
+
+    fn force_flush(&mut self) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        self.exporter.force_flush()
+    }
+}
+
+/// Replaces the dataset header of requests with the dataset the
+/// [`DatasetRouter`] is sending spans to.
+#[derive(Debug)]
+pub(crate) struct RouteDataset {
+    pub(crate) client: Arc<dyn HttpClient>,
+    pub(crate) dataset: SharedDataset,
+}
+
+#[async_trait]
+impl HttpClient for RouteDataset {
+    async fn send(&self, mut request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+        if let Some(dataset) = self.dataset.get() {
+            request.headers_mut().insert(DATASET_HEADER, dataset);
+        }
+        self.client.send(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TraceError;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    /// The dataset header and number of spans of every request.
+    #[derive(Debug, Clone, Default)]
+    struct Requests(Arc<Mutex<Vec<(String, usize)>>>);
+
+    #[async_trait]
+    impl HttpClient for Requests {
+        async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+            let dataset = request
+                .headers()
+                .get(DATASET_HEADER)
+                .and_then(|dataset| dataset.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            self.0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((dataset, request.body().len()));
+            Ok(Response::new(Bytes::new()))
+        }
+    }
+
+    /// Sends a request with a byte per span to the default dataset.
+    #[derive(Debug)]
+    struct Exporter(Arc<dyn HttpClient>);
+
+    impl SpanExporter for Exporter {
+        fn export(
+            &mut self,
+            batch: Vec<SpanData>,
+        ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+            let client = self.0.clone();
+            let request = Request::builder()
+                .header(DATASET_HEADER, "default")
+                .body(vec![0; batch.len()])
+                .expect("valid request");
+            Box::pin(async move {
+                client
+                    .send(request)
+                    .await
+                    .map(|_| ())
+                    .map_err(TraceError::Other)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dataset_router() {
+        let (layer, spans) = crate::builder("route").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("invoice", axiom.dataset = "billing").in_scope(|| {});
+            tracing::info_span!("request").in_scope(|| {});
+            tracing::info_span!("refund", axiom.dataset = "billing").in_scope(|| {});
+            tracing::info_span!("invalid", axiom.dataset = "bill\ning").in_scope(|| {});
+        });
+
+        let requests = Requests::default();
+        let dataset = SharedDataset::default();
+        let client = RouteDataset {
+            client: Arc::new(requests.clone()),
+            dataset: dataset.clone(),
+        };
+        let mut router = DatasetRouter::new(Box::new(Exporter(Arc::new(client))), dataset);
+        router
+            .export(spans.finished_spans())
+            .await
+            .expect("exported");
+
+        let requests = requests.0.lock().unwrap_or_else(PoisonError::into_inner);
+        assert_eq!(
+            *requests,
+            [("billing".to_string(), 2), ("default".to_string(), 2)]
+        );
+    }
+}
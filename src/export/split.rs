@@ -0,0 +1,152 @@
+use opentelemetry::{Array, KeyValue, Value};
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use std::{future::Future, pin::Pin};
+
+/// The estimated size of the IDs, timestamps, kind and status of a span.
+const SPAN_OVERHEAD: usize = 64;
+/// The estimated size of the timestamp of an event or the IDs of a link.
+const ITEM_OVERHEAD: usize = 32;
+/// The estimated size of the encoding of an attribute and its value.
+const ATTRIBUTE_OVERHEAD: usize = 4;
+
+/// Splits batches whose estimated request size exceeds the limit into several
+/// requests, see
+/// [`Builder::with_max_batch_bytes`](crate::Builder::with_max_batch_bytes).
+#[derive(Debug)]
+pub(crate) struct BatchSplitter {
+    exporter: Box<dyn SpanExporter>,
+    max_bytes: usize,
+}
+
+impl BatchSplitter {
+    pub(crate) fn new(exporter: Box<dyn SpanExporter>, max_bytes: usize) -> Self {
+        Self {
+            exporter,
+            max_bytes,
+        }
+    }
+}
+
+impl SpanExporter for BatchSplitter {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        let exports = split(batch, self.max_bytes)
+            .into_iter()
+            .map(|chunk| self.exporter.export(chunk))
+            .collect::<Vec<_>>();
+        Box::pin(async move {
+            // Send all of them, so a rejected request only loses its own spans
+            let mut result = Ok(());
+            for export in exports {
+                result = result.and(export.await);
+            }
+            result
+        })
+    }
+
+    fn shutdown(&mut self) {
+        self.exporter.shutdown();
+    }
+
+    fn force_flush(&mut self) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        self.exporter.force_flush()
+    }
+}
+
+/// Splits the batch into chunks of at most `max_bytes`, a span larger than
+/// that is sent on its own.
+fn split(batch: Vec<SpanData>, max_bytes: usize) -> Vec<Vec<SpanData>> {
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::new();
+    let mut chunk_size = 0;
+    for span in batch {
+        let size = estimated_size(&span);
+        if !chunk.is_empty() && chunk_size + size > max_bytes {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_size = 0;
+        }
+        chunk_size += size;
+        chunk.push(span);
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// The approximate size of the span in an OTLP request.
+fn estimated_size(span: &SpanData) -> usize {
+    let events = span
+        .events
+        .iter()
+        .map(|event| ITEM_OVERHEAD + event.name.len() + attributes_size(&event.attributes))
+        .sum::<usize>();
+    let links = span
+        .links
+        .iter()
+        .map(|link| ITEM_OVERHEAD + attributes_size(&link.attributes))
+        .sum::<usize>();
+    SPAN_OVERHEAD + span.name.len() + attributes_size(&span.attributes) + events + links
+}
+
+fn attributes_size(attributes: &[KeyValue]) -> usize {
+    attributes
+        .iter()
+        .map(|kv| ATTRIBUTE_OVERHEAD + kv.key.as_str().len() + value_size(&kv.value))
+        .sum()
+}
+
+fn value_size(value: &Value) -> usize {
+    match value {
+        Value::Bool(_) => 1,
+        Value::I64(_) | Value::F64(_) => 8,
+        Value::String(s) => s.as_str().len(),
+        Value::Array(Array::Bool(values)) => values.len(),
+        Value::Array(Array::I64(values)) => values.len() * 8,
+        Value::Array(Array::F64(values)) => values.len() * 8,
+        Value::Array(Array::String(values)) => values
+            .iter()
+            .map(|s| ATTRIBUTE_OVERHEAD + s.as_str().len())
+            .sum(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_split() {
+        let (layer, spans) = crate::builder("split").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let large = "x".repeat(1000);
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..3 {
+                tracing::info_span!("small").in_scope(|| {});
+            }
+            tracing::info_span!("large", value = large.as_str()).in_scope(|| {});
+            tracing::info_span!("small").in_scope(|| {});
+        });
+        let batch = spans.finished_spans();
+        let small = estimated_size(&batch[0]);
+        assert!(estimated_size(&batch[3]) > 1000);
+
+        let chunks = split(batch, 2 * small)
+            .iter()
+            .map(|chunk| chunk.iter().map(|span| span.name.to_string()).collect())
+            .collect::<Vec<Vec<_>>>();
+        // The large span exceeds the limit on its own
+        assert_eq!(
+            chunks,
+            [
+                vec!["small", "small"],
+                vec!["small"],
+                vec!["large"],
+                vec!["small"]
+            ]
+        );
+    }
+}
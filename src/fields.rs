@@ -0,0 +1,1152 @@
+mod dedup;
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+mod event_metrics;
+mod links;
+mod sampling;
+mod scope;
+#[cfg(feature = "valuable")]
+mod structured;
+mod summary;
+
+use dedup::SeenEvents;
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+pub(crate) use event_metrics::EventMetrics;
+use opentelemetry::{
+    trace::{Event as OtelEvent, Span as _, SpanBuilder, Status},
+    Context as OtelContext, KeyValue,
+};
+use opentelemetry_sdk::{export::trace::SpanData, trace::Tracer};
+use opentelemetry_semantic_conventions::trace::{
+    EXCEPTION_MESSAGE, EXCEPTION_STACKTRACE, EXCEPTION_TYPE, THREAD_ID, THREAD_NAME,
+};
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt::Debug,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tracing_core::{
+    callsite::Identifier,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Level, Subscriber,
+};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+pub(crate) use sampling::BurstSuppression;
+use scope::ScopedFields;
+pub use scope::{scope, Scope};
+#[cfg(feature = "valuable")]
+pub use structured::Flattening;
+pub(crate) use summary::SpanSummaries;
+use summary::SpanTiming;
+
+/// Post-processes the fields recorded by the OpenTelemetry layer, part of the
+/// layer created by [`Builder::build`](crate::Builder::build).
+///
+/// Errors recorded as fields get the `exception.type`, `exception.message` and
+/// `exception.stacktrace` attributes of the OpenTelemetry semantic
+/// conventions, with the whole `source()` chain as stacktrace. Errors recorded
+/// on spans are also added as `exception` event. With the `tracing-error`
+/// feature, the spans of a `SpanTrace` captured by a `TracedError` in the
+/// chain are added as `exception.span_trace`.
+///
+/// With [`Builder::with_threads`](crate::Builder::with_threads), events get
+/// the thread attributes spans have, and both the id of the tokio task.
+///
+/// Events get a `module_path` attribute, next to the `level` and `target`
+/// attributes of the OpenTelemetry layer, and their name as `message` with
+/// [`Builder::with_event_message`](crate::Builder::with_event_message).
+///
+/// The message of `error!` events can be used as span status description, see
+/// [`Builder::with_error_status_description`](crate::Builder::with_error_status_description).
+///
+/// Spans created with `link.trace_id` and `link.span_id` fields, in hex like
+/// the IDs of [`current_trace_id`](crate::current_trace_id), link to that span
+/// instead of recording them as attributes, e.g. for a batch that processes
+/// the messages of several traces. Further links can be added with
+/// [`OpenTelemetrySpanExt::add_link`](tracing_opentelemetry::OpenTelemetrySpanExt::add_link).
+///
+/// Fields added with [`scope`](crate::scope) are added to all spans within the
+/// span and the events recorded in them.
+///
+/// Identical events firing at a high rate can be sampled, see
+/// [`Builder::with_burst_suppression`](crate::Builder::with_burst_suppression),
+/// or coalesced into one per span, see
+/// [`Builder::with_event_dedup`](crate::Builder::with_event_dedup).
+///
+/// With the `valuable` feature, fields recorded with
+/// [`valuable`](https://docs.rs/valuable) are exported as structured
+/// attributes, see `Builder::with_flattening`.
+///
+/// Spans can be exported when they start as well, see
+/// [`Builder::with_span_start_records`](crate::Builder::with_span_start_records),
+/// and events outside of spans in spans of their own, see
+/// [`Builder::with_orphan_event_spans`](crate::Builder::with_orphan_event_spans).
+///
+/// The rate, errors and durations of all spans, sampled or not, can be
+/// exported as summaries per span name, see
+/// [`Builder::with_span_summaries`](crate::Builder::with_span_summaries).
+///
+/// With the `metrics` feature, event fields prefixed with
+/// `monotonic_counter.`, `counter.` or `histogram.` are recorded as metrics,
+/// see `Builder::with_metrics`.
+#[derive(Debug, Default)]
+pub struct FieldsLayer {
+    #[cfg(feature = "valuable")]
+    pub(crate) flattening: Flattening,
+    pub(crate) error_status_description: bool,
+    pub(crate) threads: bool,
+    pub(crate) event_message: bool,
+    pub(crate) burst_suppression: Option<BurstSuppression>,
+    pub(crate) dedup_window: Option<Duration>,
+    pub(crate) start_records: bool,
+    pub(crate) orphan_events: bool,
+    /// The tracer of the layer, to export start records and orphan events.
+    pub(crate) tracer: Option<Tracer>,
+    pub(crate) summaries: Option<Arc<SpanSummaries>>,
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    pub(crate) metrics: Option<EventMetrics>,
+    /// Keeps the host metrics exported until the layer is dropped.
+    #[cfg(all(feature = "sysinfo", not(target_arch = "wasm32")))]
+    pub(crate) system_metrics: Option<opentelemetry_sdk::metrics::SdkMeterProvider>,
+}
+
+impl FieldsLayer {
+    fn visitor(&self) -> FieldsVisitor {
+        FieldsVisitor {
+            #[cfg(feature = "valuable")]
+            flattening: self.flattening,
+            structured: Vec::new(),
+            exception: None,
+            record_message: false,
+            message: None,
+            threads: self.threads,
+        }
+    }
+
+    /// Coalesces or suppresses the event that was just recorded as the last
+    /// one, remembering it for the dedup window if it is kept.
+    fn sample(
+        &self,
+        callsite: &Identifier,
+        events: &mut Vec<OtelEvent>,
+        seen: Option<&mut SeenEvents>,
+    ) {
+        if let (Some(window), Some(seen)) = (self.dedup_window, &seen) {
+            if seen.coalesce(callsite, events, window) {
+                return;
+            }
+        }
+        if let Some(burst_suppression) = &self.burst_suppression {
+            let Some(otel_event) = events.last_mut() else {
+                return;
+            };
+            match burst_suppression.sample(callsite.clone(), &otel_event.name) {
+                None => {
+                    events.pop();
+                    return;
+                }
+                Some(0) => {}
+                Some(suppressed) => {
+                    let suppressed = i64::try_from(suppressed).unwrap_or(i64::MAX);
+                    otel_event
+                        .attributes
+                        .push(KeyValue::new(SUPPRESSED_COUNT, suppressed));
+                }
+            }
+        }
+        if let Some(seen) = seen {
+            seen.insert(callsite.clone(), events);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const TOKIO_TASK_ID: &str = "tokio.task.id";
+const EVENT_MESSAGE: &str = "message";
+const EVENT_MODULE_PATH: &str = "module_path";
+/// The number of identical events suppressed before this one.
+const SUPPRESSED_COUNT: &str = "suppressed_count";
+/// Set to `started` on the records exported when spans start.
+const SPAN_STATE: &str = "span.state";
+
+/// The spans of a [`tracing_error::SpanTrace`] in the error chain.
+const EXCEPTION_SPAN_TRACE: &str = "exception.span_trace";
+
+/// An error recorded as field, as attributes of the semantic conventions.
+struct Exception {
+    r#type: Option<String>,
+    message: String,
+    stacktrace: String,
+    span_trace: Option<String>,
+}
+
+impl Exception {
+    fn new(error: &(dyn Error + 'static)) -> Self {
+        let mut stacktrace = error.to_string();
+        #[cfg_attr(not(feature = "tracing-error"), allow(unused_mut))]
+        let mut span_trace = None;
+        let mut source = error.source();
+        while let Some(error) = source {
+            source = error.source();
+            #[cfg(feature = "tracing-error")]
+            if let Some(trace) = tracing_error::ExtractSpanTrace::span_trace(error) {
+                // A `TracedError` displays the error it wraps, which is its
+                // source, so it would be listed twice
+                span_trace.get_or_insert_with(|| span_trace_json(trace));
+                continue;
+            }
+            stacktrace.push_str("\nCaused by: ");
+            stacktrace.push_str(&error.to_string());
+        }
+        Self {
+            r#type: exception_type(error),
+            message: error.to_string(),
+            stacktrace,
+            span_trace,
+        }
+    }
+
+    fn attributes(&self) -> impl Iterator<Item = KeyValue> + '_ {
+        self.r#type
+            .iter()
+            .map(|r#type| KeyValue::new(EXCEPTION_TYPE, r#type.clone()))
+            .chain([
+                KeyValue::new(EXCEPTION_MESSAGE, self.message.clone()),
+                KeyValue::new(EXCEPTION_STACKTRACE, self.stacktrace.clone()),
+            ])
+            .chain(
+                self.span_trace
+                    .iter()
+                    .map(|span_trace| KeyValue::new(EXCEPTION_SPAN_TRACE, span_trace.clone())),
+            )
+    }
+
+    fn replace(&self, attributes: &mut Vec<KeyValue>) {
+        attributes.retain(|kv| {
+            ![
+                EXCEPTION_TYPE,
+                EXCEPTION_MESSAGE,
+                EXCEPTION_STACKTRACE,
+                EXCEPTION_SPAN_TRACE,
+            ]
+            .contains(&kv.key.as_str())
+        });
+        attributes.extend(self.attributes());
+    }
+}
+
+/// The spans of the trace, innermost first, with their locations as JSON.
+#[cfg(feature = "tracing-error")]
+fn span_trace_json(span_trace: &tracing_error::SpanTrace) -> String {
+    let mut spans = Vec::new();
+    span_trace.with_spans(|metadata, _fields| {
+        spans.push(serde_json::json!({
+            "name": metadata.name(),
+            "target": metadata.target(),
+            "file": metadata.file(),
+            "line": metadata.line(),
+        }));
+        true
+    });
+    serde_json::Value::Array(spans).to_string()
+}
+
+/// Rust errors have no type name at runtime, so this is the name their `Debug`
+/// output starts with, like the variant of error enums or the struct name.
+fn exception_type(error: &dyn Error) -> Option<String> {
+    let debug = format!("{error:?}");
+    let end = debug
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+        .unwrap_or(debug.len());
+    let name = debug[..end].trim_end_matches(':');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Adds the module path, and the message if `message` is set, as attributes
+/// next to the `level` and `target` the OpenTelemetry layer adds, so every
+/// event has the same fields in Axiom. Events without message are named after
+/// their callsite, e.g. `event src/main.rs:12`, instead of having an empty
+/// name.
+fn map_event(event: &Event<'_>, otel_event: &mut OtelEvent, message: bool) {
+    let metadata = event.metadata();
+    if otel_event.name.is_empty() {
+        otel_event.name = Cow::Borrowed(metadata.name());
+    }
+    if message {
+        otel_event
+            .attributes
+            .push(KeyValue::new(EVENT_MESSAGE, otel_event.name.clone()));
+    }
+    if let Some(module_path) = metadata.module_path() {
+        otel_event
+            .attributes
+            .push(KeyValue::new(EVENT_MODULE_PATH, module_path));
+    }
+}
+
+/// Whether the span is a record of a span as it started, which has the ids of
+/// the span but ends right away, see [`export_start_record`].
+pub(crate) fn is_start_record(span: &SpanData) -> bool {
+    span.attributes
+        .iter()
+        .any(|kv| kv.key.as_str() == SPAN_STATE && kv.value.as_str() == "started")
+}
+
+/// Exports a record of the span as it started, with the same ids and ending
+/// right away, see
+/// [`Builder::with_span_start_records`](crate::Builder::with_span_start_records).
+/// The processors that hold spans back by their duration or trace skip it,
+/// see [`is_start_record`].
+fn export_start_record(tracer: &Tracer, mut builder: SpanBuilder, parent_cx: &OtelContext) {
+    let start_time = builder.start_time.unwrap_or_else(SystemTime::now);
+    builder.events = None;
+    builder.status = Status::Unset;
+    builder
+        .attributes
+        .get_or_insert_with(Vec::new)
+        .push(KeyValue::new(SPAN_STATE, "started"));
+    builder
+        .start_with_context(tracer, parent_cx)
+        .end_with_timestamp(start_time);
+}
+
+/// Exports the event in a span of its own named after its target, see
+/// [`Builder::with_orphan_event_spans`](crate::Builder::with_orphan_event_spans).
+fn export_orphan_event(tracer: &Tracer, event: &Event<'_>, threads: bool, message: bool) {
+    let metadata = event.metadata();
+    let mut visitor = OrphanEventVisitor::default();
+    event.record(&mut visitor);
+    let mut otel_event = OtelEvent::with_name(visitor.message.unwrap_or_default());
+    otel_event.attributes = visitor.attributes;
+    otel_event.attributes.extend([
+        KeyValue::new("level", metadata.level().as_str()),
+        KeyValue::new("target", metadata.target()),
+    ]);
+    map_event(event, &mut otel_event, message);
+    if threads {
+        otel_event.attributes.extend(thread_attributes());
+        otel_event.attributes.extend(task_id());
+    }
+    let now = SystemTime::now();
+    let mut builder = SpanBuilder::from_name(metadata.target())
+        .with_start_time(now)
+        .with_events(vec![otel_event]);
+    if *metadata.level() == Level::ERROR {
+        builder = builder.with_status(Status::error(""));
+    }
+    builder
+        .start_with_context(tracer, &OtelContext::new())
+        .end_with_timestamp(now);
+}
+
+/// The same thread attributes the OpenTelemetry layer records on spans.
+fn thread_attributes() -> impl Iterator<Item = KeyValue> {
+    let thread = std::thread::current();
+    // There's no stable API for the number in `ThreadId(5)`
+    let id = format!("{:?}", thread.id())
+        .trim_start_matches("ThreadId(")
+        .trim_end_matches(')')
+        .parse::<i64>()
+        .ok()
+        .map(|id| KeyValue::new(THREAD_ID, id));
+    let name = thread
+        .name()
+        .map(|name| KeyValue::new(THREAD_NAME, name.to_string()));
+    id.into_iter().chain(name)
+}
+
+/// The id of the tokio task the current code runs in, if any.
+fn task_id() -> Option<KeyValue> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return tokio::task::try_id().map(|id| KeyValue::new(TOKIO_TASK_ID, id.to_string()));
+    #[cfg(target_arch = "wasm32")]
+    None
+}
+
+/// Records the fields of events outside of spans, which the OpenTelemetry
+/// layer drops.
+#[derive(Default)]
+struct OrphanEventVisitor {
+    message: Option<String>,
+    attributes: Vec<KeyValue>,
+}
+
+impl Visit for OrphanEventVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.attributes.push(KeyValue::new(field.name(), value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.attributes.push(KeyValue::new(field.name(), value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let value = i64::try_from(value).map_or_else(
+            |_| opentelemetry::Value::from(value.to_string()),
+            Into::into,
+        );
+        self.attributes.push(KeyValue::new(field.name(), value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.attributes.push(KeyValue::new(field.name(), value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.attributes
+                .push(KeyValue::new(field.name(), value.to_string()));
+        }
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        let exception = Exception::new(value);
+        self.attributes.extend(exception.attributes());
+        self.attributes
+            .push(KeyValue::new(field.name(), value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{value:?}"));
+        } else {
+            self.attributes
+                .push(KeyValue::new(field.name(), format!("{value:?}")));
+        }
+    }
+}
+
+/// Collects the fields the OpenTelemetry layer records losing information,
+/// other fields are left as they are.
+struct FieldsVisitor {
+    #[cfg(feature = "valuable")]
+    flattening: Flattening,
+    structured: Vec<(&'static str, Vec<KeyValue>)>,
+    exception: Option<Exception>,
+    record_message: bool,
+    message: Option<String>,
+    threads: bool,
+}
+
+impl FieldsVisitor {
+    fn is_empty(&self) -> bool {
+        self.structured.is_empty() && self.exception.is_none() && self.message.is_none()
+    }
+
+    /// Replaces what the OpenTelemetry layer recorded for the collected fields,
+    /// and attributes from previously recorded values.
+    fn replace(&self, attributes: &mut Vec<KeyValue>) {
+        for (name, structured) in &self.structured {
+            attributes.retain(|kv| {
+                let key = kv.key.as_str();
+                key != *name
+                    && !key
+                        .strip_prefix(name)
+                        .is_some_and(|rest| rest.starts_with('.'))
+            });
+            attributes.extend(structured.iter().cloned());
+        }
+        if let Some(exception) = &self.exception {
+            exception.replace(attributes);
+        }
+    }
+
+    fn replace_in_span<S>(&self, id: &Id, ctx: &Context<'_, S>)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        if self.is_empty() {
+            return;
+        }
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        let Some(otel_data) = extensions.get_mut::<OtelData>() else {
+            return;
+        };
+        self.replace(otel_data.builder.attributes.get_or_insert_with(Vec::new));
+        if let Some(exception) = &self.exception {
+            let mut event = OtelEvent::with_name("exception");
+            event.attributes.extend(exception.attributes());
+            otel_data
+                .builder
+                .events
+                .get_or_insert_with(Vec::new)
+                .push(event);
+        }
+    }
+
+    fn update_event<S>(&self, event: &Event<'_>, ctx: &Context<'_, S>, layer: &FieldsLayer)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        // The span the OpenTelemetry layer added the event to
+        let span = event.parent().and_then(|id| ctx.span(id)).or_else(|| {
+            event
+                .is_contextual()
+                .then(|| ctx.lookup_current())
+                .flatten()
+        });
+        let Some(span) = span else { return };
+        let mut extensions = span.extensions_mut();
+        let mut seen = layer
+            .dedup_window
+            .map(|_| extensions.remove::<SeenEvents>().unwrap_or_default());
+        let scoped = extensions
+            .get_mut::<ScopedFields>()
+            .map(|scoped| scoped.clone());
+        let Some(otel_data) = extensions.get_mut::<OtelData>() else {
+            return;
+        };
+        if let Some(events) = otel_data.builder.events.as_mut() {
+            if let Some(otel_event) = events.last_mut() {
+                self.replace(&mut otel_event.attributes);
+                map_event(event, otel_event, layer.event_message);
+                if let Some(scoped) = &scoped {
+                    scoped.add_to(&mut otel_event.attributes);
+                }
+                if self.threads {
+                    otel_event.attributes.extend(thread_attributes());
+                    otel_event.attributes.extend(task_id());
+                }
+                layer.sample(&event.metadata().callsite(), events, seen.as_mut());
+            }
+        }
+        // The exception attributes are also recorded on the span
+        if let (Some(exception), Some(attributes)) =
+            (&self.exception, otel_data.builder.attributes.as_mut())
+        {
+            if attributes
+                .iter()
+                .any(|kv| kv.key.as_str() == EXCEPTION_MESSAGE)
+            {
+                exception.replace(attributes);
+            }
+        }
+        // The OpenTelemetry layer marks the span as failed without description
+        if let Some(message) = &self.message {
+            let status = &mut otel_data.builder.status;
+            if matches!(status, Status::Unset)
+                || matches!(status, Status::Error { description } if description.is_empty())
+            {
+                *status = Status::error(message.clone());
+            }
+        }
+        if let Some(seen) = seen {
+            extensions.insert(seen);
+        }
+        SpanTiming::update(&mut extensions);
+    }
+}
+
+impl Visit for FieldsVisitor {
+    #[cfg(feature = "valuable")]
+    fn record_value(&mut self, field: &Field, value: valuable::Value<'_>) {
+        let mut attributes = Vec::new();
+        self.flattening
+            .attributes(field.name(), structured::to_json(value), &mut attributes);
+        self.structured.push((field.name(), attributes));
+    }
+
+    fn record_error(&mut self, _field: &Field, value: &(dyn Error + 'static)) {
+        self.exception = Some(Exception::new(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if self.record_message && field.name() == "message" {
+            self.message = Some(format!("{value:?}"));
+        }
+    }
+}
+
+impl<S> Layer<S> for FieldsLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = self.visitor();
+        attrs.record(&mut visitor);
+        visitor.replace_in_span(id, &ctx);
+        let Some(span) = ctx.span(id) else { return };
+        if let Some(otel_data) = span.extensions_mut().get_mut::<OtelData>() {
+            links::add_link(&mut otel_data.builder);
+        }
+        if let Some(task_id) = task_id().filter(|_| self.threads) {
+            if let Some(otel_data) = span.extensions_mut().get_mut::<OtelData>() {
+                otel_data
+                    .builder
+                    .attributes
+                    .get_or_insert_with(Vec::new)
+                    .push(task_id);
+            }
+        }
+        let scoped = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<ScopedFields>().cloned());
+        if let Some(scoped) = scoped {
+            let mut extensions = span.extensions_mut();
+            if let Some(otel_data) = extensions.get_mut::<OtelData>() {
+                scoped.add_to(otel_data.builder.attributes.get_or_insert_with(Vec::new));
+            }
+            extensions.insert(scoped);
+        }
+        if self.summaries.is_some() {
+            let mut extensions = span.extensions_mut();
+            let timing = extensions.get::<OtelData>().map(SpanTiming::new);
+            if let Some(timing) = timing {
+                extensions.insert(timing);
+            }
+        }
+        if let Some(tracer) = self.tracer.as_ref().filter(|_| self.start_records) {
+            let started = span
+                .extensions()
+                .get::<OtelData>()
+                .map(|otel_data| (otel_data.builder.clone(), otel_data.parent_cx.clone()));
+            if let Some((builder, parent_cx)) = started {
+                export_start_record(tracer, builder, &parent_cx);
+            }
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = self.visitor();
+        values.record(&mut visitor);
+        visitor.replace_in_span(id, &ctx);
+        if self.summaries.is_some() {
+            if let Some(span) = ctx.span(id) {
+                SpanTiming::update(&mut span.extensions_mut());
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+        if let Some(mut metrics) = self.metrics.as_ref() {
+            event.record(&mut metrics);
+        }
+        if let Some(tracer) = self.tracer.as_ref().filter(|_| self.orphan_events) {
+            let orphan =
+                event.is_root() || (event.is_contextual() && ctx.lookup_current().is_none());
+            if orphan {
+                export_orphan_event(tracer, event, self.threads, self.event_message);
+                return;
+            }
+        }
+        let mut visitor = self.visitor();
+        visitor.record_message =
+            self.error_status_description && *event.metadata().level() == Level::ERROR;
+        event.record(&mut visitor);
+        visitor.update_event(event, &ctx, self);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(summaries) = &self.summaries else {
+            return;
+        };
+        let timing = ctx
+            .span(&id)
+            .and_then(|span| span.extensions_mut().remove::<SpanTiming>());
+        if let Some(timing) = timing {
+            summaries.record(timing);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::attribute;
+    use std::fmt;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[derive(Debug)]
+    enum QueryError {
+        Connection(std::io::Error),
+    }
+
+    impl fmt::Display for QueryError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "query failed")
+        }
+    }
+
+    impl Error for QueryError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            match self {
+                QueryError::Connection(e) => Some(e),
+            }
+        }
+    }
+
+    fn query_error() -> QueryError {
+        QueryError::Connection(std::io::Error::new(
+            std::io::ErrorKind::ConnectionRefused,
+            "connection refused",
+        ))
+    }
+
+    #[test]
+    fn test_exception() {
+        let exception = Exception::new(&query_error());
+        assert_eq!(exception.r#type.as_deref(), Some("Connection"));
+        assert_eq!(exception.message, "query failed");
+        assert_eq!(
+            exception.stacktrace,
+            "query failed\nCaused by: connection refused"
+        );
+    }
+
+    #[test]
+    fn test_span_start_records() {
+        let (layer, spans) = crate::builder("fields")
+            .with_span_start_records(true)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("running", job = "import");
+            let started = spans.finished_spans();
+            assert_eq!(started.len(), 1);
+            assert_eq!(
+                attribute(&started[0], SPAN_STATE),
+                Some(&opentelemetry::Value::from("started"))
+            );
+            assert_eq!(
+                attribute(&started[0], "job"),
+                Some(&opentelemetry::Value::from("import"))
+            );
+            drop(span);
+        });
+
+        let finished = spans.finished_spans();
+        let [started, ended] = &finished[..] else {
+            panic!("expected a start record and the span");
+        };
+        assert_eq!(started.span_context, ended.span_context);
+        assert_eq!(started.start_time, ended.start_time);
+        assert_eq!(attribute(ended, SPAN_STATE), None);
+        assert!(is_start_record(started));
+        assert!(!is_start_record(ended));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_span_start_records_with_sampling() -> Result<(), crate::Error> {
+        use crate::testing::Recording;
+        use tracing_subscriber::Registry;
+
+        let recording = Recording::default();
+        let (layer, guard) = crate::Builder::default()
+            .with_exporter(recording.clone())
+            .with_span_start_records(true)
+            .with_min_span_duration(Duration::from_secs(1))
+            .errors_only()
+            .build_isolated::<Registry>()?;
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            tracing::info_span!("request").in_scope(|| {
+                tracing::info_span!("cache").in_scope(|| {});
+                tracing::info_span!("query").in_scope(|| tracing::error!("timeout"));
+            });
+        });
+        guard.force_flush()?;
+        // The start record of the root span doesn't end its trace, and the
+        // short cache lookup is dropped while its start record is kept
+        assert_eq!(
+            recording.names(),
+            ["cache", "query", "query", "request", "request"]
+        );
+        let started = recording
+            .spans()
+            .iter()
+            .filter(|span| is_start_record(span))
+            .count();
+        assert_eq!(started, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_orphan_event_spans() {
+        let (layer, spans) = crate::builder("fields")
+            .with_orphan_event_spans(true)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "startup", attempt = 3_u64, "config missing");
+            tracing::info_span!("request").in_scope(|| tracing::info!("in span"));
+        });
+
+        let orphan = spans.span("startup").expect("orphan event span");
+        assert_eq!(orphan.events.len(), 1);
+        let event = &orphan.events[0];
+        assert_eq!(event.name, "config missing");
+        assert!(event.attributes.contains(&KeyValue::new("attempt", 3)));
+        assert!(event.attributes.contains(&KeyValue::new("level", "WARN")));
+        // Events in spans are exported with their span only
+        assert_eq!(spans.finished_spans().len(), 2);
+    }
+
+    #[test]
+    fn test_span_summaries() {
+        let (layer, spans) = crate::builder("fields")
+            .with_trace_config(
+                opentelemetry_sdk::trace::config()
+                    .with_sampler(opentelemetry_sdk::trace::Sampler::AlwaysOff),
+            )
+            .with_span_summaries(Duration::from_millis(100))
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("query").in_scope(|| tracing::error!("timeout"));
+            tracing::info_span!("query").in_scope(|| {});
+            // Ends the window of the queries
+            std::thread::sleep(Duration::from_millis(150));
+            tracing::info_span!("render").in_scope(|| {});
+        });
+
+        let summaries = spans.finished_spans();
+        let [summary] = &summaries[..] else {
+            panic!("expected only the summary of the queries");
+        };
+        assert_eq!(summary.name, "span.summary");
+        assert_eq!(
+            attribute(summary, "summary.span_name"),
+            Some(&opentelemetry::Value::from("query"))
+        );
+        assert_eq!(
+            attribute(summary, "summary.count"),
+            Some(&opentelemetry::Value::from(2))
+        );
+        assert_eq!(
+            attribute(summary, "summary.error_rate"),
+            Some(&opentelemetry::Value::from(0.5))
+        );
+    }
+
+    #[test]
+    fn test_span_summaries_on_flush() {
+        let (layer, spans) = crate::builder("fields")
+            .with_span_summaries(Duration::from_secs(3600))
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("query").in_scope(|| {});
+        });
+        assert!(spans.span("span.summary").is_none());
+
+        for result in spans.provider().force_flush() {
+            result.expect("flushed");
+        }
+        let summary = spans.span("span.summary").expect("summary of the query");
+        assert_eq!(
+            attribute(&summary, "summary.count"),
+            Some(&opentelemetry::Value::from(1))
+        );
+        assert!(attribute(&summary, "summary.duration_ms.max").is_some());
+    }
+
+    #[test]
+    fn test_error_chains() {
+        let (layer, spans) = crate::builder("fields").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let error = query_error();
+            tracing::info_span!("query", error = &error as &dyn Error).in_scope(|| {
+                tracing::error!(error = &error as &dyn Error, "retrying");
+            });
+        });
+
+        let span = spans.span("query").expect("query span");
+        let stacktrace = opentelemetry::Value::from("query failed\nCaused by: connection refused");
+        assert_eq!(
+            attribute(&span, EXCEPTION_TYPE),
+            Some(&opentelemetry::Value::from("Connection"))
+        );
+        assert_eq!(attribute(&span, EXCEPTION_STACKTRACE), Some(&stacktrace));
+        assert_eq!(span.events[0].name, "exception");
+        assert_eq!(span.events[1].name, "retrying");
+        for event in span.events.iter() {
+            assert!(event
+                .attributes
+                .contains(&KeyValue::new(EXCEPTION_STACKTRACE, stacktrace.clone())));
+            assert!(event
+                .attributes
+                .contains(&KeyValue::new(EXCEPTION_MESSAGE, "query failed")));
+        }
+    }
+
+    #[test]
+    fn test_event_fields() {
+        let (layer, spans) = crate::builder("fields").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request").in_scope(|| {
+                tracing::info!(target: "app", "hello");
+                tracing::info!(user.id = 42);
+            });
+        });
+
+        let span = spans.span("request").expect("request span");
+        let hello = &span.events[0];
+        for attribute in [
+            KeyValue::new("level", "INFO"),
+            KeyValue::new("target", "app"),
+            KeyValue::new(EVENT_MODULE_PATH, module_path!()),
+        ] {
+            assert!(hello.attributes.contains(&attribute), "{attribute:?}");
+        }
+        // The message is the name already
+        assert!(!hello
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == EVENT_MESSAGE));
+        let unnamed = &span.events[1];
+        assert!(unnamed.name.starts_with("event src/fields.rs:"));
+    }
+
+    #[test]
+    fn test_event_message() {
+        let (layer, spans) = crate::builder("fields")
+            .with_event_message(true)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request").in_scope(|| {
+                tracing::info!("hello");
+                tracing::info!(user.id = 42);
+            });
+        });
+
+        let span = spans.span("request").expect("request span");
+        for event in &span.events {
+            assert!(event
+                .attributes
+                .contains(&KeyValue::new(EVENT_MESSAGE, event.name.clone())));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_threads() {
+        let (layer, spans) = crate::builder("fields").with_threads(true).build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let _guard = tracing::subscriber::set_default(subscriber);
+        tokio::spawn(async {
+            tracing::info_span!("task").in_scope(|| tracing::info!("working"));
+        })
+        .await
+        .expect("task");
+
+        let span = spans.span("task").expect("task span");
+        assert!(attribute(&span, THREAD_ID).is_some());
+        assert!(attribute(&span, TOKIO_TASK_ID).is_some());
+        let event = &span.events[0];
+        for key in [THREAD_ID, THREAD_NAME, TOKIO_TASK_ID] {
+            assert!(
+                event.attributes.iter().any(|kv| kv.key.as_str() == key),
+                "{key}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_burst_suppression() {
+        let (layer, spans) = crate::builder("fields")
+            .with_burst_suppression(1, std::time::Duration::from_secs(3600), 4)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("loop").in_scope(|| {
+                for _ in 0..10 {
+                    tracing::error!("connection refused");
+                }
+                tracing::info!("done");
+            });
+        });
+
+        let span = spans.span("loop").expect("loop span");
+        let suppressed = span
+            .events
+            .iter()
+            .map(|event| {
+                event
+                    .attributes
+                    .iter()
+                    .find(|kv| kv.key.as_str() == SUPPRESSED_COUNT)
+                    .map(|kv| kv.value.clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            suppressed,
+            [None, Some(3.into()), Some(3.into()), None],
+            "{:?}",
+            span.events
+        );
+    }
+
+    #[test]
+    fn test_event_dedup() {
+        let (layer, spans) = crate::builder("fields")
+            .with_event_dedup(std::time::Duration::from_secs(3600))
+            .with_burst_suppression(1, std::time::Duration::from_secs(3600), 2)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("retries").in_scope(|| {
+                for _ in 0..5 {
+                    tracing::warn!("retrying");
+                }
+                tracing::info!("done");
+            });
+            // Only events of the same span are coalesced
+            tracing::info_span!("other").in_scope(|| tracing::warn!("retrying"));
+        });
+
+        let span = spans.span("retries").expect("retries span");
+        assert_eq!(span.events.len(), 2);
+        assert_eq!(span.events[0].name, "retrying");
+        assert!(span.events[0]
+            .attributes
+            .contains(&KeyValue::new("occurrences", 5)));
+        let other = spans.span("other").expect("other span");
+        assert_eq!(other.events.len(), 1);
+        assert!(!other.events[0]
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "occurrences"));
+    }
+
+    #[test]
+    fn test_scope() {
+        let (layer, spans) = crate::builder("fields").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request").in_scope(|| {
+                crate::scope()
+                    .with_field("tenant_id", "acme")
+                    .with_field("plan", "free")
+                    .with_field("plan", "pro");
+                tracing::info_span!("query", plan = "own").in_scope(|| tracing::info!("done"));
+            });
+            tracing::info_span!("unscoped").in_scope(|| tracing::info!("done"));
+        });
+
+        let field = |attributes: &[KeyValue], key: &str| {
+            attributes
+                .iter()
+                .filter(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.to_string())
+                .collect::<Vec<_>>()
+        };
+        let request = spans.span("request").expect("request span");
+        assert_eq!(field(&request.attributes, "tenant_id"), ["acme"]);
+        let query = spans.span("query").expect("query span");
+        assert_eq!(field(&query.attributes, "tenant_id"), ["acme"]);
+        // Fields of the span itself take precedence
+        assert_eq!(field(&query.attributes, "plan"), ["own"]);
+        assert_eq!(field(&query.events[0].attributes, "tenant_id"), ["acme"]);
+        assert_eq!(field(&query.events[0].attributes, "plan"), ["pro"]);
+        let unscoped = spans.span("unscoped").expect("unscoped span");
+        assert!(field(&unscoped.events[0].attributes, "tenant_id").is_empty());
+    }
+
+    #[test]
+    fn test_error_status_description() {
+        let status = |builder: crate::Builder| {
+            let (layer, spans) = builder.build_for_test();
+            let subscriber = tracing_subscriber::registry().with(layer);
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info_span!("failed").in_scope(|| {
+                    tracing::warn!("retrying");
+                    tracing::error!("database is down");
+                    tracing::error!("giving up");
+                });
+            });
+            spans.span("failed").expect("failed span").status
+        };
+
+        assert_eq!(status(crate::builder("fields")), Status::error(""));
+        assert_eq!(
+            status(crate::builder("fields").with_error_status_description(true)),
+            Status::error("database is down")
+        );
+    }
+
+    #[cfg(feature = "tracing-error")]
+    #[test]
+    fn test_span_trace() {
+        use tracing_error::InstrumentError as _;
+
+        let (layer, spans) = crate::builder("fields").build_for_test();
+        let subscriber = tracing_subscriber::registry()
+            .with(layer)
+            .with(tracing_error::ErrorLayer::default());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request").in_scope(|| {
+                let error =
+                    tracing::info_span!("query").in_scope(|| query_error().in_current_span());
+                tracing::error!(error = &error as &dyn Error, "failed");
+            });
+        });
+
+        let span = spans.span("request").expect("request span");
+        let event = &span.events[0];
+        assert!(event.attributes.contains(&KeyValue::new(
+            EXCEPTION_STACKTRACE,
+            "query failed\nCaused by: connection refused"
+        )));
+        let span_trace = event
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == EXCEPTION_SPAN_TRACE)
+            .expect("span trace")
+            .value
+            .as_str();
+        let span_trace: serde_json::Value =
+            serde_json::from_str(&span_trace).expect("span trace is JSON");
+        assert_eq!(span_trace[0]["name"], "query");
+        assert_eq!(span_trace[1]["name"], "request");
+        assert_eq!(span_trace[0]["file"], file!());
+    }
+
+    #[cfg(feature = "valuable")]
+    #[test]
+    fn test_structured_spans_and_events() {
+        use std::collections::BTreeMap;
+        use valuable::{Valuable as _, Value};
+
+        let (layer, spans) = crate::builder("fields").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let address = BTreeMap::from([("city", "Berlin")]);
+            let roles = ["admin", "dev"];
+            let user = BTreeMap::from([
+                ("id", Value::U32(42)),
+                ("address", address.as_value()),
+                ("roles", roles.as_value()),
+            ]);
+            tracing::info_span!("login", user = user.as_value()).in_scope(|| {
+                tracing::info!(user = user.as_value(), "welcome");
+            });
+        });
+
+        let span = spans.span("login").expect("login span");
+        assert_eq!(attribute(&span, "user"), None);
+        assert_eq!(
+            attribute(&span, "user.id"),
+            Some(&opentelemetry::Value::I64(42))
+        );
+        assert_eq!(
+            attribute(&span, "user.address.city"),
+            Some(&opentelemetry::Value::from("Berlin"))
+        );
+        assert_eq!(
+            attribute(&span, "user.roles"),
+            Some(&opentelemetry::Value::from(r#"["admin","dev"]"#))
+        );
+        let event = &span.events[0];
+        assert!(event.attributes.contains(&KeyValue::new("user.id", 42)));
+        assert!(!event.attributes.iter().any(|kv| kv.key.as_str() == "user"));
+    }
+}
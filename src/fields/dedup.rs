@@ -0,0 +1,58 @@
+use opentelemetry::{trace::Event as OtelEvent, KeyValue, Value};
+use std::{collections::HashMap, time::Duration};
+use tracing_core::callsite::Identifier;
+
+/// The number of times an event was recorded on the span within the window.
+const OCCURRENCES: &str = "occurrences";
+
+/// The first event with each callsite and message recorded on a span within
+/// the dedup window, see
+/// [`Builder::with_event_dedup`](crate::Builder::with_event_dedup).
+#[derive(Debug, Default)]
+pub(crate) struct SeenEvents(HashMap<(Identifier, String), usize>);
+
+impl SeenEvents {
+    /// Removes the last event if it is identical to one recorded within the
+    /// window and counts it on that one instead. Returns whether it was removed.
+    pub(crate) fn coalesce(
+        &self,
+        callsite: &Identifier,
+        events: &mut Vec<OtelEvent>,
+        window: Duration,
+    ) -> bool {
+        let Some(last) = events.last() else {
+            return false;
+        };
+        let Some(&first) = self.0.get(&(callsite.clone(), last.name.to_string())) else {
+            return false;
+        };
+        let within_window = last
+            .timestamp
+            .duration_since(events[first].timestamp)
+            .is_ok_and(|elapsed| elapsed < window);
+        if !within_window {
+            return false;
+        }
+        events.pop();
+        let attributes = &mut events[first].attributes;
+        match attributes
+            .iter_mut()
+            .find(|kv| kv.key.as_str() == OCCURRENCES)
+        {
+            Some(KeyValue {
+                value: Value::I64(count),
+                ..
+            }) => *count += 1,
+            _ => attributes.push(KeyValue::new(OCCURRENCES, 2)),
+        }
+        true
+    }
+
+    /// Remembers the last event as the first of its kind.
+    pub(crate) fn insert(&mut self, callsite: Identifier, events: &[OtelEvent]) {
+        if let Some(last) = events.last() {
+            self.0
+                .insert((callsite, last.name.to_string()), events.len() - 1);
+        }
+    }
+}
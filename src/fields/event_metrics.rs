@@ -0,0 +1,266 @@
+use opentelemetry::metrics::{Counter, Histogram, Meter, MeterProvider as _, UpDownCounter};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Mutex, PoisonError},
+};
+use tracing_core::field::{Field, Visit};
+
+const MONOTONIC_COUNTER: &str = "monotonic_counter.";
+const COUNTER: &str = "counter.";
+const HISTOGRAM: &str = "histogram.";
+
+/// Records event fields named like the metrics of `tracing-opentelemetry` as
+/// metrics, see [`Builder::with_metrics`](crate::Builder::with_metrics).
+pub(crate) struct EventMetrics {
+    // Keeps the metrics exported until the layer is dropped
+    provider: SdkMeterProvider,
+    meter: Meter,
+    instruments: Mutex<HashMap<(&'static str, Kind), Instrument>>,
+}
+
+impl EventMetrics {
+    pub(crate) fn new(provider: SdkMeterProvider) -> Self {
+        let meter = provider.versioned_meter(
+            env!("CARGO_PKG_NAME"),
+            Some(env!("CARGO_PKG_VERSION")),
+            None::<&'static str>,
+            None,
+        );
+        Self {
+            provider,
+            meter,
+            instruments: Mutex::default(),
+        }
+    }
+
+    /// Records the value of the field if it is a metric.
+    fn record(&self, field: &Field, value: Number) {
+        let name = field.name();
+        let (kind, name) = if let Some(name) = name.strip_prefix(MONOTONIC_COUNTER) {
+            (Kind::MonotonicCounter(value.is_float()), name)
+        } else if let Some(name) = name.strip_prefix(COUNTER) {
+            (Kind::Counter(value.is_float()), name)
+        } else if let Some(name) = name.strip_prefix(HISTOGRAM) {
+            (Kind::Histogram(value.is_float()), name)
+        } else {
+            return;
+        };
+        let instrument = self
+            .instruments
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry((name, kind))
+            .or_insert_with(|| kind.instrument(&self.meter, name))
+            .clone();
+        instrument.record(value);
+    }
+}
+
+impl fmt::Debug for EventMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventMetrics")
+            .field("provider", &self.provider)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Visit for &EventMetrics {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record(field, Number::F64(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, Number::I64(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, Number::U64(value));
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl Number {
+    fn is_float(self) -> bool {
+        matches!(self, Self::F64(_))
+    }
+
+    /// The value if it is a non-negative integer.
+    fn as_u64(self) -> Option<u64> {
+        match self {
+            Self::U64(value) => Some(value),
+            Self::I64(value) => u64::try_from(value).ok(),
+            Self::F64(_) => None,
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::U64(value) => value as f64,
+            Self::I64(value) => value as f64,
+            Self::F64(value) => value,
+        }
+    }
+}
+
+/// The kind of instrument, and whether it records floats. Integer literals
+/// are recorded as `i64` by `tracing`, so counters and histograms of integers
+/// share one instrument regardless of their type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    MonotonicCounter(bool),
+    Counter(bool),
+    Histogram(bool),
+}
+
+impl Kind {
+    fn instrument(self, meter: &Meter, name: &'static str) -> Instrument {
+        match self {
+            Self::MonotonicCounter(false) => Instrument::U64Counter(meter.u64_counter(name).init()),
+            Self::MonotonicCounter(true) => Instrument::F64Counter(meter.f64_counter(name).init()),
+            Self::Counter(false) => {
+                Instrument::I64UpDownCounter(meter.i64_up_down_counter(name).init())
+            }
+            Self::Counter(true) => {
+                Instrument::F64UpDownCounter(meter.f64_up_down_counter(name).init())
+            }
+            Self::Histogram(false) => Instrument::U64Histogram(meter.u64_histogram(name).init()),
+            Self::Histogram(true) => Instrument::F64Histogram(meter.f64_histogram(name).init()),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Instrument {
+    U64Counter(Counter<u64>),
+    F64Counter(Counter<f64>),
+    I64UpDownCounter(UpDownCounter<i64>),
+    F64UpDownCounter(UpDownCounter<f64>),
+    U64Histogram(Histogram<u64>),
+    F64Histogram(Histogram<f64>),
+}
+
+impl Instrument {
+    /// Records the value, negative values of monotonic counters and integer
+    /// histograms are dropped.
+    fn record(&self, value: Number) {
+        match self {
+            Self::U64Counter(counter) => {
+                if let Some(value) = value.as_u64() {
+                    counter.add(value, &[]);
+                }
+            }
+            Self::F64Counter(counter) => counter.add(value.as_f64(), &[]),
+            Self::I64UpDownCounter(counter) => {
+                let value = match value {
+                    Number::I64(value) => Some(value),
+                    Number::U64(value) => i64::try_from(value).ok(),
+                    Number::F64(_) => None,
+                };
+                if let Some(value) = value {
+                    counter.add(value, &[]);
+                }
+            }
+            Self::F64UpDownCounter(counter) => counter.add(value.as_f64(), &[]),
+            Self::U64Histogram(histogram) => {
+                if let Some(value) = value.as_u64() {
+                    histogram.record(value, &[]);
+                }
+            }
+            Self::F64Histogram(histogram) => histogram.record(value.as_f64(), &[]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_sdk::{
+        metrics::{
+            data::{ResourceMetrics, Temporality},
+            reader::{AggregationSelector, MetricReader, TemporalitySelector},
+            Aggregation, InstrumentKind, ManualReader, Pipeline,
+        },
+        Resource,
+    };
+    use std::sync::{Arc, Weak};
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    /// Lets the test collect from the reader the provider owns.
+    #[derive(Debug, Clone)]
+    struct SharedReader(Arc<ManualReader>);
+
+    impl MetricReader for SharedReader {
+        fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+            self.0.register_pipeline(pipeline);
+        }
+
+        fn collect(&self, rm: &mut ResourceMetrics) -> opentelemetry::metrics::Result<()> {
+            self.0.collect(rm)
+        }
+
+        fn force_flush(&self) -> opentelemetry::metrics::Result<()> {
+            self.0.force_flush()
+        }
+
+        fn shutdown(&self) -> opentelemetry::metrics::Result<()> {
+            self.0.shutdown()
+        }
+    }
+
+    impl TemporalitySelector for SharedReader {
+        fn temporality(&self, kind: InstrumentKind) -> Temporality {
+            self.0.temporality(kind)
+        }
+    }
+
+    impl AggregationSelector for SharedReader {
+        fn aggregation(&self, kind: InstrumentKind) -> Aggregation {
+            self.0.aggregation(kind)
+        }
+    }
+
+    #[test]
+    fn test_event_metrics() {
+        let reader = Arc::new(ManualReader::builder().build());
+        let provider = SdkMeterProvider::builder()
+            .with_reader(SharedReader(reader.clone()))
+            .build();
+        let fields = crate::FieldsLayer {
+            metrics: Some(EventMetrics::new(provider)),
+            ..crate::FieldsLayer::default()
+        };
+        let subscriber = tracing_subscriber::registry().with(fields);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(monotonic_counter.orders = 1, "order placed");
+            tracing::info!(
+                monotonic_counter.orders = 2_u64,
+                histogram.latency_ms = 12.5
+            );
+            tracing::info!(counter.connections = -1, other = 5);
+        });
+
+        let mut metrics = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: Vec::new(),
+        };
+        reader.collect(&mut metrics).expect("collected");
+        let mut names = metrics
+            .scope_metrics
+            .iter()
+            .flat_map(|scope| scope.metrics.iter().map(|metric| metric.name.to_string()))
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["connections", "latency_ms", "orders"]);
+    }
+}
@@ -0,0 +1,82 @@
+use opentelemetry::{
+    trace::{Link, SpanBuilder, SpanContext, SpanId, TraceFlags, TraceId, TraceState},
+    KeyValue,
+};
+
+/// The field with the trace ID of the span a new span links to.
+const LINK_TRACE_ID: &str = "link.trace_id";
+/// The field with the span ID of the span a new span links to.
+const LINK_SPAN_ID: &str = "link.span_id";
+
+/// Replaces the `link.trace_id` and `link.span_id` attributes of a new span
+/// with a link to that span. Invalid IDs are kept as attributes.
+pub(super) fn add_link(builder: &mut SpanBuilder) {
+    let Some(attributes) = builder.attributes.as_mut() else {
+        return;
+    };
+    let Some(span_context) = span_context(attributes) else {
+        return;
+    };
+    attributes.retain(|kv| !matches!(kv.key.as_str(), LINK_TRACE_ID | LINK_SPAN_ID));
+    builder
+        .links
+        .get_or_insert_with(Vec::new)
+        .push(Link::new(span_context, Vec::new()));
+}
+
+fn span_context(attributes: &[KeyValue]) -> Option<SpanContext> {
+    let hex = |key: &str| {
+        attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.as_str())
+    };
+    let trace_id = TraceId::from_hex(&hex(LINK_TRACE_ID)?)
+        .ok()
+        .filter(|id| *id != TraceId::INVALID)?;
+    let span_id = SpanId::from_hex(&hex(LINK_SPAN_ID)?)
+        .ok()
+        .filter(|id| *id != SpanId::INVALID)?;
+    Some(SpanContext::new(
+        trace_id,
+        span_id,
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::attribute;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    #[test]
+    fn test_links() {
+        let (layer, spans) = crate::builder("links").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("batch", link.trace_id = TRACE_ID, link.span_id = SPAN_ID)
+                .in_scope(|| {});
+            tracing::info_span!("invalid", link.trace_id = "none", link.span_id = SPAN_ID)
+                .in_scope(|| {});
+        });
+
+        let batch = spans.span("batch").expect("batch span");
+        let [link] = &batch.links.links[..] else {
+            panic!("expected one link");
+        };
+        assert_eq!(link.span_context.trace_id().to_string(), TRACE_ID);
+        assert_eq!(link.span_context.span_id().to_string(), SPAN_ID);
+        assert_eq!(attribute(&batch, LINK_TRACE_ID), None);
+        assert_eq!(attribute(&batch, LINK_SPAN_ID), None);
+
+        let invalid = spans.span("invalid").expect("invalid span");
+        assert!(invalid.links.links.is_empty());
+        assert!(attribute(&invalid, LINK_TRACE_ID).is_some());
+    }
+}
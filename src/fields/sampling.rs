@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+use tracing_core::callsite::Identifier;
+
+/// The number of distinct events that are tracked, further events are always
+/// exported until the windows of tracked ones end.
+const MAX_TRACKED: usize = 4096;
+
+/// Exports only every nth of identical events firing at a high rate, see
+/// [`Builder::with_burst_suppression`](crate::Builder::with_burst_suppression).
+#[derive(Debug)]
+pub(crate) struct BurstSuppression {
+    threshold: u64,
+    window: Duration,
+    one_in: u64,
+    bursts: Mutex<HashMap<(Identifier, String), Burst>>,
+}
+
+#[derive(Debug)]
+struct Burst {
+    start: SystemTime,
+    count: u64,
+    suppressed: u64,
+}
+
+impl BurstSuppression {
+    pub(crate) fn new(threshold: u32, window: Duration, one_in: u32) -> Self {
+        Self {
+            threshold: threshold.into(),
+            window,
+            one_in: one_in.max(1).into(),
+            bursts: Mutex::default(),
+        }
+    }
+
+    /// The number of identical events suppressed since the last exported one,
+    /// or `None` if this one is suppressed as well.
+    pub(crate) fn sample(&self, callsite: Identifier, message: &str) -> Option<u64> {
+        let now = opentelemetry::time::now();
+        let expired = |start: SystemTime| {
+            now.duration_since(start)
+                .map_or(true, |elapsed| elapsed >= self.window)
+        };
+        let mut bursts = self.bursts.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = (callsite, message.to_string());
+        if bursts.len() >= MAX_TRACKED && !bursts.contains_key(&key) {
+            bursts.retain(|_, burst| !expired(burst.start) || burst.suppressed > 0);
+            if bursts.len() >= MAX_TRACKED {
+                return Some(0);
+            }
+        }
+        let burst = bursts.entry(key).or_insert(Burst {
+            start: now,
+            count: 0,
+            suppressed: 0,
+        });
+        if expired(burst.start) {
+            burst.start = now;
+            burst.count = 0;
+        }
+        burst.count += 1;
+        if burst.count <= self.threshold || (burst.count - self.threshold) % self.one_in == 0 {
+            Some(std::mem::take(&mut burst.suppressed))
+        } else {
+            burst.suppressed += 1;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_core::{callsite::Callsite as _, Kind, Level, Metadata};
+
+    struct TestCallsite;
+
+    impl tracing_core::Callsite for TestCallsite {
+        fn set_interest(&self, _interest: tracing_core::Interest) {}
+
+        fn metadata(&self) -> &Metadata<'_> {
+            &METADATA
+        }
+    }
+
+    static CALLSITE: TestCallsite = TestCallsite;
+    static METADATA: Metadata<'static> = Metadata::new(
+        "test",
+        "test",
+        Level::ERROR,
+        None,
+        None,
+        None,
+        tracing_core::field::FieldSet::new(&[], tracing_core::identify_callsite!(&CALLSITE)),
+        Kind::EVENT,
+    );
+
+    #[test]
+    fn test_sample() {
+        let suppression = BurstSuppression::new(2, Duration::from_secs(3600), 3);
+        let sample =
+            |message: &'static str| suppression.sample(CALLSITE.metadata().callsite(), message);
+        let sampled = (0..9).map(|_| sample("retry")).collect::<Vec<_>>();
+        assert_eq!(
+            sampled,
+            [
+                Some(0),
+                Some(0),
+                None,
+                None,
+                Some(2),
+                None,
+                None,
+                Some(2),
+                None
+            ]
+        );
+        // Other messages are counted separately
+        assert_eq!(sample("other"), Some(0));
+    }
+
+    #[test]
+    fn test_window() {
+        let suppression = BurstSuppression::new(1, Duration::ZERO, 10);
+        let callsite = CALLSITE.metadata().callsite();
+        // Every event starts a new window
+        for _ in 0..3 {
+            assert_eq!(suppression.sample(callsite.clone(), "x"), Some(0));
+        }
+    }
+}
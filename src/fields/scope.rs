@@ -0,0 +1,80 @@
+use opentelemetry::{Key, KeyValue, Value};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+use tracing_subscriber::{registry::LookupSpan as _, Registry};
+
+/// The fields added with [`Scope::with_field`] to a span, which its
+/// descendants inherit.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScopedFields(pub(crate) Vec<KeyValue>);
+
+impl ScopedFields {
+    fn set(&mut self, kv: KeyValue) {
+        match self.0.iter_mut().find(|field| field.key == kv.key) {
+            Some(field) => *field = kv,
+            None => self.0.push(kv),
+        }
+    }
+
+    /// Adds the fields whose keys are not set yet to the attributes.
+    pub(crate) fn add_to(&self, attributes: &mut Vec<KeyValue>) {
+        for kv in &self.0 {
+            if !attributes.iter().any(|attribute| attribute.key == kv.key) {
+                attributes.push(kv.clone());
+            }
+        }
+    }
+}
+
+/// Adds fields to the current span, all spans within it and all events
+/// recorded in them, see [`scope`].
+#[derive(Debug)]
+pub struct Scope {
+    span: tracing::Span,
+}
+
+/// The scope of the current span, to add context fields like a tenant ID once
+/// rather than to every span and event. Spans created within the current span
+/// inherit the fields, including those of tasks spawned with a future
+/// instrumented with one of them, so the fields survive `.await` points.
+///
+/// Requires a subscriber built on [`Registry`], like the ones built with
+/// `tracing_subscriber::registry()` or `tracing_subscriber::fmt()`.
+///
+/// ```rust
+/// #[tracing::instrument(skip_all)]
+/// async fn handle(tenant_id: u64) {
+///     tracing_axiom::scope().with_field("tenant_id", tenant_id.to_string());
+///     // Recorded with `tenant_id`
+///     tracing::info!("handling request");
+/// }
+/// ```
+#[must_use]
+pub fn scope() -> Scope {
+    Scope {
+        span: tracing::Span::current(),
+    }
+}
+
+impl Scope {
+    /// Adds the field, replacing a value previously added for the key. Does
+    /// nothing outside of a span.
+    #[allow(clippy::return_self_not_must_use)]
+    pub fn with_field(self, key: impl Into<Key>, value: impl Into<Value>) -> Self {
+        let kv = KeyValue::new(key, value);
+        self.span.set_attribute(kv.key.clone(), kv.value.clone());
+        self.span.with_subscriber(|(id, dispatch)| {
+            let Some(span) = dispatch
+                .downcast_ref::<Registry>()
+                .and_then(|registry| registry.span(id))
+            else {
+                return;
+            };
+            let mut extensions = span.extensions_mut();
+            match extensions.get_mut::<ScopedFields>() {
+                Some(fields) => fields.set(kv),
+                None => extensions.insert(ScopedFields(vec![kv])),
+            }
+        });
+        self
+    }
+}
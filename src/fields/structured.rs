@@ -0,0 +1,196 @@
+use opentelemetry::KeyValue;
+use serde_json::Map;
+use valuable::{NamedValues, Value};
+
+/// How fields recorded with [`valuable`](https://docs.rs/valuable) are
+/// exported, see [`Builder::with_flattening`](crate::Builder::with_flattening).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Flattening {
+    /// One attribute per nested field with the path joined by dots, e.g.
+    /// `user.address.city`. Lists are exported as JSON strings and enum
+    /// variants without fields as their name.
+    #[default]
+    Dots,
+    /// One attribute with the whole value as JSON string, for datasets that
+    /// shouldn't get a new column per nested field.
+    Json,
+}
+
+impl Flattening {
+    pub(super) fn attributes(
+        self,
+        key: &str,
+        json: serde_json::Value,
+        attributes: &mut Vec<KeyValue>,
+    ) {
+        match (self, json) {
+            (Flattening::Json, json @ serde_json::Value::Object(_)) => {
+                attributes.push(KeyValue::new(key.to_string(), json.to_string()));
+            }
+            (_, json) => flatten(key.to_string(), json, attributes),
+        }
+    }
+}
+
+fn flatten(key: String, json: serde_json::Value, attributes: &mut Vec<KeyValue>) {
+    match json {
+        serde_json::Value::Null => {}
+        serde_json::Value::Bool(b) => attributes.push(KeyValue::new(key, b)),
+        serde_json::Value::Number(n) => attributes.push(match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => KeyValue::new(key, i),
+            (None, Some(f)) if !n.is_u64() => KeyValue::new(key, f),
+            _ => KeyValue::new(key, n.to_string()),
+        }),
+        serde_json::Value::String(s) => attributes.push(KeyValue::new(key, s)),
+        json @ serde_json::Value::Array(_) => {
+            attributes.push(KeyValue::new(key, json.to_string()));
+        }
+        serde_json::Value::Object(fields) => {
+            for (name, value) in fields {
+                flatten(format!("{key}.{name}"), value, attributes);
+            }
+        }
+    }
+}
+
+pub(super) fn to_json(value: Value<'_>) -> serde_json::Value {
+    match value {
+        Value::Bool(b) => b.into(),
+        Value::Char(c) => c.to_string().into(),
+        Value::F32(f) => f.into(),
+        Value::F64(f) => f.into(),
+        Value::I8(i) => i.into(),
+        Value::I16(i) => i.into(),
+        Value::I32(i) => i.into(),
+        Value::I64(i) => i.into(),
+        Value::Isize(i) => i.into(),
+        Value::I128(i) => i64::try_from(i).map_or_else(|_| i.to_string().into(), Into::into),
+        Value::U8(u) => u.into(),
+        Value::U16(u) => u.into(),
+        Value::U32(u) => u.into(),
+        Value::U64(u) => u.into(),
+        Value::Usize(u) => u.into(),
+        Value::U128(u) => u64::try_from(u).map_or_else(|_| u.to_string().into(), Into::into),
+        Value::String(s) => s.into(),
+        Value::Path(p) => p.display().to_string().into(),
+        Value::Error(e) => e.to_string().into(),
+        Value::Unit => serde_json::Value::Null,
+        Value::Listable(list) => {
+            let mut visitor = JsonVisitor::default();
+            list.visit(&mut visitor);
+            serde_json::Value::Array(visitor.items)
+        }
+        Value::Tuplable(tuple) => {
+            let mut visitor = JsonVisitor::default();
+            tuple.visit(&mut visitor);
+            serde_json::Value::Array(visitor.items)
+        }
+        Value::Mappable(map) => {
+            let mut visitor = JsonVisitor::default();
+            map.visit(&mut visitor);
+            visitor.finish()
+        }
+        Value::Structable(structure) => {
+            let mut visitor = JsonVisitor::default();
+            structure.visit(&mut visitor);
+            visitor.finish()
+        }
+        Value::Enumerable(enumeration) => {
+            let mut visitor = JsonVisitor::default();
+            enumeration.visit(&mut visitor);
+            let variant = enumeration.variant().name().to_string();
+            if visitor.items.is_empty() && visitor.fields.is_empty() {
+                variant.into()
+            } else {
+                serde_json::Value::Object(Map::from_iter([(variant, visitor.finish())]))
+            }
+        }
+        _ => format!("{value:?}").into(),
+    }
+}
+
+/// Collects the items of lists and unnamed fields, or the named fields and
+/// entries of structs and maps.
+#[derive(Default)]
+struct JsonVisitor {
+    items: Vec<serde_json::Value>,
+    fields: Map<String, serde_json::Value>,
+}
+
+impl JsonVisitor {
+    fn finish(self) -> serde_json::Value {
+        if self.items.is_empty() {
+            serde_json::Value::Object(self.fields)
+        } else {
+            serde_json::Value::Array(self.items)
+        }
+    }
+}
+
+impl valuable::Visit for JsonVisitor {
+    fn visit_value(&mut self, value: Value<'_>) {
+        self.items.push(to_json(value));
+    }
+
+    fn visit_named_fields(&mut self, named_values: &NamedValues<'_>) {
+        for (field, value) in named_values {
+            self.fields
+                .insert(field.name().to_string(), to_json(*value));
+        }
+    }
+
+    fn visit_unnamed_fields(&mut self, values: &[Value<'_>]) {
+        self.items.extend(values.iter().copied().map(to_json));
+    }
+
+    fn visit_entry(&mut self, key: Value<'_>, value: Value<'_>) {
+        let key = match to_json(key) {
+            serde_json::Value::String(s) => s,
+            key => key.to_string(),
+        };
+        self.fields.insert(key, to_json(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use valuable::Valuable as _;
+
+    const ROLES: [&str; 2] = ["admin", "dev"];
+
+    #[test]
+    fn test_to_json() {
+        let address = BTreeMap::from([("city", "Berlin")]);
+        let user = BTreeMap::from([
+            ("id", Value::U32(42)),
+            ("address", address.as_value()),
+            ("roles", ROLES.as_value()),
+        ]);
+        assert_eq!(
+            to_json(user.as_value()),
+            serde_json::json!({ "id": 42, "address": { "city": "Berlin" }, "roles": ["admin", "dev"] })
+        );
+        assert_eq!(to_json(Some(1.5).as_value()), serde_json::json!(1.5));
+    }
+
+    #[test]
+    fn test_flattening() {
+        let json = serde_json::json!({ "id": 42, "address": { "city": "Berlin" }, "tags": [1] });
+        let mut attributes = Vec::new();
+        Flattening::Dots.attributes("user", json.clone(), &mut attributes);
+        assert_eq!(
+            attributes,
+            vec![
+                KeyValue::new("user.address.city", "Berlin"),
+                KeyValue::new("user.id", 42),
+                KeyValue::new("user.tags", "[1]"),
+            ]
+        );
+
+        let mut attributes = Vec::new();
+        Flattening::Json.attributes("user", json.clone(), &mut attributes);
+        assert_eq!(attributes, vec![KeyValue::new("user", json.to_string())]);
+    }
+}
@@ -0,0 +1,292 @@
+use opentelemetry::{
+    trace::{
+        SamplingDecision, SamplingResult, Span as _, SpanBuilder, Status, TraceResult, TraceState,
+    },
+    Context as OtelContext, KeyValue,
+};
+use opentelemetry_sdk::{
+    export::trace::SpanData,
+    trace::{Span, SpanProcessor, Tracer},
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+    time::{Duration, SystemTime},
+};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::registry::ExtensionsMut;
+
+/// The name of the spans the summaries are exported as.
+const SPAN_SUMMARY: &str = "span.summary";
+/// The number of span names that are tracked per window, spans with further
+/// names are not summarized until the window ends.
+const MAX_TRACKED: usize = 4096;
+/// The number of durations kept per span name and window to compute the
+/// percentiles, further durations replace the oldest ones.
+const MAX_SAMPLES: usize = 4096;
+
+/// Aggregates the rate, errors and durations of spans per name, whether they
+/// are sampled or not, and exports them as one summary span per name and
+/// window, see
+/// [`Builder::with_span_summaries`](crate::Builder::with_span_summaries).
+#[derive(Debug)]
+pub(crate) struct SpanSummaries {
+    interval: Duration,
+    window: Mutex<Window>,
+    /// The tracer the summaries are exported with, set once the tracer
+    /// provider is built.
+    tracer: OnceLock<Tracer>,
+}
+
+/// Exports the summaries of the current window when the tracer provider is
+/// flushed, so they aren't held back until the next span closes.
+#[derive(Debug)]
+pub(crate) struct SummaryFlush(Arc<SpanSummaries>);
+
+#[derive(Debug)]
+struct Window {
+    start: SystemTime,
+    spans: HashMap<Cow<'static, str>, Stats>,
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    count: u64,
+    errors: u64,
+    max: Duration,
+    durations: Vec<Duration>,
+    /// The index of the oldest duration once `MAX_SAMPLES` are kept.
+    next: usize,
+}
+
+/// The name, start and status of a span, kept in its extensions as the
+/// OpenTelemetry layer removes its data before the span closes.
+#[derive(Debug)]
+pub(crate) struct SpanTiming {
+    name: Cow<'static, str>,
+    start: SystemTime,
+    failed: bool,
+}
+
+impl SpanTiming {
+    pub(crate) fn new(otel_data: &OtelData) -> Self {
+        Self {
+            name: otel_data.builder.name.clone(),
+            start: otel_data
+                .builder
+                .start_time
+                .unwrap_or_else(opentelemetry::time::now),
+            failed: matches!(otel_data.builder.status, Status::Error { .. }),
+        }
+    }
+
+    /// Takes over the name and status the span has now, if it is summarized.
+    pub(crate) fn update(extensions: &mut ExtensionsMut<'_>) {
+        if extensions.get::<Self>().is_none() {
+            return;
+        }
+        let Some(otel_data) = extensions.get::<OtelData>() else {
+            return;
+        };
+        let current = Self::new(otel_data);
+        if let Some(timing) = extensions.get_mut::<Self>() {
+            timing.name = current.name;
+            timing.failed |= current.failed;
+        }
+    }
+}
+
+impl SpanSummaries {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            window: Mutex::new(Window::new(opentelemetry::time::now())),
+            tracer: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn set_tracer(&self, tracer: &Tracer) {
+        let _ = self.tracer.set(tracer.clone());
+    }
+
+    /// A span processor exporting the summaries whenever it is flushed.
+    pub(crate) fn flusher(self: &Arc<Self>) -> SummaryFlush {
+        SummaryFlush(self.clone())
+    }
+
+    /// Records a closed span, exporting the summaries of the previous window
+    /// first if it ended.
+    pub(crate) fn record(&self, timing: SpanTiming) {
+        let now = opentelemetry::time::now();
+        let mut window = self.window.lock().unwrap_or_else(PoisonError::into_inner);
+        let ended = now
+            .duration_since(window.start)
+            .map_or(false, |elapsed| elapsed >= self.interval);
+        let ended = ended.then(|| std::mem::replace(&mut *window, Window::new(now)));
+        if window.spans.len() < MAX_TRACKED || window.spans.contains_key(&timing.name) {
+            let duration = now.duration_since(timing.start).unwrap_or_default();
+            window
+                .spans
+                .entry(timing.name)
+                .or_default()
+                .record(duration, timing.failed);
+        }
+        drop(window);
+        if let (Some(tracer), Some(ended)) = (self.tracer.get(), ended) {
+            ended.export(tracer, now);
+        }
+    }
+
+    /// Exports the summaries of the current window and starts a new one.
+    fn flush(&self) {
+        let now = opentelemetry::time::now();
+        let window = std::mem::replace(
+            &mut *self.window.lock().unwrap_or_else(PoisonError::into_inner),
+            Window::new(now),
+        );
+        if let Some(tracer) = self.tracer.get() {
+            window.export(tracer, now);
+        }
+    }
+}
+
+impl SpanProcessor for SummaryFlush {
+    fn on_start(&self, _span: &mut Span, _cx: &OtelContext) {}
+
+    fn on_end(&self, _span: SpanData) {}
+
+    fn force_flush(&self) -> TraceResult<()> {
+        // Runs before the processors exporting the spans, which pick the
+        // summaries up in the same flush
+        self.0.flush();
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        Ok(())
+    }
+}
+
+impl Window {
+    fn new(start: SystemTime) -> Self {
+        Self {
+            start,
+            spans: HashMap::new(),
+        }
+    }
+
+    /// Exports a summary span per span name, bypassing the sampler so the
+    /// summaries stay complete however few traces are sampled.
+    #[allow(clippy::cast_precision_loss)]
+    fn export(self, tracer: &Tracer, end: SystemTime) {
+        let seconds = end
+            .duration_since(self.start)
+            .unwrap_or_default()
+            .as_secs_f64();
+        for (name, mut stats) in self.spans {
+            stats.durations.sort_unstable();
+            let count = stats.count as f64;
+            let mut attributes = vec![
+                KeyValue::new("summary.span_name", name),
+                KeyValue::new(
+                    "summary.count",
+                    i64::try_from(stats.count).unwrap_or(i64::MAX),
+                ),
+                KeyValue::new(
+                    "summary.errors",
+                    i64::try_from(stats.errors).unwrap_or(i64::MAX),
+                ),
+                KeyValue::new("summary.error_rate", stats.errors as f64 / count),
+            ];
+            if seconds > 0.0 {
+                attributes.push(KeyValue::new("summary.rate", count / seconds));
+            }
+            for (key, quantile) in [
+                ("summary.duration_ms.p50", 0.5),
+                ("summary.duration_ms.p90", 0.9),
+                ("summary.duration_ms.p99", 0.99),
+            ] {
+                attributes.push(KeyValue::new(key, millis(stats.percentile(quantile))));
+            }
+            // The slowest span may have been replaced in the samples
+            attributes.push(KeyValue::new("summary.duration_ms.max", millis(stats.max)));
+            SpanBuilder::from_name(SPAN_SUMMARY)
+                .with_start_time(self.start)
+                .with_attributes(attributes)
+                .with_sampling_result(SamplingResult {
+                    decision: SamplingDecision::RecordAndSample,
+                    attributes: Vec::new(),
+                    trace_state: TraceState::default(),
+                })
+                .start_with_context(tracer, &OtelContext::new())
+                .end_with_timestamp(end);
+        }
+    }
+}
+
+impl Stats {
+    fn record(&mut self, duration: Duration, failed: bool) {
+        self.count += 1;
+        if failed {
+            self.errors += 1;
+        }
+        self.max = self.max.max(duration);
+        if self.durations.len() < MAX_SAMPLES {
+            self.durations.push(duration);
+        } else {
+            self.durations[self.next] = duration;
+            self.next = (self.next + 1) % MAX_SAMPLES;
+        }
+    }
+
+    /// The nearest-rank percentile of the sorted durations.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn percentile(&self, quantile: f64) -> Duration {
+        let rank = (quantile * self.durations.len() as f64).ceil() as usize;
+        self.durations
+            .get(rank.saturating_sub(1))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let mut stats = Stats::default();
+        for ms in (1..=100).rev() {
+            stats.record(Duration::from_millis(ms), ms % 10 == 0);
+        }
+        stats.durations.sort_unstable();
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.errors, 10);
+        assert_eq!(stats.percentile(0.5), Duration::from_millis(50));
+        assert_eq!(stats.percentile(0.99), Duration::from_millis(99));
+        assert_eq!(stats.percentile(1.0), Duration::from_millis(100));
+        assert_eq!(Stats::default().percentile(0.5), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_max() {
+        let mut stats = Stats::default();
+        stats.record(Duration::from_secs(10), false);
+        for _ in 0..MAX_SAMPLES {
+            stats.record(Duration::from_millis(1), false);
+        }
+        // The slowest duration was replaced in the samples, but not in the max
+        assert_eq!(stats.percentile(1.0), Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,450 @@
+//! Write spans to rotating local files instead of sending them to Axiom, and
+//! ship them later with [`replay`], e.g. from a host with network access.
+//!
+//! Spans are written as one JSON object per line in the format of
+//! [`Builder::with_stdout_exporter`](crate::Builder::with_stdout_exporter).
+//!
+//! ```rust,no_run
+//! use tracing_axiom::file::FileExporter;
+//! use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _, Registry};
+//!
+//! # async fn capture() -> Result<(), Box<dyn std::error::Error>> {
+//! let exporter = FileExporter::new("spans.ndjson")?.with_max_file_size(64 << 20);
+//! let axiom_layer = tracing_axiom::builder("air-gapped")
+//!     .with_exporter(exporter)
+//!     .build()?;
+//! Registry::default().with(axiom_layer).try_init()?;
+//! # Ok(())
+//! # }
+//!
+//! # async fn upload() -> Result<(), tracing_axiom::Error> {
+//! // Later, with `AXIOM_TOKEN` and `AXIOM_DATASET` set
+//! let count = tracing_axiom::file::replay("spans.ndjson").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{stdout::to_json, Builder, Error};
+use opentelemetry::{
+    trace::{
+        Event, Link, SpanContext, SpanId, SpanKind, Status, TraceError, TraceFlags, TraceId,
+        TraceState,
+    },
+    Array, KeyValue, StringValue, Value,
+};
+use opentelemetry_sdk::{
+    export::trace::{ExportResult, SpanData, SpanExporter},
+    trace::{SpanEvents, SpanLinks},
+    InstrumentationLibrary, Resource,
+};
+use serde_json::{Map, Value as Json};
+use std::{
+    borrow::Cow,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::{self, BufRead as _, BufReader, BufWriter, Write as _},
+    path::{Path, PathBuf},
+    pin::Pin,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The default size after which the file is rotated.
+const MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
+/// The default number of rotated files that are kept.
+const MAX_FILES: usize = 5;
+/// The number of spans sent to Axiom per request when replaying.
+const REPLAY_BATCH_SIZE: usize = 512;
+
+/// An exporter that appends spans to a file as newline delimited JSON, see
+/// [`file`](crate::file). Set it with
+/// [`Builder::with_exporter`](crate::Builder::with_exporter).
+///
+/// When the file grows beyond the maximum size it is renamed to `<path>.1`,
+/// a previous `<path>.1` to `<path>.2` and so on, and a new file is started.
+#[derive(Debug)]
+pub struct FileExporter {
+    path: PathBuf,
+    max_file_size: u64,
+    max_files: usize,
+    writer: BufWriter<File>,
+    size: u64,
+}
+
+impl FileExporter {
+    /// Append spans to the file at `path`, which is created if it doesn't
+    /// exist. Rotates the file at 100 MiB and keeps 5 rotated files by default.
+    ///
+    /// # Errors
+    /// If the file can't be opened for writing.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let (writer, size) = open(&path).map_err(Error::File)?;
+        Ok(Self {
+            path,
+            max_file_size: MAX_FILE_SIZE,
+            max_files: MAX_FILES,
+            writer,
+            size,
+        })
+    }
+
+    /// Rotate the file once it would grow beyond `bytes`.
+    #[must_use]
+    pub fn with_max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// Keep at most `count` rotated files, older ones are deleted. With `0`
+    /// the file is deleted instead of rotated.
+    #[must_use]
+    pub fn with_max_files(mut self, count: usize) -> Self {
+        self.max_files = count;
+        self
+    }
+
+    fn write(&mut self, batch: &[SpanData]) -> io::Result<()> {
+        for span in batch {
+            let mut line = to_json(span).to_string();
+            line.push('\n');
+            if self.size > 0 && self.size + line.len() as u64 > self.max_file_size {
+                self.rotate()?;
+            }
+            self.writer.write_all(line.as_bytes())?;
+            self.size += line.len() as u64;
+        }
+        self.writer.flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            remove_if_exists(&rotated(&self.path, self.max_files))?;
+            for n in (1..self.max_files).rev() {
+                let from = rotated(&self.path, n);
+                if from.exists() {
+                    fs::rename(from, rotated(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated(&self.path, 1))?;
+        }
+        (self.writer, self.size) = open(&self.path)?;
+        Ok(())
+    }
+}
+
+impl SpanExporter for FileExporter {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        let result = self
+            .write(&batch)
+            .map_err(|e| TraceError::Other(Box::new(e)));
+        Box::pin(std::future::ready(result))
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+fn open(path: &Path) -> io::Result<(BufWriter<File>, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((BufWriter::new(file), size))
+}
+
+/// The path of the `n`th rotated file, e.g. `spans.ndjson.1`.
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut rotated = OsString::from(path);
+    rotated.push(format!(".{n}"));
+    rotated.into()
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Send the spans of a file written by a [`FileExporter`] to Axiom, configured
+/// from the environment like [`builder_with_env`](crate::builder_with_env).
+/// Returns the number of spans sent.
+///
+/// Rotated files have to be replayed one by one, the oldest first. Must be
+/// called within a tokio runtime.
+///
+/// # Errors
+/// If the configuration is invalid, the file can't be read or contains an
+/// invalid line, or sending a batch fails. Batches sent before the error are
+/// not sent again.
+pub async fn replay(path: impl AsRef<Path>) -> Result<usize, Error> {
+    replay_with(Builder::default().with_env()?, path).await
+}
+
+/// Like [`replay`], but with the token, dataset, URL, redaction and exporter
+/// settings of the builder.
+///
+/// # Errors
+/// If the builder is invalid, the file can't be read or contains an invalid
+/// line, or sending a batch fails.
+pub async fn replay_with(mut builder: Builder, path: impl AsRef<Path>) -> Result<usize, Error> {
+    let reader = BufReader::new(File::open(path).map_err(Error::File)?);
+    let mut exporter = builder.export_pipeline()?;
+    let mut batch = Vec::with_capacity(REPLAY_BATCH_SIZE);
+    let mut count = 0;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(Error::File)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        batch.push(from_json(&line).map_err(|reason| Error::InvalidSpan(i + 1, reason))?);
+        if batch.len() == REPLAY_BATCH_SIZE {
+            count += batch.len();
+            exporter.export(std::mem::take(&mut batch)).await?;
+        }
+    }
+    if !batch.is_empty() {
+        count += batch.len();
+        exporter.export(batch).await?;
+    }
+    exporter.shutdown();
+    Ok(count)
+}
+
+/// Parses a span written by [`to_json`]. Only spans that were sampled are
+/// exported, so all of them are sampled again.
+fn from_json(line: &str) -> Result<SpanData, String> {
+    let json: Json = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let status = match str_field(&json["status"], "code")? {
+        "UNSET" => Status::Unset,
+        "OK" => Status::Ok,
+        "ERROR" => Status::error(
+            json["status"]["message"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        ),
+        code => return Err(format!("unknown status code {code:?}")),
+    };
+    let span_kind = match str_field(&json, "kind")? {
+        "client" => SpanKind::Client,
+        "server" => SpanKind::Server,
+        "producer" => SpanKind::Producer,
+        "consumer" => SpanKind::Consumer,
+        "internal" => SpanKind::Internal,
+        kind => return Err(format!("unknown span kind {kind:?}")),
+    };
+    let mut events = SpanEvents::default();
+    for event in array_field(&json, "events")? {
+        events.events.push(Event::new(
+            str_field(event, "name")?.to_string(),
+            time_field(event, "time_unix_nano")?,
+            attributes(&event["attributes"]),
+            0,
+        ));
+    }
+    let mut links = SpanLinks::default();
+    for link in array_field(&json, "links")? {
+        links.links.push(Link::new(
+            span_context(link)?,
+            attributes(&link["attributes"]),
+        ));
+    }
+    let scope = &json["scope"];
+    Ok(SpanData {
+        span_context: span_context(&json)?,
+        parent_span_id: SpanId::from_hex(str_field(&json, "parent_span_id")?)
+            .map_err(|e| e.to_string())?,
+        span_kind,
+        name: str_field(&json, "name")?.to_string().into(),
+        start_time: time_field(&json, "start_time_unix_nano")?,
+        end_time: time_field(&json, "end_time_unix_nano")?,
+        attributes: attributes(&json["attributes"]),
+        dropped_attributes_count: 0,
+        events,
+        links,
+        status,
+        resource: Cow::Owned(Resource::new(attributes(&json["resource"]))),
+        instrumentation_lib: InstrumentationLibrary::new(
+            str_field(scope, "name")?.to_string(),
+            scope["version"].as_str().map(str::to_string),
+            None::<&'static str>,
+            None,
+        ),
+    })
+}
+
+fn span_context(json: &Json) -> Result<SpanContext, String> {
+    let trace_id = TraceId::from_hex(str_field(json, "trace_id")?).map_err(|e| e.to_string())?;
+    let span_id = SpanId::from_hex(str_field(json, "span_id")?).map_err(|e| e.to_string())?;
+    Ok(SpanContext::new(
+        trace_id,
+        span_id,
+        TraceFlags::SAMPLED,
+        false,
+        TraceState::default(),
+    ))
+}
+
+fn str_field<'a>(json: &'a Json, key: &str) -> Result<&'a str, String> {
+    json[key]
+        .as_str()
+        .ok_or_else(|| format!("{key} is missing or not a string"))
+}
+
+fn array_field<'a>(json: &'a Json, key: &str) -> Result<&'a [Json], String> {
+    match &json[key] {
+        Json::Null => Ok(&[]),
+        Json::Array(values) => Ok(values),
+        _ => Err(format!("{key} is not an array")),
+    }
+}
+
+fn time_field(json: &Json, key: &str) -> Result<SystemTime, String> {
+    json[key]
+        .as_u64()
+        .map(|nanos| UNIX_EPOCH + Duration::from_nanos(nanos))
+        .ok_or_else(|| format!("{key} is missing or not a timestamp"))
+}
+
+/// The attributes of a JSON object, values that aren't attribute values like
+/// `null` or nested objects are skipped.
+fn attributes(json: &Json) -> Vec<KeyValue> {
+    json.as_object()
+        .map(Map::iter)
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, json)| Some(KeyValue::new(key.clone(), value(json)?)))
+        .collect()
+}
+
+fn value(json: &Json) -> Option<Value> {
+    match json {
+        Json::Bool(b) => Some(Value::Bool(*b)),
+        Json::Number(n) => n.as_i64().map(Value::I64).or(n.as_f64().map(Value::F64)),
+        Json::String(s) => Some(Value::from(s.clone())),
+        Json::Array(values) => Some(Value::Array(match values.first() {
+            Some(Json::Bool(_)) => Array::Bool(values.iter().filter_map(Json::as_bool).collect()),
+            Some(Json::Number(n)) if n.is_i64() => {
+                Array::I64(values.iter().filter_map(Json::as_i64).collect())
+            }
+            Some(Json::Number(_)) => Array::F64(values.iter().filter_map(Json::as_f64).collect()),
+            _ => Array::String(
+                values
+                    .iter()
+                    .filter_map(Json::as_str)
+                    .map(|s| StringValue::from(s.to_string()))
+                    .collect(),
+            ),
+        })),
+        Json::Null | Json::Object(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{attribute, Recording};
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tracing-axiom-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("temp dir");
+        dir
+    }
+
+    fn recorded_spans() -> Vec<SpanData> {
+        let (layer, spans) = crate::builder("file").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("outer", otel.kind = "server", ratio = 0.5);
+            outer.in_scope(|| {
+                tracing::info_span!("inner", user.id = 42, ok = true).in_scope(|| {
+                    tracing::error!(retries = 3, "failed");
+                });
+            });
+        });
+        spans.finished_spans()
+    }
+
+    #[test]
+    fn test_json_roundtrip() -> Result<(), String> {
+        for span in recorded_spans() {
+            let parsed = from_json(&to_json(&span).to_string())?;
+            assert_eq!(parsed.span_context, span.span_context);
+            assert_eq!(parsed.parent_span_id, span.parent_span_id);
+            assert_eq!(parsed.span_kind, span.span_kind);
+            assert_eq!(parsed.name, span.name);
+            assert_eq!(parsed.status, span.status);
+            assert_eq!(parsed.events.len(), span.events.len());
+            assert_eq!(parsed.resource.len(), span.resource.len());
+            assert_eq!(parsed.instrumentation_lib, span.instrumentation_lib);
+            for kv in &span.attributes {
+                assert_eq!(attribute(&parsed, kv.key.as_str()), Some(&kv.value));
+            }
+            assert_eq!(parsed.start_time, span.start_time);
+            assert_eq!(parsed.end_time, span.end_time);
+        }
+        assert!(from_json("{}").is_err());
+        assert!(from_json("not json").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotation() -> Result<(), Error> {
+        let dir = temp_dir("rotation");
+        let path = dir.join("spans.ndjson");
+        let spans = recorded_spans();
+        let line_len = to_json(&spans[0]).to_string().len() as u64 + 1;
+        let mut exporter = FileExporter::new(&path)?
+            .with_max_file_size(line_len)
+            .with_max_files(2);
+        for _ in 0..4 {
+            exporter.write(&spans[..1]).map_err(Error::File)?;
+        }
+
+        let lines = |path: &Path| fs::read_to_string(path).map_or(0, |s| s.lines().count());
+        assert_eq!(lines(&path), 1);
+        assert_eq!(lines(&rotated(&path, 1)), 1);
+        assert_eq!(lines(&rotated(&path, 2)), 1);
+        assert!(!rotated(&path, 3).exists());
+
+        // Appends to an existing file
+        let mut exporter = FileExporter::new(&path)?;
+        exporter.write(&spans).map_err(Error::File)?;
+        assert_eq!(lines(&path), 1 + spans.len());
+
+        fs::remove_dir_all(dir).map_err(Error::File)
+    }
+
+    #[tokio::test]
+    async fn test_replay() -> Result<(), Error> {
+        let dir = temp_dir("replay");
+        let path = dir.join("spans.ndjson");
+        let spans = recorded_spans();
+        let mut exporter = FileExporter::new(&path)?;
+        exporter.write(&spans).map_err(Error::File)?;
+
+        let replayed = Recording::default();
+        let builder = crate::builder("replay").with_exporter(replayed.clone());
+        assert_eq!(replay_with(builder, &path).await?, spans.len());
+        let names = |spans: &[SpanData]| spans.iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&replayed.spans()), names(&spans));
+
+        fs::write(&path, "{}\n").map_err(Error::File)?;
+        let builder = crate::builder("replay").with_exporter(replayed);
+        assert!(matches!(
+            replay_with(builder, &path).await,
+            Err(Error::InvalidSpan(1, _))
+        ));
+
+        fs::remove_dir_all(dir).map_err(Error::File)
+    }
+}
@@ -0,0 +1,289 @@
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    subscriber::Interest,
+    Metadata,
+};
+use tracing_subscriber::{
+    filter::{EnvFilter, LevelFilter},
+    layer::{Context, Filter},
+};
+
+/// The per-layer filter that decides which spans and events are sent to Axiom,
+/// see [`Builder::with_filter`](crate::Builder::with_filter).
+///
+/// Created from an [`EnvFilter`] or a [`LevelFilter`]. By default everything is
+/// sent. Targets included or excluded with
+/// [`Builder::with_include_targets`](crate::Builder::with_include_targets) and
+/// [`Builder::with_exclude_targets`](crate::Builder::with_exclude_targets) are
+/// checked in addition.
+#[derive(Debug, Default)]
+pub struct AxiomFilter {
+    inner: Inner,
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+enum Inner {
+    #[default]
+    All,
+    Env(Box<EnvFilter>),
+    Level(LevelFilter),
+    /// Swapped at runtime, see [`ReloadHandle`](crate::ReloadHandle).
+    Reload(SharedFilter),
+}
+
+/// The filter shared with a [`ReloadHandle`](crate::ReloadHandle).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SharedFilter(Arc<RwLock<Inner>>);
+
+impl SharedFilter {
+    /// Replaces the level or env filter, the targets of the filter that was
+    /// built still apply.
+    pub(crate) fn set(&self, filter: AxiomFilter) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = filter.inner;
+        // Callsites cache whether they are enabled, re-evaluate them
+        tracing_core::callsite::rebuild_interest_cache();
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Inner> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl AxiomFilter {
+    pub(crate) fn is_default(&self) -> bool {
+        matches!(self.inner, Inner::All)
+    }
+
+    /// Replaces the level or env filter, keeping the targets.
+    pub(crate) fn set(&mut self, filter: AxiomFilter) {
+        self.inner = filter.inner;
+    }
+
+    /// Moves the level or env filter into the shared one, so it can be
+    /// replaced after the layer was built.
+    pub(crate) fn share(mut self, shared: &SharedFilter) -> Self {
+        let inner = std::mem::replace(&mut self.inner, Inner::Reload(shared.clone()));
+        shared.set(AxiomFilter {
+            inner,
+            ..AxiomFilter::default()
+        });
+        self
+    }
+
+    pub(crate) fn include_target(&mut self, target: String) {
+        self.include.push(target);
+    }
+
+    pub(crate) fn exclude_target(&mut self, target: String) {
+        self.exclude.push(target);
+    }
+
+    fn target_enabled(&self, target: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|t| matches_target(t, target)))
+            && !self.exclude.iter().any(|t| matches_target(t, target))
+    }
+}
+
+/// Whether `target` is `prefix` or one of its submodules.
+pub(crate) fn matches_target(prefix: &str, target: &str) -> bool {
+    target
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+impl From<EnvFilter> for AxiomFilter {
+    fn from(filter: EnvFilter) -> Self {
+        Self {
+            inner: Inner::Env(Box::new(filter)),
+            ..Self::default()
+        }
+    }
+}
+
+impl From<LevelFilter> for AxiomFilter {
+    fn from(filter: LevelFilter) -> Self {
+        Self {
+            inner: Inner::Level(filter),
+            ..Self::default()
+        }
+    }
+}
+
+impl<S> Filter<S> for AxiomFilter {
+    fn enabled(&self, meta: &Metadata<'_>, ctx: &Context<'_, S>) -> bool {
+        self.target_enabled(meta.target()) && Filter::<S>::enabled(&self.inner, meta, ctx)
+    }
+
+    fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> Interest {
+        if !self.target_enabled(meta.target()) {
+            return Interest::never();
+        }
+        Filter::<S>::callsite_enabled(&self.inner, meta)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Filter::<S>::max_level_hint(&self.inner)
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        Filter::<S>::on_new_span(&self.inner, attrs, id, ctx);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        Filter::<S>::on_record(&self.inner, id, values, ctx);
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        Filter::<S>::on_enter(&self.inner, id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        Filter::<S>::on_exit(&self.inner, id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        Filter::<S>::on_close(&self.inner, id, ctx);
+    }
+}
+
+impl<S> Filter<S> for Inner {
+    fn enabled(&self, meta: &Metadata<'_>, ctx: &Context<'_, S>) -> bool {
+        match self {
+            Inner::All => true,
+            Inner::Env(filter) => Filter::<S>::enabled(&**filter, meta, ctx),
+            Inner::Level(filter) => Filter::<S>::enabled(filter, meta, ctx),
+            Inner::Reload(shared) => Filter::<S>::enabled(&*shared.read(), meta, ctx),
+        }
+    }
+
+    fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> Interest {
+        match self {
+            Inner::All => Interest::always(),
+            Inner::Env(filter) => Filter::<S>::callsite_enabled(&**filter, meta),
+            Inner::Level(filter) => Filter::<S>::callsite_enabled(filter, meta),
+            Inner::Reload(shared) => Filter::<S>::callsite_enabled(&*shared.read(), meta),
+        }
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        match self {
+            Inner::All => None,
+            Inner::Env(filter) => Filter::<S>::max_level_hint(&**filter),
+            Inner::Level(filter) => Some(*filter),
+            Inner::Reload(shared) => Filter::<S>::max_level_hint(&*shared.read()),
+        }
+    }
+
+    // Only the `EnvFilter` tracks spans, for span based directives.
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        match self {
+            Inner::Env(filter) => Filter::<S>::on_new_span(&**filter, attrs, id, ctx),
+            Inner::Reload(shared) => Filter::<S>::on_new_span(&*shared.read(), attrs, id, ctx),
+            Inner::All | Inner::Level(_) => {}
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        match self {
+            Inner::Env(filter) => Filter::<S>::on_record(&**filter, id, values, ctx),
+            Inner::Reload(shared) => Filter::<S>::on_record(&*shared.read(), id, values, ctx),
+            Inner::All | Inner::Level(_) => {}
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        match self {
+            Inner::Env(filter) => Filter::<S>::on_enter(&**filter, id, ctx),
+            Inner::Reload(shared) => Filter::<S>::on_enter(&*shared.read(), id, ctx),
+            Inner::All | Inner::Level(_) => {}
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        match self {
+            Inner::Env(filter) => Filter::<S>::on_exit(&**filter, id, ctx),
+            Inner::Reload(shared) => Filter::<S>::on_exit(&*shared.read(), id, ctx),
+            Inner::All | Inner::Level(_) => {}
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        match self {
+            Inner::Env(filter) => Filter::<S>::on_close(&**filter, id, ctx),
+            Inner::Reload(shared) => Filter::<S>::on_close(&*shared.read(), id, ctx),
+            Inner::All | Inner::Level(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt as _, EnvFilter};
+
+    fn sent_spans(builder: crate::Builder) -> Vec<String> {
+        let (layer, spans) = builder.build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("info").in_scope(|| {});
+            tracing::debug_span!("debug").in_scope(|| {});
+            tracing::info_span!(target: "noisy", "noisy").in_scope(|| {});
+            tracing::info_span!(target: "noisy::inner", "noisy::inner").in_scope(|| {});
+            tracing::info_span!(target: "noisy_neighbor", "noisy_neighbor").in_scope(|| {});
+        });
+        spans
+            .finished_spans()
+            .into_iter()
+            .map(|span| span.name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_no_filter() {
+        assert_eq!(
+            sent_spans(crate::builder("filter")),
+            vec!["info", "debug", "noisy", "noisy::inner", "noisy_neighbor"]
+        );
+    }
+
+    #[test]
+    fn test_level_filter() {
+        let builder = crate::builder("filter").with_filter(LevelFilter::INFO);
+        assert_eq!(
+            sent_spans(builder),
+            vec!["info", "noisy", "noisy::inner", "noisy_neighbor"]
+        );
+    }
+
+    #[test]
+    fn test_max_level() {
+        let builder = crate::builder("filter").with_max_level(tracing::Level::INFO);
+        assert_eq!(
+            sent_spans(builder),
+            vec!["info", "noisy", "noisy::inner", "noisy_neighbor"]
+        );
+    }
+
+    #[test]
+    fn test_env_filter() {
+        let builder = crate::builder("filter").with_filter(EnvFilter::new("debug,noisy=off"));
+        assert_eq!(sent_spans(builder), vec!["info", "debug"]);
+    }
+
+    #[test]
+    fn test_include_targets() {
+        let builder = crate::builder("filter").with_include_targets(["noisy"]);
+        assert_eq!(sent_spans(builder), vec!["noisy", "noisy::inner"]);
+    }
+
+    #[test]
+    fn test_exclude_targets() {
+        let builder = crate::builder("filter")
+            .with_exclude_targets(["noisy"])
+            .with_max_level(tracing::Level::INFO);
+        assert_eq!(sent_spans(builder), vec!["info", "noisy_neighbor"]);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::Error;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ShutdownError;
+#[cfg(not(target_arch = "wasm32"))]
+use opentelemetry::trace::TraceResult;
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{sync::mpsc, time::Duration};
+
+/// The tracer of the most recently built layer. A tracer only holds a weak
+/// reference to its provider, so this does not keep the provider alive after
+/// it was shut down.
+static TRACER: Mutex<Option<Tracer>> = Mutex::new(None);
+
+/// The meter providers of the most recently built layer, flushed and shut
+/// down together with its tracer provider.
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+static METER_PROVIDERS: Mutex<Vec<SdkMeterProvider>> = Mutex::new(Vec::new());
+
+pub(crate) fn register(tracer: &Tracer) {
+    if let Ok(mut registered) = TRACER.lock() {
+        *registered = Some(tracer.clone());
+    }
+}
+
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+pub(crate) fn register_meter_providers(providers: Vec<SdkMeterProvider>) {
+    if let Ok(mut registered) = METER_PROVIDERS.lock() {
+        *registered = providers;
+    }
+}
+
+/// Exports all spans that have ended but were not exported yet and waits for the
+/// export to finish. The metrics of the layer are exported as well.
+///
+/// This is required at the end of every invocation in serverless environments,
+/// see [`Builder::serverless`](crate::Builder::serverless).
+///
+/// # Errors
+///
+/// Errors if exporting any of the spans or metrics failed.
+pub fn force_flush() -> Result<(), Error> {
+    if let Some(provider) = registered_provider() {
+        for result in provider.force_flush() {
+            result?;
+        }
+    }
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    for provider in METER_PROVIDERS
+        .lock()
+        .map(|providers| providers.clone())
+        .unwrap_or_default()
+    {
+        provider.force_flush()?;
+    }
+    Ok(())
+}
+
+/// Exports all spans that have ended but were not exported yet and shuts the
+/// global tracer provider down, waiting at most for the timeout. Spans that
+/// end afterwards are not recorded anymore, and neither are metrics, whose
+/// meter providers are shut down as well. Meant to be called at the end of
+/// `main` or on `SIGTERM`, e.g. within the grace period of Kubernetes, to know
+/// whether all spans made it out.
+///
+/// Must not be called on the thread of a current-thread tokio runtime that
+/// drives the export, which could not make progress while this waits.
+///
+/// # Errors
+///
+/// If the export did not finish before the timeout, or exporting any of the
+/// spans or metrics failed.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn shutdown_with_timeout(timeout: Duration) -> Result<(), ShutdownError> {
+    let provider = registered_provider();
+    #[cfg(feature = "metrics")]
+    let meter_providers = METER_PROVIDERS
+        .lock()
+        .map(|mut providers| std::mem::take(&mut *providers))
+        .unwrap_or_default();
+    // The provider is kept alive until the export finished
+    opentelemetry::global::shutdown_tracer_provider();
+    within(timeout, move || {
+        if let Some(provider) = provider {
+            shutdown(provider)?;
+        }
+        #[cfg(feature = "metrics")]
+        for provider in meter_providers {
+            provider.shutdown()?;
+        }
+        Ok(())
+    })
+}
+
+/// How long [`flush_on_shutdown`] waits for the export after a signal.
+#[cfg(all(feature = "signal", not(target_arch = "wasm32")))]
+const SIGNAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Exports the remaining spans when the process receives `SIGTERM` or
+/// `SIGINT` (Ctrl-C on Windows), then exits it with the usual exit code of
+/// the signal. The export is given 5 seconds, well within the grace period of
+/// Kubernetes. Call it once after building the layer, within a tokio runtime:
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
+///
+/// let axiom_layer = tracing_axiom::default("my-service")?;
+/// tracing_subscriber::registry().with(axiom_layer).init();
+/// tracing_axiom::flush_on_shutdown()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The signals don't terminate the process by themselves anymore afterwards,
+/// applications with their own graceful shutdown should call
+/// [`shutdown_with_timeout`] at its end instead.
+///
+/// # Errors
+///
+/// If the signal handlers could not be registered.
+///
+/// # Panics
+///
+/// If called outside of a tokio runtime.
+#[cfg(all(feature = "signal", not(target_arch = "wasm32")))]
+pub fn flush_on_shutdown() -> Result<(), Error> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        for (kind, code) in [
+            (SignalKind::terminate(), 143),
+            (SignalKind::interrupt(), 130),
+        ] {
+            let mut signal = signal(kind).map_err(Error::Signal)?;
+            drop(tokio::spawn(async move {
+                if signal.recv().await.is_some() {
+                    flush_and_exit(code).await;
+                }
+            }));
+        }
+    }
+    #[cfg(not(unix))]
+    drop(tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            flush_and_exit(130).await;
+        }
+    }));
+    Ok(())
+}
+
+/// Shuts the tracer provider down on the blocking thread pool, so a
+/// current-thread runtime keeps driving the export meanwhile.
+#[cfg(all(feature = "signal", not(target_arch = "wasm32")))]
+async fn flush_and_exit(code: i32) {
+    let result = tokio::task::spawn_blocking(|| shutdown_with_timeout(SIGNAL_TIMEOUT)).await;
+    if let Ok(Err(error)) = result {
+        crate::diagnostics::report(tracing_core::Level::ERROR, &error.to_string());
+    }
+    std::process::exit(code);
+}
+
+fn registered_provider() -> Option<TracerProvider> {
+    TRACER
+        .lock()
+        .ok()
+        .and_then(|tracer| tracer.as_ref().and_then(Tracer::provider))
+}
+
+/// Exports the remaining spans of the provider and shuts it down.
+#[cfg(not(target_arch = "wasm32"))]
+fn shutdown(provider: TracerProvider) -> TraceResult<()> {
+    let result = provider.force_flush().into_iter().collect();
+    // Shuts the span processors down, unless the application holds on to
+    // the provider as well
+    drop(provider);
+    result
+}
+
+/// Runs the shutdown on another thread and waits for it at most for the
+/// timeout.
+#[cfg(not(target_arch = "wasm32"))]
+fn within(
+    timeout: Duration,
+    shutdown: impl FnOnce() -> Result<(), ShutdownError> + Send + 'static,
+) -> Result<(), ShutdownError> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(shutdown());
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(_) => Err(ShutdownError::Timeout {
+            pending: crate::queue_stats().map_or(0, |stats| stats.len),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Span as _, Tracer as _, TracerProvider as _};
+    use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+    use std::{future::Future, pin::Pin};
+
+    /// Takes the duration to export a batch.
+    #[derive(Debug)]
+    struct Slow(Duration);
+
+    impl SpanExporter for Slow {
+        fn export(
+            &mut self,
+            _batch: Vec<SpanData>,
+        ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+            let duration = self.0;
+            Box::pin(async move {
+                tokio::time::sleep(duration).await;
+                Ok(())
+            })
+        }
+    }
+
+    fn provider(export_duration: Duration) -> TracerProvider {
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(Slow(export_duration), opentelemetry_sdk::runtime::Tokio)
+            .build();
+        provider.tracer("flush").start("span").end();
+        provider
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shutdown() {
+        let provider = provider(Duration::ZERO);
+        assert!(within(Duration::from_secs(5), move || Ok(shutdown(provider)?)).is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shutdown_timeout() {
+        let provider = provider(Duration::from_secs(5));
+        assert!(matches!(
+            within(Duration::from_millis(50), move || Ok(shutdown(provider)?)),
+            Err(ShutdownError::Timeout { .. })
+        ));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{queue_stats, QueueStats};
+use opentelemetry_sdk::export::trace::ExportResult;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::SystemTime,
+};
+
+/// A snapshot of the health of the export to Axiom, see [`health`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Health {
+    /// When spans were last exported successfully, `None` if they never were.
+    pub last_export: Option<SystemTime>,
+    /// The number of exports that failed since the last successful one.
+    pub consecutive_failures: u64,
+    /// The export queue, `None` if spans are not exported in batches.
+    pub queue: Option<QueueStats>,
+}
+
+impl Health {
+    /// Whether the last export succeeded, or nothing was exported yet.
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.consecutive_failures == 0
+    }
+}
+
+/// The health of the export of the most recently built layer, to include it
+/// in the readiness or liveness endpoint of a service, e.g. `/healthz`.
+///
+/// ```rust
+/// let health = tracing_axiom::health();
+/// if !health.is_healthy() {
+///     eprintln!("{} exports to Axiom failed", health.consecutive_failures);
+/// }
+/// ```
+#[must_use]
+pub fn health() -> Health {
+    let export = EXPORT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_default();
+    export.snapshot(queue_stats())
+}
+
+/// The export results of the most recently built layer.
+static EXPORT: Mutex<Option<Arc<ExportHealth>>> = Mutex::new(None);
+
+#[derive(Debug, Default)]
+pub(crate) struct ExportHealth {
+    last_export: Mutex<Option<SystemTime>>,
+    consecutive_failures: AtomicU64,
+}
+
+impl ExportHealth {
+    pub(crate) fn register(self: &Arc<Self>) {
+        *EXPORT.lock().unwrap_or_else(PoisonError::into_inner) = Some(self.clone());
+    }
+
+    /// The health of these exports, with the stats of their queue.
+    pub(crate) fn snapshot(&self, queue: Option<QueueStats>) -> Health {
+        Health {
+            last_export: self.last_export(),
+            consecutive_failures: self.consecutive_failures(),
+            queue,
+        }
+    }
+
+    fn last_export(&self) -> Option<SystemTime> {
+        *self
+            .last_export
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn record(&self, result: &ExportResult) {
+        if result.is_ok() {
+            *self
+                .last_export
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(opentelemetry::time::now());
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+        } else {
+            self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn consecutive_failures(&self) -> u64 {
+        self.consecutive_failures.load(Ordering::Relaxed)
+    }
+}
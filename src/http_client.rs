@@ -0,0 +1,101 @@
+//! [`reqwest-middleware`](https://docs.rs/reqwest-middleware) middleware creating a
+//! client span for every outgoing HTTP request. Enable with the
+//! `reqwest-middleware` feature.
+//!
+//! ```rust
+//! use reqwest_middleware::ClientBuilder;
+//! use tracing_axiom::http_client::TracingMiddleware;
+//!
+//! let client = ClientBuilder::new(reqwest::Client::new())
+//!     .with(TracingMiddleware)
+//!     .build();
+//! ```
+
+use crate::{propagation::inject_context_from, spans};
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next, Result};
+use task_local_extensions::Extensions;
+use tracing::Instrument as _;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+/// Middleware that creates a client span for every request and injects its trace
+/// context into the request headers, so the receiving service continues the trace.
+///
+/// The span records the `http.request.method`, `url.full`, `server.address` and
+/// `http.response.status_code` attributes. Error responses (4xx and 5xx) and
+/// failed requests mark the span as failed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingMiddleware;
+
+#[async_trait::async_trait]
+impl Middleware for TracingMiddleware {
+    async fn handle(
+        &self,
+        mut request: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let span = spans::http_client_span(
+            request.method().as_str(),
+            request.url().as_str(),
+            request.url().host_str(),
+        );
+        inject_context_from(&span.context(), request.headers_mut());
+        let result = next.run(request, extensions).instrument(span.clone()).await;
+        match &result {
+            Ok(response) => spans::record_http_client_status(&span, response.status().as_u16()),
+            Err(error) => spans::record_error(&span, error),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::{global, trace::TracerProvider as _};
+    use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::TracerProvider};
+    use reqwest_middleware::ClientBuilder;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::{layer::SubscriberExt as _, Registry};
+
+    struct Capture(Arc<Mutex<Option<String>>>);
+
+    #[async_trait::async_trait]
+    impl Middleware for Capture {
+        async fn handle(
+            &self,
+            request: Request,
+            _extensions: &mut Extensions,
+            _next: Next<'_>,
+        ) -> Result<Response> {
+            *self.0.lock().expect("not poisoned") = request
+                .headers()
+                .get("traceparent")
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string);
+            Ok(http::Response::new("").into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_injects_trace_context() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = TracerProvider::builder().build();
+        let subscriber = Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let traceparent = Arc::new(Mutex::new(None));
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(TracingMiddleware)
+            .with(Capture(traceparent.clone()))
+            .build();
+        let response = client.get("http://localhost/").send().await?;
+        assert!(response.status().is_success());
+
+        let traceparent = traceparent.lock().expect("not poisoned").clone();
+        assert!(traceparent.is_some_and(|traceparent| traceparent.starts_with("00-")));
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{export::json_value, Error};
+use http::{
+    header::{CONTENT_TYPE, USER_AGENT},
+    Request,
+};
+use opentelemetry_http::HttpClient;
+use opentelemetry_sdk::Resource;
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Sends events to an Axiom dataset with the token, URL and HTTP client of
+/// the most recently built layer, e.g. business events that don't belong in a
+/// trace.
+///
+/// A value that serializes to a JSON array is sent as one event per element,
+/// any other value as a single event. The events are sent right away, not
+/// queued with the spans.
+///
+/// The resource attributes of the layer (service name, environment, tags) are
+/// added to every event that is a JSON object, unless it has a field with the
+/// same name, see
+/// [`Builder::with_ingest_resource_prefix`](crate::Builder::with_ingest_resource_prefix).
+///
+/// ```rust,no_run
+/// # async fn checkout() -> Result<(), tracing_axiom::Error> {
+/// #[derive(serde::Serialize)]
+/// struct Order {
+///     id: u64,
+///     total_cents: u64,
+/// }
+///
+/// tracing_axiom::ingest("orders", Order { id: 42, total_cents: 1999 }).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Unless a client was set with
+/// [`Builder::with_http_client`](crate::Builder::with_http_client), the
+/// future must be awaited within a tokio runtime.
+///
+/// # Errors
+///
+/// Errors if no layer sending to Axiom was built, because it is disabled, a
+/// dry run or has no token, if the events can't be serialized or if Axiom
+/// does not accept them.
+pub async fn ingest(dataset: &str, events: impl Serialize) -> Result<(), Error> {
+    let ingester = INGESTER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .ok_or_else(|| Error::Ingest("no layer sending to Axiom was built".to_string()))?;
+    ingester.send(dataset, &events).await
+}
+
+/// The ingester of the most recently built layer.
+static INGESTER: Mutex<Option<Arc<Ingester>>> = Mutex::new(None);
+
+/// Sends events to the ingest endpoint of a dataset, see [`ingest`].
+#[derive(Debug)]
+pub(crate) struct Ingester {
+    pub(crate) url: Url,
+    pub(crate) user_agent: String,
+    /// The client adding the token to requests.
+    pub(crate) client: Arc<dyn HttpClient>,
+    /// The fields added to every event.
+    pub(crate) resource: Map<String, Value>,
+}
+
+impl Ingester {
+    pub(crate) fn register(self) {
+        *INGESTER.lock().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(self));
+    }
+
+    /// The fields of the resource attributes, with the prefix prepended to
+    /// their keys.
+    pub(crate) fn resource_fields(resource: &Resource, prefix: &str) -> Map<String, Value> {
+        resource
+            .iter()
+            .map(|(key, value)| (format!("{prefix}{}", key.as_str()), json_value(value)))
+            .collect()
+    }
+
+    async fn send(&self, dataset: &str, events: &impl Serialize) -> Result<(), Error> {
+        let mut events = match serde_json::to_value(events) {
+            Ok(Value::Array(events)) => events,
+            Ok(event) => vec![event],
+            Err(e) => return Err(Error::Ingest(e.to_string())),
+        };
+        for event in &mut events {
+            if let Value::Object(fields) = event {
+                for (key, value) in &self.resource {
+                    fields.entry(key).or_insert_with(|| value.clone());
+                }
+            }
+        }
+        let body = serde_json::to_vec(&events).map_err(|e| Error::Ingest(e.to_string()))?;
+        let uri = self.ingest_url(dataset)?.to_string();
+        let request = Request::post(&uri)
+            .header(CONTENT_TYPE, "application/json")
+            .header(USER_AGENT, &self.user_agent)
+            .body(body)
+            .map_err(|_| Error::Ingest(format!("invalid dataset {dataset:?}")))?;
+        let response = self
+            .client
+            .send(request)
+            .await
+            .map_err(|e| Error::Ingest(format!("{uri}: {e}")))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Error::Ingest(format!(
+                "{uri} responded with {}",
+                response.status()
+            )))
+        }
+    }
+
+    /// The ingest endpoint of the dataset under the URL, with the name
+    /// percent-encoded so it stays one path segment.
+    fn ingest_url(&self, dataset: &str) -> Result<Url, Error> {
+        // Dot segments would be dropped from the path
+        if matches!(dataset, "" | "." | "..") {
+            return Err(Error::Ingest(format!("invalid dataset {dataset:?}")));
+        }
+        let mut url = self.url.clone();
+        url.path_segments_mut()
+            .map_err(|()| Error::Ingest(format!("{} can't have a path", self.url)))?
+            .pop_if_empty()
+            .extend(["v1", "datasets", dataset, "ingest"]);
+        Ok(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use http::Response;
+    use opentelemetry_http::HttpError;
+
+    /// The URI and body of every request.
+    #[derive(Debug, Clone, Default)]
+    struct Requests(Arc<Mutex<Vec<(String, String)>>>);
+
+    #[async_trait]
+    impl HttpClient for Requests {
+        async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+            let body = String::from_utf8(request.body().clone())?;
+            self.0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((request.uri().to_string(), body));
+            Ok(Response::new(Bytes::new()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ingest() -> Result<(), Error> {
+        let requests = Requests::default();
+        let ingester = Ingester {
+            url: "https://api.axiom.co/".parse()?,
+            user_agent: "tracing-axiom/test".to_string(),
+            client: Arc::new(requests.clone()),
+            resource: Map::new(),
+        };
+        ingester
+            .send("orders", &serde_json::json!({"id": 42}))
+            .await?;
+        ingester
+            .send("orders", &[serde_json::json!({"id": 43})])
+            .await?;
+        assert!(ingester.send("", &()).await.is_err());
+        assert!(ingester.send("..", &()).await.is_err());
+
+        let requests = requests.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let uri = "https://api.axiom.co/v1/datasets/orders/ingest";
+        assert_eq!(
+            *requests,
+            [
+                (uri.to_string(), r#"[{"id":42}]"#.to_string()),
+                (uri.to_string(), r#"[{"id":43}]"#.to_string())
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ingest_url() -> Result<(), Error> {
+        let ingester = Ingester {
+            url: "https://example.com/axiom/".parse()?,
+            user_agent: "tracing-axiom/test".to_string(),
+            client: Arc::new(Requests::default()),
+            resource: Map::new(),
+        };
+        assert_eq!(
+            ingester.ingest_url("orders")?.as_str(),
+            "https://example.com/axiom/v1/datasets/orders/ingest"
+        );
+        assert_eq!(
+            ingester.ingest_url("or ders/../x?y#z")?.as_str(),
+            "https://example.com/axiom/v1/datasets/or%20ders%2F..%2Fx%3Fy%23z/ingest"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ingest_resource() -> Result<(), Error> {
+        let resource = Resource::new([
+            opentelemetry::KeyValue::new("service.name", "checkout"),
+            opentelemetry::KeyValue::new("team", "payments"),
+        ]);
+        let requests = Requests::default();
+        let mut ingester = Ingester {
+            url: "https://api.axiom.co/".parse()?,
+            user_agent: "tracing-axiom/test".to_string(),
+            client: Arc::new(requests.clone()),
+            resource: Ingester::resource_fields(&resource, ""),
+        };
+        ingester
+            .send(
+                "orders",
+                &serde_json::json!([{"id": 42, "team": "sales"}, "paid"]),
+            )
+            .await?;
+        ingester.resource = Ingester::resource_fields(&resource, "resource.");
+        ingester
+            .send("orders", &serde_json::json!({"id": 43}))
+            .await?;
+
+        let requests = requests.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let bodies = requests
+            .iter()
+            .map(|(_, body)| serde_json::from_str::<Value>(body))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::Ingest(e.to_string()))?;
+        assert_eq!(
+            bodies,
+            [
+                serde_json::json!([
+                    {"id": 42, "team": "sales", "service.name": "checkout"},
+                    "paid"
+                ]),
+                serde_json::json!([
+                    {"id": 43, "resource.service.name": "checkout", "resource.team": "payments"}
+                ])
+            ]
+        );
+        Ok(())
+    }
+}
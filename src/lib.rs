@@ -36,21 +36,148 @@
 //! The example above gets the Axiom API token from the `AXIOM_TOKEN` env and
 //! the dataset name from `AXIOM_DATASET`. For more advanced configuration, see [`builder()`].
 
+#[cfg(all(feature = "actix-web", not(feature = "noop")))]
+pub mod actix;
+#[cfg(not(feature = "noop"))]
 mod builder;
+#[cfg(not(feature = "noop"))]
+mod collapse;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "noop")))]
+mod container;
+#[cfg(not(feature = "noop"))]
+mod diagnostics;
 mod error;
+#[cfg(not(feature = "noop"))]
+mod export;
+#[cfg(not(feature = "noop"))]
+mod fields;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "noop")))]
+pub mod file;
+#[cfg(not(feature = "noop"))]
+mod filter;
+#[cfg(not(feature = "noop"))]
+mod flush;
+#[cfg(not(feature = "noop"))]
+mod health;
+#[cfg(all(feature = "reqwest-middleware", not(feature = "noop")))]
+pub mod http_client;
+#[cfg(not(feature = "noop"))]
+mod ingest;
+#[cfg(all(feature = "prometheus", not(feature = "noop")))]
+mod metrics;
+#[cfg(not(feature = "noop"))]
+mod min_duration;
+#[cfg(feature = "noop")]
+mod noop;
+pub mod presets;
+#[cfg(not(feature = "noop"))]
+pub mod propagation;
+#[cfg(not(feature = "noop"))]
+mod queue;
+#[cfg(not(feature = "noop"))]
+pub mod redaction;
+#[cfg(not(feature = "noop"))]
+mod reload;
+#[cfg(not(feature = "noop"))]
+mod runtime;
+#[cfg(not(feature = "noop"))]
+mod sampling;
+pub mod semconv;
+#[cfg(all(
+    any(
+        feature = "tower",
+        feature = "actix-web",
+        feature = "reqwest-middleware"
+    ),
+    not(feature = "noop")
+))]
+mod spans;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "noop")))]
+mod stdout;
+#[cfg(all(
+    feature = "sysinfo",
+    not(target_arch = "wasm32"),
+    not(feature = "noop")
+))]
+mod system;
+#[cfg(not(feature = "noop"))]
+mod tail;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "noop")))]
+mod task;
+#[cfg(not(feature = "noop"))]
+pub mod testing;
+#[cfg(all(feature = "tonic", not(feature = "noop")))]
+pub mod tonic;
+#[cfg(all(feature = "tower", not(feature = "noop")))]
+pub mod tower;
+#[cfg(not(feature = "noop"))]
+mod trace_link;
+#[cfg(all(feature = "wasm", target_arch = "wasm32", not(feature = "noop")))]
+mod wasm;
 
-pub use builder::Builder;
-pub use error::Error;
-use opentelemetry_sdk::trace::Tracer;
+#[cfg(not(feature = "noop"))]
+use builder::AxiomLayer;
+#[cfg(not(feature = "noop"))]
+pub use builder::{Builder, ProviderGuard};
+#[cfg(not(feature = "noop"))]
+pub use diagnostics::InternalLog;
+pub use error::{Error, ShutdownError};
+#[cfg(not(feature = "noop"))]
+pub use export::{folded_fields, Encoding, ExportedEvent};
+#[cfg(all(feature = "valuable", not(feature = "noop")))]
+pub use fields::Flattening;
+#[cfg(not(feature = "noop"))]
+pub use fields::{scope, FieldsLayer, Scope};
+#[cfg(not(feature = "noop"))]
+pub use filter::AxiomFilter;
+#[cfg(all(feature = "signal", not(feature = "noop"), not(target_arch = "wasm32")))]
+pub use flush::flush_on_shutdown;
+#[cfg(not(feature = "noop"))]
+pub use flush::force_flush;
+#[cfg(all(not(feature = "noop"), not(target_arch = "wasm32")))]
+pub use flush::shutdown_with_timeout;
+#[cfg(not(feature = "noop"))]
+pub use health::{health, Health};
+#[cfg(not(feature = "noop"))]
+pub use ingest::ingest;
+#[cfg(feature = "noop")]
+use noop::AxiomLayer;
+#[cfg(feature = "noop")]
+pub use noop::{force_flush, scope, shutdown_with_timeout, Builder, NoopLayer, Scope};
+#[cfg(not(feature = "noop"))]
+pub use propagation::Propagation;
+#[cfg(not(feature = "noop"))]
+pub use queue::{dropped_spans, queue_stats, Backpressure, QueueStats};
+#[cfg(not(feature = "noop"))]
+pub use reload::ReloadHandle;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "noop")))]
+pub use task::spawn_instrumented;
+#[cfg(not(feature = "noop"))]
+pub use trace_link::{current_trace_id, trace_url};
 use tracing_core::Subscriber;
-use tracing_opentelemetry::OpenTelemetryLayer;
 use tracing_subscriber::registry::LookupSpan;
 
-#[cfg(doctest)]
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm"), not(feature = "noop")))]
+compile_error!("the `wasm` feature is required to build for wasm32");
+
+#[cfg(all(feature = "valuable", not(tracing_unstable)))]
+compile_error!("the `valuable` feature requires `RUSTFLAGS=\"--cfg tracing_unstable\"`");
+
+#[cfg(not(any(feature = "export", feature = "noop")))]
+compile_error!("either a TLS feature, the `export` feature or the `noop` feature is required");
+
+/// Used by the macros of this crate, not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use tracing;
+}
+
+#[cfg(all(doctest, not(feature = "noop")))]
 #[doc = include_str!("../README.md")]
 pub struct ReadmeDoctests;
 
-/// Creates a default [`OpenTelemetryLayer`] with a [`Tracer`] that sends traces to Axiom.
+/// Creates a default [`OpenTelemetryLayer`](tracing_opentelemetry::OpenTelemetryLayer) with a
+/// [`Tracer`](opentelemetry_sdk::trace::Tracer) that sends traces to Axiom.
 ///
 /// It uses the environment variables `AXIOM_TOKEN` and optionally `AXIOM_URL` and `AXIOM_DATASET`
 /// to configure the endpoint.
@@ -61,7 +188,7 @@ pub struct ReadmeDoctests;
 /// Errors if the initialization was unsuccessful, likely because a global
 /// subscriber was already installed or `AXIOM_TOKEN` and/or `AXIOM_DATASET`
 /// is not set or invalid.
-pub fn default<S>(service_name: &str) -> Result<OpenTelemetryLayer<S, Tracer>, Error>
+pub fn default<S>(service_name: &str) -> Result<AxiomLayer<S>, Error>
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
 {
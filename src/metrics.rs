@@ -0,0 +1,220 @@
+use crate::{
+    queue::{Counters, Queue},
+    Error,
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{Request, Response};
+use opentelemetry_http::{HttpClient, HttpError};
+use prometheus::{
+    core::{Collector, Desc},
+    proto::MetricFamily,
+    IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+};
+use std::sync::Arc;
+
+/// Metrics about the export registered with a [`Registry`], see
+/// [`Builder::with_prometheus_registry`](crate::Builder::with_prometheus_registry).
+#[derive(Debug, Clone)]
+pub(crate) struct ExportMetrics {
+    registry: Registry,
+    batches: IntCounter,
+    failures: IntCounterVec,
+    bytes: IntCounter,
+}
+
+impl ExportMetrics {
+    pub(crate) fn new(registry: &Registry) -> Self {
+        Self {
+            registry: registry.clone(),
+            batches: IntCounter::new(
+                "tracing_axiom_batches_sent_total",
+                "Batches of spans sent to Axiom successfully",
+            )
+            .expect("this is a valid metric"),
+            failures: IntCounterVec::new(
+                Opts::new(
+                    "tracing_axiom_export_failures_total",
+                    "Failed requests to Axiom by HTTP status code, or `error` without response",
+                ),
+                &["status"],
+            )
+            .expect("this is a valid metric"),
+            bytes: IntCounter::new(
+                "tracing_axiom_bytes_sent_total",
+                "Bytes of request bodies sent to Axiom",
+            )
+            .expect("this is a valid metric"),
+        }
+    }
+
+    /// Registers the metrics, and gauges of the queue that are read when the
+    /// registry is gathered.
+    pub(crate) fn register(&self, queue: &Queue) -> Result<(), Error> {
+        self.registry.register(Box::new(self.batches.clone()))?;
+        self.registry.register(Box::new(self.failures.clone()))?;
+        self.registry.register(Box::new(self.bytes.clone()))?;
+        self.registry
+            .register(Box::new(QueueCollector::new(queue.counters())))?;
+        Ok(())
+    }
+}
+
+/// Counts the requests sent by the wrapped client.
+#[derive(Debug)]
+pub(crate) struct MeteredClient {
+    pub(crate) client: Arc<dyn HttpClient>,
+    pub(crate) metrics: ExportMetrics,
+}
+
+#[async_trait]
+impl HttpClient for MeteredClient {
+    async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+        let len = u64::try_from(request.body().len()).unwrap_or(u64::MAX);
+        let result = self.client.send(request).await;
+        self.metrics.bytes.inc_by(len);
+        match &result {
+            Ok(response) if response.status().is_success() => self.metrics.batches.inc(),
+            Ok(response) => self
+                .metrics
+                .failures
+                .with_label_values(&[response.status().as_str()])
+                .inc(),
+            Err(_) => self.metrics.failures.with_label_values(&["error"]).inc(),
+        }
+        result
+    }
+}
+
+/// Reports the [`QueueStats`](crate::QueueStats) of a queue as gauges.
+struct QueueCollector {
+    counters: Arc<Counters>,
+    len: IntGauge,
+    capacity: IntGauge,
+    dropped: IntGauge,
+    descs: Vec<Desc>,
+}
+
+impl QueueCollector {
+    fn new(counters: Arc<Counters>) -> Self {
+        let gauge =
+            |name: &str, help: &str| IntGauge::new(name, help).expect("this is a valid metric");
+        let len = gauge(
+            "tracing_axiom_queue_spans",
+            "Spans waiting in the queue to be exported",
+        );
+        let capacity = gauge(
+            "tracing_axiom_queue_capacity",
+            "Spans the queue holds before spans are dropped",
+        );
+        let dropped = gauge(
+            "tracing_axiom_dropped_spans",
+            "Spans dropped because the queue was full",
+        );
+        let descs = [&len, &capacity, &dropped]
+            .iter()
+            .flat_map(|gauge| gauge.desc())
+            .cloned()
+            .collect();
+        Self {
+            counters,
+            len,
+            capacity,
+            dropped,
+            descs,
+        }
+    }
+}
+
+impl Collector for QueueCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.descs.iter().collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let stats = self.counters.stats();
+        self.len.set(i64::try_from(stats.len).unwrap_or(i64::MAX));
+        self.capacity
+            .set(i64::try_from(stats.capacity).unwrap_or(i64::MAX));
+        self.dropped
+            .set(i64::try_from(stats.dropped).unwrap_or(i64::MAX));
+        [&self.len, &self.capacity, &self.dropped]
+            .iter()
+            .flat_map(|gauge| gauge.collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Respond(http::StatusCode);
+
+    #[async_trait]
+    impl HttpClient for Respond {
+        async fn send(&self, _request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+            let mut response = Response::new(Bytes::new());
+            *response.status_mut() = self.0;
+            Ok(response)
+        }
+    }
+
+    fn value(registry: &Registry, name: &str, label: Option<&str>) -> Option<f64> {
+        let family = registry
+            .gather()
+            .into_iter()
+            .find(|family| family.get_name() == name)?;
+        let metric = family.get_metric().iter().find(|metric| {
+            label.map_or(true, |label| {
+                metric.get_label().iter().any(|l| l.get_value() == label)
+            })
+        })?;
+        Some(match family.get_field_type() {
+            prometheus::proto::MetricType::COUNTER => metric.get_counter().get_value(),
+            _ => metric.get_gauge().get_value(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_metrics() -> Result<(), Error> {
+        let registry = Registry::new();
+        let metrics = ExportMetrics::new(&registry);
+        metrics.register(&Queue::default())?;
+        assert!(metrics.register(&Queue::default()).is_err());
+
+        for status in [200, 200, 413] {
+            let client = MeteredClient {
+                client: Arc::new(Respond(
+                    http::StatusCode::from_u16(status).expect("valid status"),
+                )),
+                metrics: metrics.clone(),
+            };
+            let result = client.send(Request::new(vec![0; 10])).await;
+            assert!(result.is_ok());
+        }
+
+        assert_eq!(
+            value(&registry, "tracing_axiom_batches_sent_total", None),
+            Some(2.0)
+        );
+        assert_eq!(
+            value(
+                &registry,
+                "tracing_axiom_export_failures_total",
+                Some("413")
+            ),
+            Some(1.0)
+        );
+        assert_eq!(
+            value(&registry, "tracing_axiom_bytes_sent_total", None),
+            Some(30.0)
+        );
+        assert_eq!(
+            value(&registry, "tracing_axiom_queue_spans", None),
+            Some(0.0)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use crate::fields::is_start_record;
+use opentelemetry::{
+    trace::{Span as _, SpanId, Status, TraceContextExt as _, TraceResult},
+    Context,
+};
+use opentelemetry_sdk::{
+    export::trace::SpanData,
+    trace::{Span, SpanProcessor},
+};
+use std::{
+    collections::HashSet,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+/// Drops spans that took less than the minimum duration before they reach the
+/// processor that exports them, see
+/// [`Builder::with_min_span_duration`](crate::Builder::with_min_span_duration).
+///
+/// Spans are only dropped if that leaves the trace intact, so the local root
+/// spans, spans with children and failed spans are always kept.
+#[derive(Debug)]
+pub(crate) struct MinDuration {
+    processor: Box<dyn SpanProcessor>,
+    min_duration: Duration,
+    /// The running spans that are kept however short they are.
+    kept: Mutex<HashSet<SpanId>>,
+}
+
+impl MinDuration {
+    pub(crate) fn new(processor: Box<dyn SpanProcessor>, min_duration: Duration) -> Self {
+        Self {
+            processor,
+            min_duration,
+            kept: Mutex::default(),
+        }
+    }
+}
+
+impl SpanProcessor for MinDuration {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        let parent = cx.span();
+        let parent = parent.span_context();
+        let kept = if !parent.is_valid() || parent.is_remote() {
+            span.span_context().span_id()
+        } else {
+            parent.span_id()
+        };
+        self.kept
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(kept);
+        self.processor.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        // Start records end right away and would take the entry of their span
+        if is_start_record(&span) {
+            self.processor.on_end(span);
+            return;
+        }
+        let kept = self
+            .kept
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&span.span_context.span_id());
+        let short = span
+            .end_time
+            .duration_since(span.start_time)
+            .map_or(true, |duration| duration < self.min_duration);
+        if kept || !short || matches!(span.status, Status::Error { .. }) {
+            self.processor.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        self.processor.force_flush()
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        self.processor.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Recording;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_min_duration() {
+        let recording = Recording::default();
+        let filter = MinDuration::new(Box::new(recording.clone()), Duration::from_millis(50));
+        let (layer, _spans) = crate::builder("min_duration")
+            .with_span_processor(filter)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("root").in_scope(|| {
+                tracing::info_span!("cache get").in_scope(|| {});
+                tracing::info_span!("failed cache get").in_scope(|| tracing::error!("miss"));
+                tracing::info_span!("parent").in_scope(|| {
+                    tracing::info_span!("decode row").in_scope(|| {});
+                });
+                tracing::info_span!("query").in_scope(|| {
+                    std::thread::sleep(Duration::from_millis(60));
+                });
+            });
+        });
+
+        assert_eq!(
+            recording.names(),
+            ["failed cache get", "parent", "query", "root"]
+        );
+    }
+}
@@ -0,0 +1,515 @@
+//! Inert stand-ins for the [`Builder`] and the layer with the `noop` feature,
+//! so instrumented code compiles without any of the export dependencies.
+//!
+//! Only the builder methods that don't take OpenTelemetry types or types of the
+//! export API, like `Redaction`, are available, and of the build methods only
+//! `build`. They accept and ignore their arguments, and the layer does nothing.
+//!
+//! The feature is not additive: it removes API that other crates in the same
+//! build may use, so only enable it in the binary at the top of the dependency
+//! graph, never in a library.
+
+use crate::{Error, ShutdownError};
+use std::{borrow::Cow, marker::PhantomData, time::Duration};
+use tracing_core::Subscriber;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+pub(crate) type AxiomLayer<S> = NoopLayer<S>;
+
+/// A layer that ignores all spans and events.
+#[derive(Debug)]
+pub struct NoopLayer<S>(PhantomData<fn(S)>);
+
+impl<S> Layer<S> for NoopLayer<S> where S: Subscriber + for<'span> LookupSpan<'span> {}
+
+/// Builder for an inert layer, see the `noop` feature.
+#[derive(Debug, Default)]
+pub struct Builder {
+    _private: (),
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::unnecessary_wraps)]
+impl Builder {
+    /// Ignored with the `noop` feature.
+    ///
+    /// # Errors
+    /// Never with the `noop` feature.
+    pub fn with_dataset(self, _dataset_name: impl Into<String>) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    /// Ignored with the `noop` feature.
+    ///
+    /// # Errors
+    /// Never with the `noop` feature.
+    pub fn with_token(self, _token: impl Into<String>) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    /// Ignored with the `noop` feature.
+    ///
+    /// # Errors
+    /// Never with the `noop` feature.
+    pub fn with_url(self, _url: &str) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    /// Ignored with the `noop` feature.
+    ///
+    /// # Errors
+    /// Never with the `noop` feature.
+    pub fn with_failover_url(self, _url: &str) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    /// Ignored with the `noop` feature.
+    ///
+    /// # Errors
+    /// Never with the `noop` feature.
+    pub fn with_secondary_endpoint<K, V>(
+        self,
+        _url: &str,
+        _headers: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_min_span_duration(self, _min_duration: Duration) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_span_collapsing(self, _min_run: usize) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_local_collector(self, _port: u16) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_eager_connect(self, _enabled: bool) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn lenient(self) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn errors_only(self) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_latency_sampling(self, _threshold: Duration, _ratio: f64) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_traces_path(self, _path: impl Into<String>) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    ///
+    /// # Errors
+    /// Never with the `noop` feature.
+    pub fn with_remote_sampling(self, _url: &str, _interval: Duration) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_tail_sampling_limits(self, _max_wait: Duration, _max_spans: usize) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_circuit_breaker(self, _failures: u32, _cooldown: Duration) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_service_name(self, _service_name: impl Into<String>) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_service_version(self, _service_version: impl Into<String>) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_instrumentation_scope(
+        self,
+        _name: impl Into<String>,
+        _version: Option<impl Into<String>>,
+        _schema_url: Option<impl Into<String>>,
+    ) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_build_info(
+        self,
+        _git_sha: Option<impl Into<String>>,
+        _build_timestamp: Option<impl Into<String>>,
+    ) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_max_attributes_per_span(self, _max: u32) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_max_events_per_span(self, _max: u32) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_max_links_per_span(self, _max: u32) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_service_instance_id(self, _instance_id: impl Into<String>) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_environment(self, _environment: impl Into<String>) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_tags<T, K, V>(self, _tags: T) -> Self
+    where
+        T: IntoIterator<Item = (K, V)>,
+    {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn add_tag<K, V>(self, _key: K, _value: V) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_baggage_keys<I>(self, _keys: I) -> Self
+    where
+        I: IntoIterator,
+    {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_trace_context_propagation(self) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_xray_compat(self) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_timeout(self, _timeout: Duration) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_ingest_resource_prefix(self, _prefix: impl Into<String>) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_connect_timeout(self, _timeout: Duration) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_request_timeout(self, _timeout: Duration) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_pool_idle_timeout(self, _timeout: Duration) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_pool_max_idle_per_host(self, _max: usize) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_tcp_keepalive(self, _interval: Duration) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_flush_interval(self, _interval: Duration) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_user_agent_suffix(self, _suffix: impl Into<String>) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_max_batch_bytes(self, _max_bytes: usize) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_span_name_mapper<F>(self, _mapper: F) -> Self
+    where
+        F: Fn(&str) -> Cow<'_, str> + Send + Sync + 'static,
+    {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_max_attribute_length(self, _max_len: usize) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_field_limits(self, _max_fields: usize, _max_depth: usize) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_location(self, _enabled: bool) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_threads(self, _enabled: bool) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_event_message(self, _enabled: bool) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_span_start_records(self, _enabled: bool) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_orphan_event_spans(self, _enabled: bool) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_span_summaries(self, _interval: Duration) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_error_status_description(self, _enabled: bool) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_burst_suppression(self, _threshold: u32, _window: Duration, _one_in: u32) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_event_dedup(self, _window: Duration) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_export_tracing(self, _enabled: bool) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_internal_logging(self, _level: tracing_core::Level) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_filter<F>(self, _filter: F) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_include_targets<T: IntoIterator>(self, _targets: T) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_exclude_targets<T: IntoIterator>(self, _targets: T) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_max_level(self, _level: tracing_core::Level) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn disabled(self) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_stdout_exporter(self) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn serverless(self) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    #[must_use]
+    pub fn with_env_prefix(self, _prefix: impl Into<String>) -> Self {
+        self
+    }
+
+    /// Ignored with the `noop` feature.
+    ///
+    /// # Errors
+    /// Never with the `noop` feature.
+    pub fn with_env(self) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    /// Create a layer that does nothing.
+    ///
+    /// # Errors
+    /// Never with the `noop` feature.
+    pub fn build<S>(self) -> Result<NoopLayer<S>, Error>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        Ok(NoopLayer(PhantomData))
+    }
+}
+
+/// Ignores the fields with the `noop` feature.
+#[derive(Debug)]
+pub struct Scope {
+    _private: (),
+}
+
+/// Does nothing with the `noop` feature.
+#[must_use]
+pub fn scope() -> Scope {
+    Scope { _private: () }
+}
+
+impl Scope {
+    /// Ignored with the `noop` feature.
+    #[allow(clippy::needless_pass_by_value, clippy::return_self_not_must_use)]
+    pub fn with_field<K, V>(self, _key: K, _value: V) -> Self {
+        self
+    }
+}
+
+/// Does nothing with the `noop` feature.
+///
+/// # Errors
+/// Never with the `noop` feature.
+pub fn force_flush() -> Result<(), Error> {
+    Ok(())
+}
+
+/// Does nothing with the `noop` feature.
+///
+/// # Errors
+/// Never with the `noop` feature.
+pub fn shutdown_with_timeout(_timeout: Duration) -> Result<(), ShutdownError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::{layer::SubscriberExt as _, Registry};
+
+    #[test]
+    fn test_noop_layer() -> Result<(), crate::Error> {
+        let layer = crate::builder("noop")
+            .with_dataset("test")?
+            .with_token("not-a-token")?
+            .add_tag("key", 1)
+            .build()?;
+        let subscriber = Registry::default().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("ignored").in_scope(|| {
+                crate::scope().with_field("tenant_id", "ignored");
+                tracing::info!("ignored");
+            });
+        });
+        crate::force_flush()
+    }
+}
@@ -0,0 +1,116 @@
+//! Ready made subscriber setups that combine the Axiom layer with a local
+//! [`fmt`](mod@tracing_subscriber::fmt) layer.
+//!
+//! Each preset installs a [`Registry`] with both layers as the global default
+//! subscriber, so it can only be called once per process.
+//!
+//! ```rust,no_run
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     tracing_axiom::presets::fmt_and_axiom(tracing_axiom::builder_with_env("presets")?)?;
+//!     tracing::info!("Hello, world!");
+//!     opentelemetry::global::shutdown_tracer_provider();
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{Builder, Error};
+use std::io::IsTerminal as _;
+use tracing_core::Subscriber;
+#[cfg(not(feature = "noop"))]
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{
+    filter::LevelFilter,
+    layer::{Filter, SubscriberExt as _},
+    util::SubscriberInitExt as _,
+    Layer, Registry,
+};
+
+/// Installs the Axiom layer together with a human readable
+/// [`fmt`](mod@tracing_subscriber::fmt) layer on stdout.
+///
+/// # Errors
+///
+/// Errors if the Axiom layer could not be built or a global subscriber was
+/// already installed.
+pub fn fmt_and_axiom(builder: Builder) -> Result<(), Error> {
+    let axiom_layer = builder.build()?;
+    let fmt_layer = tracing_subscriber::fmt::layer();
+    Registry::default()
+        .with(fmt_layer)
+        .with(axiom_layer)
+        .try_init()?;
+    Ok(())
+}
+
+/// Installs the Axiom layer together with an AWS `CloudWatch` compatible JSON
+/// [`fmt`](mod@tracing_subscriber::fmt) layer on stdout. The JSON layer omits
+/// timestamps (`CloudWatch` adds its own), ANSI colors and targets.
+///
+/// # Errors
+///
+/// Errors if the Axiom layer could not be built or a global subscriber was
+/// already installed.
+pub fn cloudwatch_and_axiom(builder: Builder) -> Result<(), Error> {
+    let axiom_layer = builder.build()?;
+    let cloudwatch_layer = tracing_subscriber::fmt::layer()
+        .json()
+        .with_ansi(false)
+        .without_time()
+        .with_target(false);
+    Registry::default()
+        .with(axiom_layer)
+        .with(cloudwatch_layer)
+        .try_init()?;
+    Ok(())
+}
+
+/// Installs the Axiom layer together with a [`fmt`](mod@tracing_subscriber::fmt)
+/// layer on stdout that is human readable in a terminal and JSON otherwise,
+/// e.g. when the output is collected in a container.
+///
+/// The fmt layer has a filter of its own, set with the `RUST_LOG` environment
+/// variable and `info` by default, while the Axiom layer keeps the filter of
+/// the builder, see [`Builder::with_filter`].
+///
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// tracing_axiom::presets::init_with_fmt(tracing_axiom::builder_with_env("my-service")?)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Errors if the Axiom layer could not be built or a global subscriber was
+/// already installed.
+pub fn init_with_fmt(builder: Builder) -> Result<(), Error> {
+    let axiom_layer = builder.build()?;
+    let fmt_layer: Box<dyn Layer<Registry> + Send + Sync> = if std::io::stdout().is_terminal() {
+        Box::new(tracing_subscriber::fmt::layer().pretty())
+    } else {
+        Box::new(tracing_subscriber::fmt::layer().json())
+    };
+    Registry::default()
+        .with(fmt_layer.with_filter(fmt_filter()))
+        .with(axiom_layer)
+        .try_init()?;
+    Ok(())
+}
+
+/// The filter of the fmt layer, from `RUST_LOG` or `info`.
+#[cfg(not(feature = "noop"))]
+fn fmt_filter<S: Subscriber>() -> impl Filter<S> {
+    EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy()
+}
+
+/// The filter of the fmt layer, the level in `RUST_LOG` or `info`.
+#[cfg(feature = "noop")]
+fn fmt_filter<S: Subscriber>() -> impl Filter<S> {
+    std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(LevelFilter::INFO)
+}
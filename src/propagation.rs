@@ -0,0 +1,147 @@
+//! Context propagation across service boundaries.
+//!
+//! The helpers in this module use the global text map propagator, which can be
+//! installed with [`Builder::with_propagator`](crate::Builder::with_propagator).
+//!
+//! ```rust
+//! use tracing_axiom::propagation::{extract_context, inject_context};
+//! use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+//!
+//! fn handle(incoming: &http::HeaderMap) -> http::HeaderMap {
+//!     let span = tracing::info_span!("handle");
+//!     // Continue the trace of the caller
+//!     span.set_parent(extract_context(incoming));
+//!     let _guard = span.enter();
+//!
+//!     // Pass the trace on to the next service
+//!     let mut outgoing = http::HeaderMap::new();
+//!     inject_context(&mut outgoing);
+//!     outgoing
+//! }
+//! ```
+
+mod b3;
+mod baggage;
+mod xray;
+
+use b3::B3Propagator;
+pub(crate) use baggage::BaggageProcessor;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::{
+    global,
+    propagation::{Extractor, Injector, TextMapCompositePropagator, TextMapPropagator},
+    Context,
+};
+use opentelemetry_sdk::propagation::{BaggagePropagator, TraceContextPropagator};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+pub(crate) use xray::XrayIdGenerator;
+use xray::XrayPropagator;
+
+/// The text map propagator to install as the global propagator when the layer
+/// is built, see [`Builder::with_propagator`](crate::Builder::with_propagator).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Propagation {
+    /// [W3C Trace Context](https://www.w3.org/TR/trace-context/) using the
+    /// `traceparent` and `tracestate` headers.
+    TraceContext,
+    /// [B3](https://github.com/openzipkin/b3-propagation) using the single `b3`
+    /// header. Both single and multi header formats are accepted on extraction.
+    B3,
+    /// [B3](https://github.com/openzipkin/b3-propagation) using the multiple
+    /// `X-B3-*` headers. Both single and multi header formats are accepted on
+    /// extraction.
+    B3Multi,
+    /// [AWS X-Ray](https://docs.aws.amazon.com/xray/latest/devguide/xray-concepts.html#xray-concepts-tracingheader)
+    /// using the `X-Amzn-Trace-Id` header.
+    XRay,
+}
+
+impl Propagation {
+    /// Installs the propagator globally, together with the W3C Baggage one if
+    /// `baggage` is set.
+    pub(crate) fn install(self, baggage: bool) {
+        let propagator: Box<dyn TextMapPropagator + Send + Sync> = match self {
+            Self::TraceContext => Box::new(TraceContextPropagator::new()),
+            Self::B3 => Box::new(B3Propagator::new(true)),
+            Self::B3Multi => Box::new(B3Propagator::new(false)),
+            Self::XRay => Box::new(XrayPropagator::new()),
+        };
+        let mut propagators = vec![propagator];
+        if baggage {
+            propagators.push(Box::new(BaggagePropagator::new()));
+        }
+        global::set_text_map_propagator(TextMapCompositePropagator::new(propagators));
+    }
+}
+
+/// Extracts the trace context of the caller from the request headers, to be used
+/// as the parent of the span handling the request with
+/// [`OpenTelemetrySpanExt::set_parent`](tracing_opentelemetry::OpenTelemetrySpanExt::set_parent).
+#[must_use]
+pub fn extract_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// Injects the trace context of the current [`tracing::Span`] into the headers of an
+/// outgoing request.
+pub fn inject_context(headers: &mut HeaderMap) {
+    inject_context_from(&tracing::Span::current().context(), headers);
+}
+
+/// Injects the given trace context into the headers of an outgoing request.
+pub fn inject_context_from(cx: &Context, headers: &mut HeaderMap) {
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(cx, &mut HeaderInjector(headers));
+    });
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::{propagation::TextMapPropagator, trace::TraceContextExt as _};
+
+    #[test]
+    fn test_header_round_trip() {
+        let propagator = TraceContextPropagator::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+        let cx = propagator.extract(&HeaderExtractor(&headers));
+        assert_eq!(
+            cx.span().span_context().trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+
+        let mut injected = HeaderMap::new();
+        propagator.inject_context(&cx, &mut HeaderInjector(&mut injected));
+        assert_eq!(injected.get("traceparent"), headers.get("traceparent"));
+    }
+}
@@ -0,0 +1,210 @@
+//! [B3](https://github.com/openzipkin/b3-propagation) propagation for interop with
+//! Zipkin instrumented services.
+
+use opentelemetry::{
+    propagation::{text_map_propagator::FieldIter, Extractor, Injector, TextMapPropagator},
+    trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+    Context,
+};
+
+const B3_SINGLE_HEADER: &str = "b3";
+const B3_TRACE_ID_HEADER: &str = "x-b3-traceid";
+const B3_SPAN_ID_HEADER: &str = "x-b3-spanid";
+const B3_SAMPLED_HEADER: &str = "x-b3-sampled";
+const B3_DEBUG_FLAG_HEADER: &str = "x-b3-flags";
+
+/// Propagates trace context using B3 headers. Extraction accepts both the single
+/// `b3` header and the multi `X-B3-*` headers, injection uses the configured one.
+#[derive(Debug)]
+pub(crate) struct B3Propagator {
+    single_header: bool,
+    fields: [String; 4],
+}
+
+impl B3Propagator {
+    pub(crate) fn new(single_header: bool) -> Self {
+        Self {
+            single_header,
+            fields: [
+                B3_SINGLE_HEADER.to_string(),
+                B3_TRACE_ID_HEADER.to_string(),
+                B3_SPAN_ID_HEADER.to_string(),
+                B3_SAMPLED_HEADER.to_string(),
+            ],
+        }
+    }
+
+    fn extract_single_header(extractor: &dyn Extractor) -> Option<SpanContext> {
+        let header = extractor.get(B3_SINGLE_HEADER)?.trim();
+        let mut parts = header.split('-');
+        let trace_id = parse_trace_id(parts.next()?)?;
+        let span_id = parse_span_id(parts.next()?)?;
+        let flags = match parts.next() {
+            Some(sampled) => parse_sampled(sampled)?,
+            None => TraceFlags::default(),
+        };
+        valid(trace_id, span_id, flags)
+    }
+
+    fn extract_multi_header(extractor: &dyn Extractor) -> Option<SpanContext> {
+        let trace_id = parse_trace_id(extractor.get(B3_TRACE_ID_HEADER)?.trim())?;
+        let span_id = parse_span_id(extractor.get(B3_SPAN_ID_HEADER)?.trim())?;
+        let flags = if extractor.get(B3_DEBUG_FLAG_HEADER).map(str::trim) == Some("1") {
+            TraceFlags::SAMPLED
+        } else {
+            match extractor.get(B3_SAMPLED_HEADER) {
+                Some(sampled) => parse_sampled(sampled.trim())?,
+                None => TraceFlags::default(),
+            }
+        };
+        valid(trace_id, span_id, flags)
+    }
+}
+
+fn parse_trace_id(trace_id: &str) -> Option<TraceId> {
+    // 64 bit trace ids are left padded to 128 bit
+    if (trace_id.len() == 16 || trace_id.len() == 32)
+        && !trace_id.chars().any(|c| c.is_ascii_uppercase())
+    {
+        TraceId::from_hex(trace_id).ok()
+    } else {
+        None
+    }
+}
+
+fn parse_span_id(span_id: &str) -> Option<SpanId> {
+    if span_id.len() == 16 && !span_id.chars().any(|c| c.is_ascii_uppercase()) {
+        SpanId::from_hex(span_id).ok()
+    } else {
+        None
+    }
+}
+
+fn parse_sampled(sampled: &str) -> Option<TraceFlags> {
+    match sampled {
+        "1" | "d" | "true" => Some(TraceFlags::SAMPLED),
+        "0" | "false" => Some(TraceFlags::default()),
+        _ => None,
+    }
+}
+
+fn valid(trace_id: TraceId, span_id: SpanId, flags: TraceFlags) -> Option<SpanContext> {
+    let span_context = SpanContext::new(trace_id, span_id, flags, true, TraceState::default());
+    span_context.is_valid().then_some(span_context)
+}
+
+impl TextMapPropagator for B3Propagator {
+    fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return;
+        }
+        let sampled = if span_context.is_sampled() { "1" } else { "0" };
+        if self.single_header {
+            injector.set(
+                B3_SINGLE_HEADER,
+                format!(
+                    "{}-{}-{sampled}",
+                    span_context.trace_id(),
+                    span_context.span_id()
+                ),
+            );
+        } else {
+            injector.set(B3_TRACE_ID_HEADER, span_context.trace_id().to_string());
+            injector.set(B3_SPAN_ID_HEADER, span_context.span_id().to_string());
+            injector.set(B3_SAMPLED_HEADER, sampled.to_string());
+        }
+    }
+
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        Self::extract_single_header(extractor)
+            .or_else(|| Self::extract_multi_header(extractor))
+            .map_or_else(|| cx.clone(), |sc| cx.with_remote_span_context(sc))
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        if self.single_header {
+            FieldIter::new(&self.fields[..1])
+        } else {
+            FieldIter::new(&self.fields[1..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    fn extract(headers: &[(&str, &str)]) -> SpanContext {
+        let headers: HashMap<String, String> = headers
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        let cx = B3Propagator::new(true).extract_with_context(&Context::new(), &headers);
+        cx.span().span_context().clone()
+    }
+
+    #[test]
+    fn test_extract_single_header() {
+        let sc = extract(&[("b3", &format!("{TRACE_ID}-{SPAN_ID}-1"))]);
+        assert_eq!(sc.trace_id().to_string(), TRACE_ID);
+        assert_eq!(sc.span_id().to_string(), SPAN_ID);
+        assert!(sc.is_sampled());
+        assert!(sc.is_remote());
+
+        let sc = extract(&[("b3", &format!("a3ce929d0e0e4736-{SPAN_ID}-0"))]);
+        assert_eq!(
+            sc.trace_id().to_string(),
+            "0000000000000000a3ce929d0e0e4736"
+        );
+        assert!(!sc.is_sampled());
+
+        assert!(!extract(&[("b3", "0")]).is_valid());
+        assert!(!extract(&[("b3", &format!("{TRACE_ID}-{SPAN_ID}-x"))]).is_valid());
+    }
+
+    #[test]
+    fn test_extract_multi_header() {
+        let sc = extract(&[
+            ("x-b3-traceid", TRACE_ID),
+            ("x-b3-spanid", SPAN_ID),
+            ("x-b3-sampled", "1"),
+        ]);
+        assert_eq!(sc.trace_id().to_string(), TRACE_ID);
+        assert!(sc.is_sampled());
+
+        let sc = extract(&[
+            ("x-b3-traceid", TRACE_ID),
+            ("x-b3-spanid", SPAN_ID),
+            ("x-b3-flags", "1"),
+        ]);
+        assert!(sc.is_sampled());
+    }
+
+    #[test]
+    fn test_inject() {
+        let sc = SpanContext::new(
+            TraceId::from_hex(TRACE_ID).expect("valid trace id"),
+            SpanId::from_hex(SPAN_ID).expect("valid span id"),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let cx = Context::new().with_remote_span_context(sc);
+
+        let mut single = HashMap::new();
+        B3Propagator::new(true).inject_context(&cx, &mut single);
+        assert_eq!(single.get("b3"), Some(&format!("{TRACE_ID}-{SPAN_ID}-1")));
+
+        let mut multi = HashMap::new();
+        B3Propagator::new(false).inject_context(&cx, &mut multi);
+        assert_eq!(multi.get("x-b3-traceid"), Some(&TRACE_ID.to_string()));
+        assert_eq!(multi.get("x-b3-spanid"), Some(&SPAN_ID.to_string()));
+        assert_eq!(multi.get("x-b3-sampled"), Some(&"1".to_string()));
+    }
+}
@@ -0,0 +1,74 @@
+use opentelemetry::{baggage::BaggageExt as _, trace::Span as _, Context, Key, KeyValue};
+use opentelemetry_sdk::{
+    export::trace::SpanData,
+    trace::{Span, SpanProcessor},
+};
+
+/// Copies the configured baggage entries of the parent context onto spans when
+/// they start, see
+/// [`Builder::with_baggage_keys`](crate::Builder::with_baggage_keys).
+#[derive(Debug)]
+pub(crate) struct BaggageProcessor {
+    keys: Vec<Key>,
+}
+
+impl BaggageProcessor {
+    pub(crate) fn new(keys: Vec<Key>) -> Self {
+        Self { keys }
+    }
+}
+
+impl SpanProcessor for BaggageProcessor {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        let baggage = cx.baggage();
+        for key in &self.keys {
+            if let Some(value) = baggage.get(key.clone()) {
+                span.set_attribute(KeyValue::new(key.clone(), value.clone()));
+            }
+        }
+    }
+
+    fn on_end(&self, _span: SpanData) {}
+
+    fn force_flush(&self) -> opentelemetry::trace::TraceResult<()> {
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> opentelemetry::trace::TraceResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::attribute;
+    use opentelemetry::{baggage::BaggageExt as _, KeyValue};
+    use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_baggage_keys() {
+        let (layer, spans) = crate::builder("baggage")
+            .with_baggage_keys(["tenant_id"])
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let cx = opentelemetry::Context::new().with_baggage([
+                KeyValue::new("tenant_id", "acme"),
+                KeyValue::new("session", "secret"),
+            ]);
+            let span = tracing::info_span!("request");
+            span.set_parent(cx);
+            span.in_scope(|| tracing::info_span!("query").in_scope(|| {}));
+        });
+
+        for name in ["request", "query"] {
+            let span = spans.span(name).expect("span was recorded");
+            assert_eq!(
+                attribute(&span, "tenant_id"),
+                Some(&opentelemetry::Value::from("acme"))
+            );
+            assert_eq!(attribute(&span, "session"), None);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! [AWS X-Ray](https://docs.aws.amazon.com/xray/latest/devguide/xray-concepts.html#xray-concepts-tracingheader)
+//! compatible propagation and trace ids.
+
+use opentelemetry::{
+    propagation::{text_map_propagator::FieldIter, Extractor, Injector, TextMapPropagator},
+    trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+    Context,
+};
+use opentelemetry_sdk::trace::{IdGenerator, RandomIdGenerator};
+use std::time::UNIX_EPOCH;
+
+const XRAY_HEADER: &str = "x-amzn-trace-id";
+
+/// Generates trace ids that can be converted to X-Ray trace ids, the first 32
+/// bits are the current unix time in seconds, the remaining 96 bits are random.
+#[derive(Debug, Default)]
+pub(crate) struct XrayIdGenerator {
+    random: RandomIdGenerator,
+}
+
+impl IdGenerator for XrayIdGenerator {
+    fn new_trace_id(&self) -> TraceId {
+        // `SystemTime::now` is not available in the browser
+        let epoch_seconds = opentelemetry::time::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let random = u128::from_be_bytes(self.random.new_trace_id().to_bytes());
+        // X-Ray epochs are 32 bit
+        let epoch = u128::from(epoch_seconds & u64::from(u32::MAX));
+        TraceId::from((epoch << 96) | (random & ((1 << 96) - 1)))
+    }
+
+    fn new_span_id(&self) -> SpanId {
+        self.random.new_span_id()
+    }
+}
+
+/// Propagates trace context using the `X-Amzn-Trace-Id` header, e.g.
+/// `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`.
+#[derive(Debug)]
+pub(crate) struct XrayPropagator {
+    fields: [String; 1],
+}
+
+impl XrayPropagator {
+    pub(crate) fn new() -> Self {
+        Self {
+            fields: [XRAY_HEADER.to_string()],
+        }
+    }
+
+    fn extract_span_context(extractor: &dyn Extractor) -> Option<SpanContext> {
+        let header = extractor.get(XRAY_HEADER)?;
+        let mut trace_id = None;
+        let mut span_id = None;
+        let mut flags = TraceFlags::default();
+        for part in header.split(';') {
+            match part.trim().split_once('=')? {
+                ("Root", root) => trace_id = Some(parse_root(root)?),
+                ("Parent", parent) => span_id = Some(SpanId::from_hex(parent).ok()?),
+                ("Sampled", "1") => flags = TraceFlags::SAMPLED,
+                _ => {}
+            }
+        }
+        let span_context =
+            SpanContext::new(trace_id?, span_id?, flags, true, TraceState::default());
+        span_context.is_valid().then_some(span_context)
+    }
+}
+
+fn parse_root(root: &str) -> Option<TraceId> {
+    let mut parts = root.split('-');
+    let (version, epoch, id) = (parts.next()?, parts.next()?, parts.next()?);
+    if version != "1" || epoch.len() != 8 || id.len() != 24 || parts.next().is_some() {
+        return None;
+    }
+    TraceId::from_hex(&format!("{epoch}{id}")).ok()
+}
+
+impl TextMapPropagator for XrayPropagator {
+    fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return;
+        }
+        let trace_id = span_context.trace_id().to_string();
+        let (epoch, id) = trace_id.split_at(8);
+        let sampled = u8::from(span_context.is_sampled());
+        injector.set(
+            XRAY_HEADER,
+            format!(
+                "Root=1-{epoch}-{id};Parent={};Sampled={sampled}",
+                span_context.span_id()
+            ),
+        );
+    }
+
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        Self::extract_span_context(extractor)
+            .map_or_else(|| cx.clone(), |sc| cx.with_remote_span_context(sc))
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        FieldIter::new(&self.fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashMap, time::SystemTime};
+
+    const HEADER: &str =
+        "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1";
+
+    #[test]
+    fn test_round_trip() {
+        let mut headers = HashMap::new();
+        headers.insert(XRAY_HEADER.to_string(), HEADER.to_string());
+        let cx = XrayPropagator::new().extract_with_context(&Context::new(), &headers);
+        let sc = cx.span().span_context().clone();
+        assert_eq!(
+            sc.trace_id().to_string(),
+            "5759e988bd862e3fe1be46a994272793"
+        );
+        assert_eq!(sc.span_id().to_string(), "53995c3f42cd8ad8");
+        assert!(sc.is_sampled());
+
+        let mut injected = HashMap::new();
+        XrayPropagator::new().inject_context(&cx, &mut injected);
+        assert_eq!(injected.get(XRAY_HEADER), Some(&HEADER.to_string()));
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        for header in [
+            "Root=2-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8",
+            "Root=1-5759e988-bd862e3fe1be46a994272793",
+            "Parent=53995c3f42cd8ad8;Sampled=1",
+            "garbage",
+        ] {
+            let mut headers = HashMap::new();
+            headers.insert(XRAY_HEADER.to_string(), header.to_string());
+            assert!(XrayPropagator::extract_span_context(&headers).is_none());
+        }
+    }
+
+    #[test]
+    fn test_id_generator_uses_epoch() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let trace_id = XrayIdGenerator::default().new_trace_id().to_bytes();
+        let epoch = u32::from_be_bytes([trace_id[0], trace_id[1], trace_id[2], trace_id[3]]);
+        assert!(u64::from(epoch).abs_diff(now) <= 1);
+    }
+}
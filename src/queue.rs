@@ -0,0 +1,474 @@
+use crate::export::ExportPipeline;
+use opentelemetry::Context;
+use opentelemetry_sdk::{
+    export::trace::SpanData,
+    runtime::{Runtime, RuntimeChannel, TrySend, TrySendError},
+    trace::{BatchConfigBuilder, BatchSpanProcessor, Span, SpanProcessor},
+};
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+    task::{Context as TaskContext, Poll, Waker},
+    time::Duration,
+};
+use tokio_stream::Stream;
+
+/// What happens to spans that end while the export queue is full, see
+/// [`Builder::with_backpressure`](crate::Builder::with_backpressure).
+///
+/// The queue holds 2048 spans unless set with the `OTEL_BSP_MAX_QUEUE_SIZE`
+/// environment variable. Dropped spans are counted, see [`dropped_spans`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Drop the span that just ended.
+    #[default]
+    DropNewest,
+    /// Drop the oldest span in the queue to make room for the one that just
+    /// ended.
+    DropOldest,
+    /// Block the thread the span ended on until there is room in the queue,
+    /// for at most the duration, and drop the span if there is none by then.
+    /// Not supported on `wasm32`, where spans are dropped right away.
+    Block(Duration),
+}
+
+/// The number of spans the most recently built layer dropped because its
+/// export queue was full.
+#[must_use]
+pub fn dropped_spans() -> u64 {
+    queue_stats().map_or(0, |stats| stats.dropped)
+}
+
+/// A snapshot of the export queue, see [`queue_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct QueueStats {
+    /// The number of spans waiting to be exported.
+    pub len: usize,
+    /// The number of spans the queue holds before [`Backpressure`] applies.
+    pub capacity: usize,
+    /// The number of spans dropped because the queue was full, since the
+    /// layer was built.
+    pub dropped: u64,
+}
+
+/// The export queue of the most recently built layer, to alert when spans
+/// back up before they are dropped. Cheap enough to poll every few seconds,
+/// e.g. to report it as gauges. `None` if no layer exporting spans in batches
+/// was built.
+#[must_use]
+pub fn queue_stats() -> Option<QueueStats> {
+    COUNTERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .map(|counters| counters.stats())
+}
+
+/// The counters of the most recently built layer.
+static COUNTERS: Mutex<Option<Arc<Counters>>> = Mutex::new(None);
+
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    len: AtomicUsize,
+    capacity: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+impl Counters {
+    /// Makes these the counters reported by [`queue_stats`].
+    pub(crate) fn register(self: &Arc<Self>) {
+        *COUNTERS.lock().unwrap_or_else(PoisonError::into_inner) = Some(self.clone());
+    }
+
+    pub(crate) fn stats(&self) -> QueueStats {
+        QueueStats {
+            len: self.len.load(Ordering::Relaxed),
+            capacity: self.capacity.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+thread_local! {
+    /// Set while a span is sent to the queue, to tell it from the flush and
+    /// shutdown messages, which are never dropped.
+    static SENDING_SPAN: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The export queue of the batch span processor with a [`Backpressure`] policy.
+#[derive(Debug, Default)]
+pub(crate) struct Queue {
+    backpressure: Backpressure,
+    flush_interval: Option<Duration>,
+    counters: Arc<Counters>,
+}
+
+impl Queue {
+    pub(crate) fn with_backpressure(self, backpressure: Backpressure) -> Self {
+        Self {
+            backpressure,
+            ..self
+        }
+    }
+
+    /// Export every `interval` instead of the `OTEL_BSP_SCHEDULE_DELAY`.
+    pub(crate) fn with_flush_interval(self, interval: Duration) -> Self {
+        Self {
+            flush_interval: Some(interval),
+            ..self
+        }
+    }
+
+    pub(crate) fn counters(&self) -> Arc<Counters> {
+        self.counters.clone()
+    }
+
+    /// A batch span processor using this queue.
+    pub(crate) fn processor<R: RuntimeChannel>(
+        self,
+        exporter: ExportPipeline,
+        runtime: R,
+    ) -> QueueProcessor<R> {
+        let runtime = QueueRuntime {
+            runtime,
+            backpressure: self.backpressure,
+            counters: self.counters,
+        };
+        let mut config = BatchConfigBuilder::default();
+        if let Some(interval) = self.flush_interval {
+            config = config.with_scheduled_delay(interval);
+        }
+        QueueProcessor(
+            BatchSpanProcessor::builder(exporter, runtime)
+                .with_batch_config(config.build())
+                .build(),
+        )
+    }
+}
+
+/// A batch span processor that marks the spans it sends to its queue.
+pub(crate) struct QueueProcessor<R: RuntimeChannel>(BatchSpanProcessor<QueueRuntime<R>>);
+
+impl<R: RuntimeChannel> fmt::Debug for QueueProcessor<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("QueueProcessor").field(&self.0).finish()
+    }
+}
+
+impl<R: RuntimeChannel> SpanProcessor for QueueProcessor<R> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.0.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        SENDING_SPAN.with(|sending| sending.set(true));
+        self.0.on_end(span);
+        SENDING_SPAN.with(|sending| sending.set(false));
+    }
+
+    fn force_flush(&self) -> opentelemetry::trace::TraceResult<()> {
+        self.0.force_flush()
+    }
+
+    fn shutdown(&mut self) -> opentelemetry::trace::TraceResult<()> {
+        self.0.shutdown()
+    }
+}
+
+/// Runs the batch span processor on the wrapped runtime, with a [`Channel`]
+/// as queue.
+#[derive(Debug, Clone)]
+pub(crate) struct QueueRuntime<R> {
+    runtime: R,
+    backpressure: Backpressure,
+    counters: Arc<Counters>,
+}
+
+impl<R: Runtime> Runtime for QueueRuntime<R> {
+    type Interval = R::Interval;
+    type Delay = R::Delay;
+
+    fn interval(&self, duration: Duration) -> Self::Interval {
+        self.runtime.interval(duration)
+    }
+
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send + 'static>>) {
+        self.runtime.spawn(future);
+    }
+
+    fn delay(&self, duration: Duration) -> Self::Delay {
+        self.runtime.delay(duration)
+    }
+}
+
+impl<R: RuntimeChannel> RuntimeChannel for QueueRuntime<R> {
+    type Receiver<T: fmt::Debug + Send> = Receiver<T>;
+    type Sender<T: fmt::Debug + Send> = Sender<T>;
+
+    fn batch_message_channel<T: fmt::Debug + Send>(
+        &self,
+        capacity: usize,
+    ) -> (Self::Sender<T>, Self::Receiver<T>) {
+        self.counters.capacity.store(capacity, Ordering::Relaxed);
+        let channel = Arc::new(Channel {
+            state: Mutex::new(State {
+                messages: VecDeque::new(),
+                spans: 0,
+                waker: None,
+                sender_dropped: false,
+                receiver_dropped: false,
+            }),
+            not_full: Condvar::new(),
+            capacity,
+            backpressure: self.backpressure,
+            counters: self.counters.clone(),
+        });
+        (Sender(channel.clone()), Receiver(channel))
+    }
+}
+
+/// A queue of at most `capacity` spans, in addition to which flush and
+/// shutdown messages are always accepted.
+struct Channel<T> {
+    state: Mutex<State<T>>,
+    not_full: Condvar,
+    capacity: usize,
+    backpressure: Backpressure,
+    counters: Arc<Counters>,
+}
+
+struct State<T> {
+    /// The messages and whether they are spans.
+    messages: VecDeque<(T, bool)>,
+    spans: usize,
+    waker: Option<Waker>,
+    sender_dropped: bool,
+    receiver_dropped: bool,
+}
+
+impl<T> Channel<T> {
+    fn state(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set_len(&self, state: &State<T>) {
+        self.counters.len.store(state.spans, Ordering::Relaxed);
+    }
+
+    fn drop_span(&self) -> TrySendError {
+        self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+        TrySendError::ChannelFull
+    }
+}
+
+pub(crate) struct Sender<T>(Arc<Channel<T>>);
+
+impl<T: Send> TrySend for Sender<T> {
+    type Message = T;
+
+    fn try_send(&self, message: T) -> Result<(), TrySendError> {
+        let is_span = SENDING_SPAN.with(Cell::get);
+        let mut state = self.0.state();
+        if state.receiver_dropped {
+            return Err(TrySendError::ChannelClosed);
+        }
+        if is_span && state.spans >= self.0.capacity {
+            match self.0.backpressure {
+                Backpressure::DropNewest => return Err(self.0.drop_span()),
+                Backpressure::DropOldest => {
+                    let Some(oldest) = state.messages.iter().position(|(_, span)| *span) else {
+                        return Err(self.0.drop_span());
+                    };
+                    state.messages.remove(oldest);
+                    state.spans -= 1;
+                    self.0.drop_span();
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                Backpressure::Block(timeout) => {
+                    state = self
+                        .0
+                        .not_full
+                        .wait_timeout_while(state, timeout, |state| {
+                            state.spans >= self.0.capacity && !state.receiver_dropped
+                        })
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                    if state.receiver_dropped {
+                        return Err(TrySendError::ChannelClosed);
+                    }
+                    if state.spans >= self.0.capacity {
+                        return Err(self.0.drop_span());
+                    }
+                }
+                #[cfg(target_arch = "wasm32")]
+                Backpressure::Block(_) => return Err(self.0.drop_span()),
+            }
+        }
+        state.messages.push_back((message, is_span));
+        if is_span {
+            state.spans += 1;
+            self.0.set_len(&state);
+        }
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.state();
+        state.sender_dropped = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("capacity", &self.0.capacity)
+            .field("backpressure", &self.0.backpressure)
+            .finish_non_exhaustive()
+    }
+}
+
+pub(crate) struct Receiver<T>(Arc<Channel<T>>);
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<T>> {
+        let mut state = self.0.state();
+        if let Some((message, is_span)) = state.messages.pop_front() {
+            if is_span {
+                state.spans -= 1;
+                self.0.set_len(&state);
+                self.0.not_full.notify_one();
+            }
+            return Poll::Ready(Some(message));
+        }
+        if state.sender_dropped {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.0.state().receiver_dropped = true;
+        self.0.not_full.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(capacity: usize, backpressure: Backpressure) -> (Sender<u8>, Receiver<u8>) {
+        let runtime = QueueRuntime {
+            runtime: opentelemetry_sdk::runtime::Tokio,
+            backpressure,
+            counters: Arc::default(),
+        };
+        runtime.batch_message_channel(capacity)
+    }
+
+    #[test]
+    fn test_queue_settings() {
+        let queue = Queue::default()
+            .with_flush_interval(Duration::from_millis(200))
+            .with_backpressure(Backpressure::DropOldest);
+        assert_eq!(queue.flush_interval, Some(Duration::from_millis(200)));
+        assert_eq!(queue.backpressure, Backpressure::DropOldest);
+    }
+
+    fn send_span(sender: &Sender<u8>, message: u8) -> Result<(), TrySendError> {
+        SENDING_SPAN.with(|sending| sending.set(true));
+        let result = sender.try_send(message);
+        SENDING_SPAN.with(|sending| sending.set(false));
+        result
+    }
+
+    fn received(receiver: &Receiver<u8>) -> Vec<u8> {
+        let state = receiver.0.state();
+        state.messages.iter().map(|(message, _)| *message).collect()
+    }
+
+    #[test]
+    fn test_drop_newest() {
+        let (sender, receiver) = channel(2, Backpressure::DropNewest);
+        for span in 1..=3 {
+            let _ = send_span(&sender, span);
+        }
+        // Flush messages are never dropped
+        assert!(sender.try_send(0).is_ok());
+        assert_eq!(received(&receiver), [1, 2, 0]);
+        assert_eq!(sender.0.counters.dropped.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_len() {
+        use tokio_stream::StreamExt as _;
+
+        let (sender, mut receiver) = channel(4, Backpressure::DropNewest);
+        let counters = sender.0.counters.clone();
+        assert_eq!(counters.capacity.load(Ordering::Relaxed), 4);
+        assert!(sender.try_send(0).is_ok());
+        for span in 1..=2 {
+            assert!(send_span(&sender, span).is_ok());
+        }
+        // Only spans are counted
+        assert_eq!(counters.len.load(Ordering::Relaxed), 2);
+        receiver.next().await;
+        receiver.next().await;
+        assert_eq!(counters.len.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let (sender, receiver) = channel(2, Backpressure::DropOldest);
+        assert!(sender.try_send(0).is_ok());
+        for span in 1..=4 {
+            assert!(send_span(&sender, span).is_ok());
+        }
+        assert_eq!(received(&receiver), [0, 3, 4]);
+        assert_eq!(sender.0.counters.dropped.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block() {
+        use tokio_stream::StreamExt as _;
+
+        let (sender, mut receiver) = channel(1, Backpressure::Block(Duration::from_secs(10)));
+        assert!(send_span(&sender, 1).is_ok());
+        let blocked = std::thread::spawn(move || send_span(&sender, 2).map(|()| sender));
+        assert_eq!(receiver.next().await, Some(1));
+        let sender = blocked.join().expect("sender thread").expect("sent");
+        assert_eq!(received(&receiver), [2]);
+        assert_eq!(sender.0.counters.dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_block_timeout() {
+        let (sender, _receiver) = channel(1, Backpressure::Block(Duration::from_millis(10)));
+        assert!(send_span(&sender, 1).is_ok());
+        assert!(matches!(
+            send_span(&sender, 2),
+            Err(TrySendError::ChannelFull)
+        ));
+        assert_eq!(sender.0.counters.dropped.load(Ordering::Relaxed), 1);
+    }
+}
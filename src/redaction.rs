@@ -0,0 +1,232 @@
+//! Redact sensitive attributes before spans leave the process.
+//!
+//! ```rust
+//! # fn main() -> Result<(), tracing_axiom::Error> {
+//! use tracing_axiom::redaction::Redaction;
+//!
+//! let redaction = Redaction::new()
+//!     .key("http.request.header.authorization")
+//!     .key_pattern("*.password")?
+//!     .emails()
+//!     .value_pattern(r"token=\w+", "token=[REDACTED]")?;
+//! let builder = tracing_axiom::builder("my-service").with_redaction(redaction);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::Error;
+use opentelemetry::{trace::Status, Array, KeyValue, StringValue, Value};
+use opentelemetry_sdk::export::trace::SpanData;
+use regex::Regex;
+use std::borrow::Cow;
+
+/// The value that replaces the values of redacted keys.
+pub const REDACTED: &str = "[REDACTED]";
+
+const EMAIL: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}";
+const CREDIT_CARD: &str = r"\b(?:\d[ -]?){12,18}\d\b";
+
+fn builtin(pattern: &str) -> Regex {
+    Regex::new(pattern).expect("this is a valid regex")
+}
+
+/// Rules to redact attribute values of spans, their events and links.
+///
+/// The values of attributes with a matching key are replaced with
+/// [`REDACTED`]. Value patterns are replaced in all string values, event names
+/// (the message of `tracing` events) and error descriptions.
+#[derive(Debug, Clone, Default)]
+pub struct Redaction {
+    keys: Vec<String>,
+    key_patterns: Vec<Regex>,
+    value_patterns: Vec<(Regex, String)>,
+}
+
+impl Redaction {
+    /// Create a new redaction without any rules.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redact the value of attributes with exactly this key.
+    #[must_use]
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.keys.push(key.into());
+        self
+    }
+
+    /// Redact the value of attributes with a key matching the glob pattern,
+    /// where `*` matches any number of characters and `?` a single character,
+    /// e.g. `*.password` or `http.request.header.*`.
+    ///
+    /// # Errors
+    /// If the pattern is too large to compile.
+    pub fn key_pattern(mut self, pattern: &str) -> Result<Self, Error> {
+        let pattern = regex::escape(pattern)
+            .replace(r"\*", ".*")
+            .replace(r"\?", ".");
+        self.key_patterns.push(Regex::new(&format!("^{pattern}$"))?);
+        Ok(self)
+    }
+
+    /// Replace all matches of the regular expression in string values with the
+    /// replacement, which can refer to capture groups like `$1`.
+    ///
+    /// # Errors
+    /// If the regular expression is invalid.
+    pub fn value_pattern(
+        mut self,
+        pattern: &str,
+        replacement: impl Into<String>,
+    ) -> Result<Self, Error> {
+        self.value_patterns
+            .push((Regex::new(pattern)?, replacement.into()));
+        Ok(self)
+    }
+
+    /// Mask email addresses in string values.
+    #[must_use]
+    pub fn emails(mut self) -> Self {
+        self.value_patterns
+            .push((builtin(EMAIL), REDACTED.to_string()));
+        self
+    }
+
+    /// Mask credit card numbers (13 to 19 digits, optionally separated by
+    /// spaces or dashes) in string values.
+    #[must_use]
+    pub fn credit_cards(mut self) -> Self {
+        self.value_patterns
+            .push((builtin(CREDIT_CARD), REDACTED.to_string()));
+        self
+    }
+
+    pub(crate) fn apply(&self, span: &mut SpanData) {
+        self.redact_attributes(&mut span.attributes);
+        for event in &mut span.events.events {
+            self.redact_attributes(&mut event.attributes);
+            if let Some(name) = self.replace(&event.name) {
+                event.name = Cow::Owned(name);
+            }
+        }
+        for link in &mut span.links.links {
+            self.redact_attributes(&mut link.attributes);
+        }
+        if let Status::Error { description } = &mut span.status {
+            if let Some(redacted) = self.replace(description) {
+                *description = Cow::Owned(redacted);
+            }
+        }
+    }
+
+    fn redact_attributes(&self, attributes: &mut [KeyValue]) {
+        for kv in attributes {
+            if self.redacts_key(kv.key.as_str()) {
+                kv.value = Value::from(REDACTED);
+                continue;
+            }
+            match &mut kv.value {
+                Value::String(s) => {
+                    if let Some(redacted) = self.replace(s.as_str()) {
+                        *s = StringValue::from(redacted);
+                    }
+                }
+                Value::Array(Array::String(values)) => {
+                    for s in values {
+                        if let Some(redacted) = self.replace(s.as_str()) {
+                            *s = StringValue::from(redacted);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn redacts_key(&self, key: &str) -> bool {
+        self.keys.iter().any(|k| k == key) || self.key_patterns.iter().any(|p| p.is_match(key))
+    }
+
+    /// The value with all value patterns replaced, if any of them matched.
+    fn replace(&self, value: &str) -> Option<String> {
+        let mut result = Cow::Borrowed(value);
+        for (pattern, replacement) in &self.value_patterns {
+            if let Cow::Owned(replaced) = pattern.replace_all(&result, replacement.as_str()) {
+                result = Cow::Owned(replaced);
+            }
+        }
+        match result {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(redacted) => Some(redacted),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::attribute;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_key_pattern() -> Result<(), Error> {
+        let redaction = Redaction::new()
+            .key("token")
+            .key_pattern("*.password")?
+            .key_pattern("header.?")?;
+        assert!(redaction.redacts_key("token"));
+        assert!(!redaction.redacts_key("token.id"));
+        assert!(redaction.redacts_key("db.password"));
+        assert!(!redaction.redacts_key("db.password.length"));
+        assert!(redaction.redacts_key("header.a"));
+        assert!(!redaction.redacts_key("header.ab"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_patterns() -> Result<(), Error> {
+        let redaction = Redaction::new()
+            .emails()
+            .credit_cards()
+            .value_pattern(r"token=(\w)\w+", "token=$1***")?;
+        assert_eq!(
+            redaction
+                .replace("mail jane.doe@example.com now")
+                .as_deref(),
+            Some("mail [REDACTED] now")
+        );
+        assert_eq!(
+            redaction.replace("card 4111 1111 1111 1111").as_deref(),
+            Some("card [REDACTED]")
+        );
+        assert_eq!(
+            redaction.replace("token=abcdef").as_deref(),
+            Some("token=a***")
+        );
+        assert_eq!(redaction.replace("order 1234"), None);
+        assert!(Redaction::new().value_pattern("(", "").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_redacts_spans() {
+        let redaction = Redaction::new().key("user.password").emails();
+        let (layer, spans) = crate::builder("redaction")
+            .with_redaction(redaction)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("login", user.password = "hunter2", user.email = "a@b.io")
+                .in_scope(|| tracing::info!("welcome a@b.io"));
+        });
+
+        let span = spans.span("login").expect("login span");
+        assert_eq!(
+            attribute(&span, "user.password"),
+            Some(&Value::from(REDACTED))
+        );
+        assert_eq!(attribute(&span, "user.email"), Some(&Value::from(REDACTED)));
+        assert_eq!(span.events[0].name, "welcome [REDACTED]");
+    }
+}
@@ -0,0 +1,242 @@
+#[cfg(not(target_arch = "wasm32"))]
+mod remote;
+
+use crate::{builder::validate_token, filter::SharedFilter, AxiomFilter, Error};
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{header::AUTHORIZATION, HeaderValue, Request, Response};
+use opentelemetry::{
+    trace::{Link, SamplingResult, SpanKind, TraceId},
+    Context, KeyValue,
+};
+use opentelemetry_http::{HttpClient, HttpError};
+use opentelemetry_sdk::trace::{Sampler, ShouldSample};
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use remote::RemoteSampling;
+use std::sync::{Arc, PoisonError, RwLock};
+use tracing_core::Level;
+use tracing_subscriber::filter::LevelFilter;
+
+/// Changes the settings of a layer while it is running, e.g. to send more
+/// detail during an incident without restarting the service. Created with
+/// [`Builder::build_with_reload`](crate::Builder::build_with_reload).
+///
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
+///
+/// let (axiom_layer, reload) = tracing_axiom::builder_with_env("my-service")?
+///     .with_max_level(tracing::Level::INFO)
+///     .build_with_reload()?;
+/// tracing_subscriber::registry().with(axiom_layer).init();
+///
+/// // Later, e.g. from an admin endpoint
+/// reload.set_min_level(tracing::Level::DEBUG);
+/// reload.set_sample_ratio(0.1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ReloadHandle {
+    pub(crate) filter: SharedFilter,
+    pub(crate) sampler: ReloadableSampler,
+    pub(crate) token: SharedToken,
+}
+
+impl ReloadHandle {
+    /// Replaces the level or env filter, like
+    /// [`Builder::with_filter`](crate::Builder::with_filter). Targets included
+    /// or excluded when the layer was built still apply.
+    pub fn set_filter(&self, filter: impl Into<AxiomFilter>) {
+        self.filter.set(filter.into());
+    }
+
+    /// Only sends spans and events up to the given verbosity, like
+    /// [`Builder::with_max_level`](crate::Builder::with_max_level), e.g.
+    /// [`Level::DEBUG`] to include `DEBUG` but not `TRACE`.
+    pub fn set_min_level(&self, level: Level) {
+        self.set_filter(LevelFilter::from_level(level));
+    }
+
+    /// Samples the given ratio of new traces, between `0.0` and `1.0`. Spans
+    /// with a parent follow the sampling decision of the parent.
+    pub fn set_sample_ratio(&self, ratio: f64) {
+        self.sampler
+            .set(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                ratio,
+            ))));
+    }
+
+    /// Replaces the sampler, e.g. one set with the trace config.
+    pub fn set_sampler(&self, sampler: impl ShouldSample + 'static) {
+        self.sampler.set(sampler);
+    }
+
+    /// Replaces the Axiom API token, e.g. when it is rotated. Has no effect if
+    /// the spans are not sent to Axiom.
+    ///
+    /// # Errors
+    /// If the token is empty or does not start with `xaat-`.
+    pub fn set_token(&self, token: impl Into<String>) -> Result<(), Error> {
+        self.token.set(validate_token(token.into())?);
+        Ok(())
+    }
+}
+
+/// A sampler that can be replaced after the layer was built.
+#[derive(Debug, Clone)]
+pub(crate) struct ReloadableSampler(Arc<RwLock<Box<dyn ShouldSample>>>);
+
+impl Default for ReloadableSampler {
+    fn default() -> Self {
+        Self(Arc::new(RwLock::new(Box::new(Sampler::ParentBased(
+            Box::new(Sampler::AlwaysOn),
+        )))))
+    }
+}
+
+impl ReloadableSampler {
+    pub(crate) fn set(&self, sampler: impl ShouldSample + 'static) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Box::new(sampler);
+    }
+
+    pub(crate) fn set_boxed(&self, sampler: Box<dyn ShouldSample>) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = sampler;
+    }
+}
+
+impl ShouldSample for ReloadableSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .should_sample(parent_context, trace_id, name, span_kind, attributes, links)
+    }
+}
+
+/// The Axiom API token that can be replaced after the layer was built.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SharedToken(Arc<RwLock<String>>);
+
+impl SharedToken {
+    pub(crate) fn set(&self, token: String) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = token;
+    }
+
+    fn authorization(&self) -> Option<HeaderValue> {
+        let token = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        HeaderValue::from_str(&format!("Bearer {token}")).ok()
+    }
+}
+
+/// Sends the current token with every request.
+#[derive(Debug)]
+pub(crate) struct Authorize {
+    pub(crate) client: Arc<dyn HttpClient>,
+    pub(crate) token: SharedToken,
+}
+
+#[async_trait]
+impl HttpClient for Authorize {
+    async fn send(&self, mut request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+        if let Some(authorization) = self.token.authorization() {
+            request.headers_mut().insert(AUTHORIZATION, authorization);
+        }
+        self.client.send(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::SamplingDecision;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_reload_filter() {
+        let mut builder = crate::builder("reload")
+            .with_max_level(tracing::Level::INFO)
+            .with_exclude_targets(["noisy"]);
+        let reload = builder.reload_handle();
+        let (layer, spans) = builder.build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("before").in_scope(|| {});
+            reload.set_min_level(Level::DEBUG);
+            tracing::debug_span!("after").in_scope(|| {});
+            tracing::debug_span!(target: "noisy", "noisy").in_scope(|| {});
+        });
+        let names = spans
+            .finished_spans()
+            .into_iter()
+            .map(|span| span.name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["after"]);
+    }
+
+    #[test]
+    fn test_reload_sampler() {
+        let sampler = ReloadableSampler::default();
+        let decision = |sampler: &ReloadableSampler| {
+            sampler
+                .should_sample(
+                    None,
+                    TraceId::from(1),
+                    "span",
+                    &SpanKind::Internal,
+                    &[],
+                    &[],
+                )
+                .decision
+        };
+        assert_eq!(decision(&sampler), SamplingDecision::RecordAndSample);
+        let reload = ReloadHandle {
+            sampler: sampler.clone(),
+            ..ReloadHandle::default()
+        };
+        reload.set_sample_ratio(0.0);
+        assert_eq!(decision(&sampler), SamplingDecision::Drop);
+    }
+
+    #[test]
+    fn test_reload_sample_ratio() {
+        let mut builder = crate::builder("reload");
+        let reload = builder.reload_handle();
+        let (layer, spans) = builder.build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            reload.set_sample_ratio(0.0);
+            tracing::info_span!("dropped").in_scope(|| {});
+            reload.set_sample_ratio(1.0);
+            tracing::info_span!("sampled").in_scope(|| {});
+        });
+        let names = spans
+            .finished_spans()
+            .into_iter()
+            .map(|span| span.name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["sampled"]);
+    }
+
+    #[test]
+    fn test_set_token() {
+        let reload = ReloadHandle::default();
+        assert!(matches!(
+            reload.set_token("invalid"),
+            Err(Error::InvalidToken)
+        ));
+        assert!(reload.set_token("xaat-rotated").is_ok());
+        assert_eq!(
+            reload.token.authorization(),
+            Some(HeaderValue::from_static("Bearer xaat-rotated"))
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use super::ReloadableSampler;
+use crate::{diagnostics, filter::matches_target};
+use opentelemetry::{
+    trace::{Link, SamplingResult, SpanKind, TraceContextExt as _, TraceId},
+    Context, KeyValue,
+};
+use opentelemetry_http::HttpClient;
+use opentelemetry_sdk::trace::{Sampler, ShouldSample, Tracer};
+use reqwest::Url;
+use serde_json::Value;
+use std::{sync::Arc, time::Duration};
+use tracing_core::Level;
+
+/// The attribute with the module path of a span, added by the OpenTelemetry
+/// layer unless [`Builder::with_location`](crate::Builder::with_location) is
+/// disabled.
+const CODE_NAMESPACE: &str = "code.namespace";
+
+/// The attribute of errors recorded as fields, see
+/// [`FieldsLayer`](crate::FieldsLayer).
+const EXCEPTION_MESSAGE: &str = "exception.message";
+
+/// Periodically fetches [`SamplingRules`] and applies them to the sampler, see
+/// [`Builder::with_remote_sampling`](crate::Builder::with_remote_sampling).
+#[derive(Debug, Clone)]
+pub(crate) struct RemoteSampling {
+    pub(crate) url: Url,
+    pub(crate) interval: Duration,
+}
+
+impl RemoteSampling {
+    /// Applies the fetched rules every interval until the tracer provider is
+    /// dropped, which the tracer only holds a weak reference to.
+    pub(crate) async fn run(
+        self,
+        client: Arc<dyn HttpClient>,
+        sampler: ReloadableSampler,
+        tracer: Tracer,
+    ) {
+        while tracer.provider().is_some() {
+            match self.fetch(client.as_ref()).await {
+                Ok(rules) => {
+                    diagnostics::report(Level::DEBUG, &format!("applied sampling rules {rules:?}"));
+                    sampler.set(rules);
+                }
+                // The previous rules stay in place until the next fetch
+                Err(error) => diagnostics::report(
+                    Level::WARN,
+                    &format!(
+                        "fetching the sampling rules from {} failed: {error}",
+                        self.url
+                    ),
+                ),
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn fetch(&self, client: &dyn HttpClient) -> Result<SamplingRules, String> {
+        let request = http::Request::get(self.url.as_str())
+            .body(Vec::new())
+            .map_err(|error| error.to_string())?;
+        let response = client
+            .send(request)
+            .await
+            .map_err(|error| error.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("status {}", response.status()));
+        }
+        SamplingRules::parse(response.body())
+    }
+}
+
+/// Samples new traces by the ratio of the most specific rule that matches
+/// their root span, in the format documented on
+/// [`Builder::with_remote_sampling`](crate::Builder::with_remote_sampling).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SamplingRules {
+    ratio: f64,
+    targets: Vec<(String, f64)>,
+    errors: Option<f64>,
+}
+
+impl SamplingRules {
+    fn parse(json: &[u8]) -> Result<Self, String> {
+        let rules: Value = serde_json::from_slice(json).map_err(|error| error.to_string())?;
+        let ratio = |value: &Value, name: &str| {
+            value
+                .as_f64()
+                .filter(|ratio| (0.0..=1.0).contains(ratio))
+                .ok_or_else(|| format!("`{name}` must be a ratio between 0.0 and 1.0"))
+        };
+        let mut targets = match rules.get("targets") {
+            Some(Value::Object(targets)) => targets
+                .iter()
+                .map(|(target, value)| Ok((target.clone(), ratio(value, target)?)))
+                .collect::<Result<Vec<_>, String>>()?,
+            Some(_) => return Err("`targets` must be an object".to_string()),
+            None => Vec::new(),
+        };
+        // Longer targets are more specific
+        targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        Ok(Self {
+            ratio: rules
+                .get("ratio")
+                .map_or(Ok(1.0), |value| ratio(value, "ratio"))?,
+            targets,
+            errors: rules
+                .get("errors")
+                .map(|value| ratio(value, "errors"))
+                .transpose()?,
+        })
+    }
+
+    fn ratio(&self, attributes: &[KeyValue]) -> f64 {
+        let attribute = |key: &str| attributes.iter().find(|kv| kv.key.as_str() == key);
+        if let Some(errors) = self.errors {
+            if attribute(EXCEPTION_MESSAGE).is_some() {
+                return errors;
+            }
+        }
+        let Some(module) = attribute(CODE_NAMESPACE).map(|kv| kv.value.as_str()) else {
+            return self.ratio;
+        };
+        self.targets
+            .iter()
+            .find(|(target, _)| matches_target(target, &module))
+            .map_or(self.ratio, |(_, ratio)| *ratio)
+    }
+}
+
+impl ShouldSample for SamplingRules {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        let parent_sampled = parent_context
+            .filter(|cx| cx.has_active_span())
+            .map(|cx| cx.span().span_context().is_sampled());
+        let sampler = match parent_sampled {
+            Some(true) => Sampler::AlwaysOn,
+            Some(false) => Sampler::AlwaysOff,
+            None => Sampler::TraceIdRatioBased(self.ratio(attributes)),
+        };
+        sampler.should_sample(parent_context, trace_id, name, span_kind, attributes, links)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use http::{Request, Response};
+    use opentelemetry::trace::SamplingDecision;
+    use opentelemetry_http::HttpError;
+
+    #[test]
+    fn test_parse() {
+        let rules = SamplingRules::parse(
+            br#"{"ratio": 0.5, "targets": {"app": 0.1, "app::db": 0.0}, "errors": 1.0}"#,
+        )
+        .expect("valid rules");
+        assert_eq!(
+            rules,
+            SamplingRules {
+                ratio: 0.5,
+                targets: vec![("app::db".to_string(), 0.0), ("app".to_string(), 0.1)],
+                errors: Some(1.0),
+            }
+        );
+        assert_eq!(
+            SamplingRules::parse(b"{}"),
+            Ok(SamplingRules {
+                ratio: 1.0,
+                targets: Vec::new(),
+                errors: None,
+            })
+        );
+        assert!(SamplingRules::parse(br#"{"ratio": 2}"#).is_err());
+        assert!(SamplingRules::parse(br#"{"targets": {"app": "all"}}"#).is_err());
+        assert!(SamplingRules::parse(b"not json").is_err());
+    }
+
+    #[test]
+    fn test_ratio() {
+        let rules = SamplingRules::parse(
+            br#"{"ratio": 0.5, "targets": {"app": 0.1, "app::db": 0.0}, "errors": 1.0}"#,
+        )
+        .expect("valid rules");
+        let module = |module: &'static str| KeyValue::new(CODE_NAMESPACE, module);
+        assert!((rules.ratio(&[]) - 0.5).abs() < f64::EPSILON);
+        assert!((rules.ratio(&[module("app::http")]) - 0.1).abs() < f64::EPSILON);
+        assert!(rules.ratio(&[module("app::db::pool")]).abs() < f64::EPSILON);
+        assert!((rules.ratio(&[module("application")]) - 0.5).abs() < f64::EPSILON);
+        let error = KeyValue::new("exception.message", "timeout");
+        assert!((rules.ratio(&[module("app::db"), error]) - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_should_sample() {
+        let rules = SamplingRules::parse(br#"{"ratio": 0.0}"#).expect("valid rules");
+        let result = rules.should_sample(
+            None,
+            TraceId::from(1),
+            "span",
+            &SpanKind::Internal,
+            &[],
+            &[],
+        );
+        assert_eq!(result.decision, SamplingDecision::Drop);
+    }
+
+    struct Rules(&'static str);
+
+    #[async_trait]
+    impl HttpClient for Rules {
+        async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+            assert_eq!(request.uri(), "https://config.example.com/sampling");
+            Ok(Response::new(Bytes::from_static(self.0.as_bytes())))
+        }
+    }
+
+    impl std::fmt::Debug for Rules {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_tuple("Rules").finish()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let remote = RemoteSampling {
+            url: "https://config.example.com/sampling"
+                .parse()
+                .expect("valid URL"),
+            interval: Duration::from_secs(60),
+        };
+        let rules = remote
+            .fetch(&Rules(r#"{"ratio": 0.25}"#))
+            .await
+            .expect("valid rules");
+        assert!((rules.ratio - 0.25).abs() < f64::EPSILON);
+        assert!(remote.fetch(&Rules("[")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_with_provider() {
+        use opentelemetry::trace::TracerProvider as _;
+
+        let remote = RemoteSampling {
+            url: "https://config.example.com/sampling"
+                .parse()
+                .expect("valid URL"),
+            interval: Duration::from_millis(10),
+        };
+        let provider = opentelemetry_sdk::trace::TracerProvider::default();
+        let sampler = ReloadableSampler::default();
+        let run = tokio::spawn(remote.run(
+            Arc::new(Rules(r#"{"ratio": 0.25}"#)),
+            sampler,
+            provider.tracer("remote"),
+        ));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!run.is_finished());
+        drop(provider);
+        tokio::time::timeout(Duration::from_secs(1), run)
+            .await
+            .expect("stopped with the provider")
+            .expect("did not panic");
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{export::ExportPipeline, queue::Queue};
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{Request, Response, Uri};
+use opentelemetry_http::{HttpClient, HttpError};
+use opentelemetry_sdk::{runtime::RuntimeChannel, trace::SpanProcessor};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Mutex, PoisonError};
+use std::{fmt, sync::Arc};
+use tracing::{instrument::WithSubscriber as _, subscriber::NoSubscriber};
+
+type ProcessorFn = dyn FnOnce(ExportPipeline, Queue) -> Box<dyn SpanProcessor> + Send;
+
+/// The async runtime driving the batch span processor, erased so the builder
+/// does not need to be generic over it.
+pub(crate) struct BatchRuntime(Box<ProcessorFn>);
+
+impl BatchRuntime {
+    pub(crate) fn new<R: RuntimeChannel>(runtime: R) -> Self {
+        Self(Box::new(move |exporter, queue: Queue| {
+            Box::new(queue.processor(exporter, runtime))
+        }))
+    }
+
+    /// The batch span processor exporting on this runtime.
+    pub(crate) fn processor(
+        self,
+        exporter: ExportPipeline,
+        queue: Queue,
+    ) -> Box<dyn SpanProcessor> {
+        (self.0)(exporter, queue)
+    }
+}
+
+impl fmt::Debug for BatchRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchRuntime").finish_non_exhaustive()
+    }
+}
+
+/// A user provided HTTP client, shared so it can be handed to the exporter.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedHttpClient(pub(crate) Arc<dyn HttpClient>);
+
+#[async_trait]
+impl HttpClient for SharedHttpClient {
+    async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+        self.0.send(request).await
+    }
+}
+
+/// Sends every request to the URI instead of the one the exporter built, see
+/// [`Builder::with_traces_path`](crate::Builder::with_traces_path).
+#[derive(Debug)]
+pub(crate) struct FixedEndpoint {
+    pub(crate) client: Arc<dyn HttpClient>,
+    pub(crate) uri: Uri,
+}
+
+#[async_trait]
+impl HttpClient for FixedEndpoint {
+    async fn send(&self, mut request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+        *request.uri_mut() = self.uri.clone();
+        self.client.send(request).await
+    }
+}
+
+/// Sends requests without a tracing subscriber, so spans of the HTTP client
+/// itself never reach the exporter again.
+#[derive(Debug)]
+pub(crate) struct SuppressTracing(pub(crate) Arc<dyn HttpClient>);
+
+#[async_trait]
+impl HttpClient for SuppressTracing {
+    async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+        self.0
+            .send(request)
+            .with_subscriber(NoSubscriber::default())
+            .await
+    }
+}
+
+/// Exports with a blocking [`reqwest`] client on the blocking thread pool of
+/// `async-std`, as the async [`reqwest::Client`] only works within tokio.
+#[cfg(feature = "async-std")]
+#[derive(Debug, Clone)]
+pub(crate) struct AsyncStdClient(reqwest::blocking::Client);
+
+#[cfg(feature = "async-std")]
+impl AsyncStdClient {
+    pub(crate) fn new() -> Self {
+        Self(reqwest::blocking::Client::new())
+    }
+}
+
+#[cfg(feature = "async-std")]
+#[async_trait]
+impl HttpClient for AsyncStdClient {
+    async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+        let client = self.0.clone();
+        async_std::task::spawn_blocking(move || {
+            let request = request.try_into()?;
+            let mut response = client.execute(request)?.error_for_status()?;
+            let headers = std::mem::take(response.headers_mut());
+            let mut http_response = Response::builder()
+                .status(response.status())
+                .body(response.bytes()?)?;
+            *http_response.headers_mut() = headers;
+            Ok(http_response)
+        })
+        .await
+    }
+}
+
+/// Drives the batch span processor on a private current-thread tokio runtime
+/// in a background thread, for applications that do not run within tokio.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub(crate) struct BackgroundRuntime(tokio::runtime::Handle);
+
+/// The runtime shared by everything built outside of tokio.
+#[cfg(not(target_arch = "wasm32"))]
+static BACKGROUND_RUNTIME: Mutex<Option<BackgroundRuntime>> = Mutex::new(None);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl BackgroundRuntime {
+    /// The runtime shared by all layers, started in its thread the first time
+    /// it is needed, so building layers repeatedly doesn't start more threads.
+    pub(crate) fn shared() -> Result<Self, crate::Error> {
+        let mut shared = BACKGROUND_RUNTIME
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(runtime) = &*shared {
+            return Ok(runtime.clone());
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let handle = runtime.handle().clone();
+        std::thread::Builder::new()
+            .name("tracing-axiom".to_string())
+            .spawn(move || runtime.block_on(std::future::pending::<()>()))?;
+        Ok(shared.insert(Self(handle)).clone())
+    }
+
+    pub(crate) fn handle(&self) -> &tokio::runtime::Handle {
+        &self.0
+    }
+}
+
+// Timers must be created within the runtime, so the handle is entered for them.
+#[cfg(not(target_arch = "wasm32"))]
+impl opentelemetry_sdk::runtime::Runtime for BackgroundRuntime {
+    type Interval = tokio_stream::wrappers::IntervalStream;
+    type Delay = std::pin::Pin<Box<tokio::time::Sleep>>;
+
+    fn interval(&self, duration: std::time::Duration) -> Self::Interval {
+        let _guard = self.0.enter();
+        tokio_stream::wrappers::IntervalStream::new(tokio::time::interval(duration))
+    }
+
+    fn spawn(
+        &self,
+        future: std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send + 'static>>,
+    ) {
+        drop(self.0.spawn(future));
+    }
+
+    fn delay(&self, duration: std::time::Duration) -> Self::Delay {
+        let _guard = self.0.enter();
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RuntimeChannel for BackgroundRuntime {
+    type Receiver<T: fmt::Debug + Send> = tokio_stream::wrappers::ReceiverStream<T>;
+    type Sender<T: fmt::Debug + Send> = tokio::sync::mpsc::Sender<T>;
+
+    fn batch_message_channel<T: fmt::Debug + Send>(
+        &self,
+        capacity: usize,
+    ) -> (Self::Sender<T>, Self::Receiver<T>) {
+        let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+        (
+            sender,
+            tokio_stream::wrappers::ReceiverStream::new(receiver),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::{dispatcher, Dispatch};
+
+    #[derive(Debug)]
+    struct AssertNoSubscriber;
+
+    #[async_trait]
+    impl HttpClient for AssertNoSubscriber {
+        async fn send(&self, _request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+            assert!(dispatcher::get_default(Dispatch::is::<NoSubscriber>));
+            Ok(Response::new(Bytes::new()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_suppress_tracing() {
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry());
+        let client = SuppressTracing(Arc::new(AssertNoSubscriber));
+        assert!(client.send(Request::new(Vec::new())).await.is_ok());
+        assert!(dispatcher::get_default(|d| !d.is::<NoSubscriber>()));
+    }
+}
@@ -0,0 +1,103 @@
+use opentelemetry::{
+    trace::{Link, SamplingDecision, SamplingResult, SpanKind, TraceContextExt as _, TraceId},
+    Context, KeyValue, Value,
+};
+use opentelemetry_sdk::trace::ShouldSample;
+
+/// The span field that forces keeping (`true`) or dropping (`false`) the
+/// trace, overriding the sampler.
+const SAMPLE: &str = "axiom.sample";
+/// The trace state key the forced decision is passed on to child spans with,
+/// in this and downstream services.
+const TRACE_STATE_KEY: &str = "axiom";
+
+/// The decision forced by the `axiom.sample` attribute, if any.
+pub(crate) fn forced(attributes: &[KeyValue]) -> Option<bool> {
+    attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == SAMPLE)
+        .and_then(|kv| match &kv.value {
+            Value::Bool(keep) => Some(*keep),
+            _ => None,
+        })
+}
+
+/// Keeps or drops traces with the `axiom.sample` field regardless of the
+/// sampler it wraps, see [`Builder`](crate::Builder).
+#[derive(Debug, Clone)]
+pub(crate) struct ForcedSampling(pub(crate) Box<dyn ShouldSample>);
+
+impl ShouldSample for ForcedSampling {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        let parent_state = parent_context
+            .filter(|cx| cx.has_active_span())
+            .map(|cx| cx.span().span_context().trace_state().clone());
+        let inherited = parent_state
+            .as_ref()
+            .and_then(|state| match state.get(TRACE_STATE_KEY) {
+                Some("keep") => Some(true),
+                Some("drop") => Some(false),
+                _ => None,
+            });
+        let Some(keep) = forced(attributes).or(inherited) else {
+            return self.0.should_sample(
+                parent_context,
+                trace_id,
+                name,
+                span_kind,
+                attributes,
+                links,
+            );
+        };
+        let parent_state = parent_state.unwrap_or_default();
+        let trace_state = parent_state
+            .insert(TRACE_STATE_KEY, if keep { "keep" } else { "drop" })
+            .unwrap_or(parent_state);
+        SamplingResult {
+            decision: if keep {
+                SamplingDecision::RecordAndSample
+            } else {
+                SamplingDecision::Drop
+            },
+            attributes: Vec::new(),
+            trace_state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_sdk::trace::{config, Sampler};
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_forced_sampling() {
+        let (layer, spans) = crate::builder("sampling")
+            .with_trace_config(config().with_sampler(Sampler::AlwaysOff))
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("debug session", axiom.sample = true).in_scope(|| {
+                tracing::info_span!("child").in_scope(|| {});
+            });
+            tracing::info_span!("regular").in_scope(|| {});
+        });
+
+        let mut names = spans
+            .finished_spans()
+            .into_iter()
+            .map(|span| span.name.to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["child", "debug session"]);
+    }
+}
@@ -0,0 +1,217 @@
+//! Helpers for spans with the attribute names of the `OpenTelemetry` semantic
+//! conventions, so every service records e.g. `http.request.method` instead
+//! of a mix of `http.method`, `method` and `verb`.
+//!
+//! ```rust
+//! use tracing_axiom::{http_server_span, semconv::record_response};
+//!
+//! let span = http_server_span!("GET", "/users/:id");
+//! let _guard = span.enter();
+//! // Handle the request
+//! record_response(&span, 200);
+//! ```
+//!
+//! Database calls get client spans with [`db_span!`](crate::db_span):
+//!
+//! ```rust
+//! use tracing_axiom::{db_span, semconv::sanitize_statement};
+//!
+//! let sql = "SELECT * FROM users WHERE email = 'jane@example.com'";
+//! let span = db_span!("postgresql", &sanitize_statement(sql));
+//! ```
+
+use tracing::Span;
+
+/// Creates a server span for an incoming HTTP request with the attributes of
+/// the semantic conventions. The span is named `{method} {route}`, with the
+/// route template rather than the path to keep the number of span names low,
+/// and has the target of the module it is created in.
+///
+/// `url.path`, `http.response.status_code` and the status are left empty, to be
+/// recorded later, the latter two with [`record_response`].
+///
+/// ```rust
+/// let span = tracing_axiom::http_server_span!("POST", "/orders");
+/// span.record("url.path", "/orders");
+/// ```
+#[macro_export]
+macro_rules! http_server_span {
+    ($method:expr, $route:expr) => {{
+        let method: &str = $method;
+        let route: &str = $route;
+        $crate::__private::tracing::info_span!(
+            "HTTP request",
+            otel.name = format!("{method} {route}"),
+            otel.kind = "server",
+            otel.status_code = $crate::__private::tracing::field::Empty,
+            otel.status_description = $crate::__private::tracing::field::Empty,
+            http.request.method = method,
+            http.route = route,
+            http.response.status_code = $crate::__private::tracing::field::Empty,
+            url.path = $crate::__private::tracing::field::Empty,
+        )
+    }};
+}
+
+/// Records the response status on a span created by
+/// [`http_server_span!`](crate::http_server_span), a server error (5xx) marks
+/// the span as failed.
+pub fn record_response(span: &Span, status: u16) {
+    span.record("http.response.status_code", status);
+    if status >= 500 {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+/// Creates a client span for a database call with the attributes of the
+/// semantic conventions, `db.system`, `db.statement` and `db.operation`. The
+/// span is named after the operation, the first keyword of the statement like
+/// `SELECT`, or the system if there is none.
+///
+/// The statement is recorded as it is, use [`sanitize_statement`] to strip
+/// literals that may contain personal data:
+///
+/// ```rust
+/// use tracing_axiom::semconv::sanitize_statement;
+///
+/// let span = tracing_axiom::db_span!("mysql", &sanitize_statement("DELETE FROM carts WHERE id = 7"));
+/// ```
+#[macro_export]
+macro_rules! db_span {
+    ($system:expr, $statement:expr) => {{
+        let system: &str = $system;
+        let statement: &str = $statement;
+        let operation = $crate::semconv::db_operation(statement);
+        $crate::__private::tracing::info_span!(
+            "DB query",
+            otel.name = operation.unwrap_or(system),
+            otel.kind = "client",
+            otel.status_code = $crate::__private::tracing::field::Empty,
+            otel.status_description = $crate::__private::tracing::field::Empty,
+            db.system = system,
+            db.statement = statement,
+            db.operation = operation,
+        )
+    }};
+}
+
+/// The operation of a statement, its first word if that is a keyword like
+/// `SELECT` or `INSERT`.
+#[must_use]
+pub fn db_operation(statement: &str) -> Option<&str> {
+    statement
+        .split_whitespace()
+        .next()
+        .filter(|word| word.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Replaces the string and number literals of a SQL statement with `?`, so
+/// values such as emails or ids are not exported. Identifiers, including the
+/// digits in them, and placeholders like `$1` are kept.
+///
+/// ```rust
+/// use tracing_axiom::semconv::sanitize_statement;
+///
+/// assert_eq!(
+///     sanitize_statement("SELECT * FROM t1 WHERE name = 'O''Brien' AND age > 42 AND id = $1"),
+///     "SELECT * FROM t1 WHERE name = ? AND age > ? AND id = $1"
+/// );
+/// ```
+#[must_use]
+pub fn sanitize_statement(statement: &str) -> String {
+    let mut sanitized = String::with_capacity(statement.len());
+    let mut chars = statement.chars().peekable();
+    let mut previous = None;
+    while let Some(c) = chars.next() {
+        let in_word = previous.is_some_and(|p: char| p.is_alphanumeric() || p == '_' || p == '$');
+        if c == '\'' {
+            // `''` is an escaped quote within the literal
+            loop {
+                match chars.next() {
+                    Some('\'') if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                    }
+                    Some('\'') | None => break,
+                    Some(_) => {}
+                }
+            }
+            sanitized.push('?');
+        } else if c.is_ascii_digit() && !in_word {
+            while chars.next_if(|c| c.is_ascii_digit() || *c == '.').is_some() {}
+            sanitized.push('?');
+        } else {
+            sanitized.push(c);
+        }
+        previous = Some(c);
+    }
+    sanitized
+}
+
+#[cfg(all(test, not(feature = "noop")))]
+mod tests {
+    use super::*;
+    use crate::testing::attribute;
+    use opentelemetry::{trace::Status, Value};
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_http_server_span() {
+        let (layer, spans) = crate::builder("semconv").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = crate::http_server_span!("GET", "/users/:id");
+            span.record("url.path", "/users/42");
+            record_response(&span, 503);
+        });
+
+        let span = spans.span("GET /users/:id").expect("span was recorded");
+        assert_eq!(
+            attribute(&span, "http.request.method"),
+            Some(&Value::from("GET"))
+        );
+        assert_eq!(
+            attribute(&span, "http.route"),
+            Some(&Value::from("/users/:id"))
+        );
+        assert_eq!(
+            attribute(&span, "url.path"),
+            Some(&Value::from("/users/42"))
+        );
+        assert_eq!(
+            attribute(&span, "http.response.status_code"),
+            Some(&Value::I64(503))
+        );
+        assert!(matches!(span.status, Status::Error { .. }));
+    }
+
+    #[test]
+    fn test_db_span() {
+        let (layer, spans) = crate::builder("semconv").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            crate::db_span!("postgresql", "UPDATE users SET name = $1").in_scope(|| {});
+            crate::db_span!("redis", "-- comment").in_scope(|| {});
+        });
+
+        let span = spans.span("UPDATE").expect("span was recorded");
+        assert_eq!(
+            attribute(&span, "db.system"),
+            Some(&Value::from("postgresql"))
+        );
+        assert_eq!(
+            attribute(&span, "db.operation"),
+            Some(&Value::from("UPDATE"))
+        );
+        let span = spans.span("redis").expect("span named after the system");
+        assert_eq!(attribute(&span, "db.operation"), None);
+    }
+
+    #[test]
+    fn test_sanitize_statement() {
+        assert_eq!(
+            sanitize_statement("INSERT INTO logs_2024 VALUES ('it''s', -1.5, x'00')"),
+            "INSERT INTO logs_2024 VALUES (?, -?, x?)"
+        );
+        assert_eq!(sanitize_statement("SELECT 'unterminated"), "SELECT ?");
+    }
+}
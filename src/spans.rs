@@ -0,0 +1,89 @@
+//! Spans following the `OpenTelemetry` semantic conventions.
+
+use std::fmt::Display;
+use tracing::{field::Empty, Span};
+
+/// Creates a server span for an incoming HTTP request. The span is named
+/// `{method} {route}`, or just `{method}` when the route is unknown, as the raw
+/// path would lead to high cardinality span names.
+pub(crate) fn http_server_span(method: &str, route: Option<&str>, path: &str) -> Span {
+    let name = match route {
+        Some(route) => format!("{method} {route}"),
+        None => method.to_string(),
+    };
+    tracing::info_span!(
+        "HTTP request",
+        otel.name = name,
+        otel.kind = "server",
+        otel.status_code = Empty,
+        otel.status_description = Empty,
+        http.request.method = method,
+        http.route = route,
+        http.response.status_code = Empty,
+        url.path = path,
+    )
+}
+
+/// Records the response status on a span created by [`http_server_span`], a
+/// server error (5xx) marks the span as failed.
+pub(crate) fn record_http_status(span: &Span, status: u16) {
+    crate::semconv::record_response(span, status);
+}
+
+/// Creates a client span for an outgoing HTTP request, named `{method}`.
+#[cfg(feature = "reqwest-middleware")]
+pub(crate) fn http_client_span(method: &str, url: &str, host: Option<&str>) -> Span {
+    tracing::info_span!(
+        "HTTP request",
+        otel.name = method,
+        otel.kind = "client",
+        otel.status_code = Empty,
+        otel.status_description = Empty,
+        http.request.method = method,
+        http.response.status_code = Empty,
+        url.full = url,
+        server.address = host,
+    )
+}
+
+/// Records the response status on a span created by [`http_client_span`], any
+/// error status (4xx and 5xx) marks the span as failed.
+#[cfg(feature = "reqwest-middleware")]
+pub(crate) fn record_http_client_status(span: &Span, status: u16) {
+    span.record("http.response.status_code", status);
+    if status >= 400 {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+/// Marks the span as failed with the error as status description.
+pub(crate) fn record_error(span: &Span, error: &dyn Display) {
+    span.record("otel.status_code", "ERROR");
+    span.record("otel.status_description", tracing::field::display(error));
+}
+
+/// Creates a server span for an incoming gRPC request, named `{service}/{method}`.
+#[cfg(feature = "tonic")]
+pub(crate) fn grpc_server_span(service: &str, method: &str) -> Span {
+    tracing::info_span!(
+        "gRPC request",
+        otel.name = format!("{service}/{method}"),
+        otel.kind = "server",
+        otel.status_code = Empty,
+        otel.status_description = Empty,
+        rpc.system = "grpc",
+        rpc.service = service,
+        rpc.method = method,
+        rpc.grpc.status_code = Empty,
+    )
+}
+
+/// Records the gRPC status code on a span created by [`grpc_server_span`], any
+/// code other than `OK` (0) marks the span as failed.
+#[cfg(feature = "tonic")]
+pub(crate) fn record_grpc_status(span: &Span, code: i32) {
+    span.record("rpc.grpc.status_code", code);
+    if code != 0 {
+        span.record("otel.status_code", "ERROR");
+    }
+}
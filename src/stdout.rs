@@ -0,0 +1,145 @@
+//! Dry-run exporter that prints spans as JSON lines to stdout instead of
+//! sending them to Axiom.
+
+use crate::export::json_value;
+use opentelemetry::{
+    trace::{SpanKind, Status, TraceError},
+    KeyValue,
+};
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use serde_json::{json, Map};
+use std::{
+    future::Future,
+    io::Write as _,
+    pin::Pin,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Prints one JSON object per span with the attribute names as they arrive in
+/// Axiom, so missing or unexpectedly nested fields can be debugged locally.
+#[derive(Debug, Default)]
+pub(crate) struct StdoutExporter;
+
+impl SpanExporter for StdoutExporter {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        let mut stdout = std::io::stdout().lock();
+        let result = batch
+            .iter()
+            .try_for_each(|span| writeln!(stdout, "{}", to_json(span)))
+            .map_err(|e| TraceError::Other(Box::new(e)));
+        Box::pin(std::future::ready(result))
+    }
+}
+
+pub(crate) fn to_json(span: &SpanData) -> serde_json::Value {
+    let (status_code, status_message) = match &span.status {
+        Status::Unset => ("UNSET", None),
+        Status::Ok => ("OK", None),
+        Status::Error { description } => ("ERROR", Some(description.to_string())),
+    };
+    json!({
+        "start_time_unix_nano": unix_nanos(span.start_time),
+        "end_time_unix_nano": unix_nanos(span.end_time),
+        "trace_id": span.span_context.trace_id().to_string(),
+        "span_id": span.span_context.span_id().to_string(),
+        "parent_span_id": span.parent_span_id.to_string(),
+        "name": span.name,
+        "kind": kind(&span.span_kind),
+        "status": { "code": status_code, "message": status_message },
+        "attributes": attributes(&span.attributes),
+        "events": span.events.iter().map(|event| json!({
+            "time_unix_nano": unix_nanos(event.timestamp),
+            "name": event.name,
+            "attributes": attributes(&event.attributes),
+        })).collect::<Vec<_>>(),
+        "links": span.links.iter().map(|link| json!({
+            "trace_id": link.span_context.trace_id().to_string(),
+            "span_id": link.span_context.span_id().to_string(),
+            "attributes": attributes(&link.attributes),
+        })).collect::<Vec<_>>(),
+        "resource": span.resource.iter().map(|(k, v)| (k.to_string(), json_value(v))).collect::<Map<_, _>>(),
+        "scope": {
+            "name": span.instrumentation_lib.name,
+            "version": span.instrumentation_lib.version,
+        },
+    })
+}
+
+fn kind(kind: &SpanKind) -> &'static str {
+    match kind {
+        SpanKind::Client => "client",
+        SpanKind::Server => "server",
+        SpanKind::Producer => "producer",
+        SpanKind::Consumer => "consumer",
+        SpanKind::Internal => "internal",
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+}
+
+fn attributes(attributes: &[KeyValue]) -> Map<String, serde_json::Value> {
+    attributes
+        .iter()
+        .map(|kv| (kv.key.to_string(), json_value(&kv.value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::{
+        trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState},
+        Array, StringValue, Value,
+    };
+    use opentelemetry_sdk::{trace::SpanEvents, Resource};
+    use std::{borrow::Cow, time::Duration};
+
+    #[test]
+    fn test_to_json() {
+        let start_time = UNIX_EPOCH + Duration::from_secs(1);
+        let span = SpanData {
+            span_context: SpanContext::new(
+                TraceId::from_bytes(1u128.to_be_bytes()),
+                SpanId::from_bytes(2u64.to_be_bytes()),
+                TraceFlags::SAMPLED,
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: SpanId::INVALID,
+            span_kind: SpanKind::Server,
+            name: "GET /users".into(),
+            start_time,
+            end_time: start_time + Duration::from_millis(5),
+            attributes: vec![
+                KeyValue::new("http.response.status_code", 500),
+                KeyValue::new(
+                    "user.roles",
+                    Value::Array(Array::String(vec![StringValue::from("a")])),
+                ),
+            ],
+            dropped_attributes_count: 0,
+            events: SpanEvents::default(),
+            links: opentelemetry_sdk::trace::SpanLinks::default(),
+            status: Status::error("boom"),
+            resource: Cow::Owned(Resource::new(vec![KeyValue::new("service.name", "api")])),
+            instrumentation_lib: opentelemetry_sdk::InstrumentationLibrary::default(),
+        };
+
+        let json = to_json(&span);
+        assert_eq!(json["trace_id"], "00000000000000000000000000000001");
+        assert_eq!(json["kind"], "server");
+        assert_eq!(json["end_time_unix_nano"], 1_005_000_000);
+        assert_eq!(json["status"]["code"], "ERROR");
+        assert_eq!(json["status"]["message"], "boom");
+        assert_eq!(json["attributes"]["http.response.status_code"], 500);
+        assert_eq!(json["attributes"]["user.roles"], json!(["a"]));
+        assert_eq!(json["resource"]["service.name"], "api");
+    }
+}
@@ -0,0 +1,157 @@
+use opentelemetry::{
+    metrics::{MeterProvider as _, Unit},
+    KeyValue,
+};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use std::sync::{Arc, Mutex, PoisonError};
+use sysinfo::{Disks, System};
+
+const STATE: &str = "state";
+
+/// Registers gauges of the CPU, memory and disk usage of the host that are
+/// read whenever the provider exports, see
+/// [`Builder::with_system_metrics`](crate::Builder::with_system_metrics).
+pub(crate) fn register(provider: &SdkMeterProvider) {
+    let meter = provider.versioned_meter(
+        env!("CARGO_PKG_NAME"),
+        Some(env!("CARGO_PKG_VERSION")),
+        None::<&'static str>,
+        None,
+    );
+    let system = Arc::new(Mutex::new(System::new()));
+
+    let cpu = system.clone();
+    meter
+        .f64_observable_gauge("system.cpu.utilization")
+        .with_unit(Unit::new("1"))
+        .with_description("The CPU usage of all cores since the last export, between 0 and 1")
+        .with_callback(move |gauge| {
+            let mut cpu = cpu.lock().unwrap_or_else(PoisonError::into_inner);
+            cpu.refresh_cpu();
+            gauge.observe(f64::from(cpu.global_cpu_info().cpu_usage()) / 100.0, &[]);
+        })
+        .init();
+
+    meter
+        .u64_observable_gauge("system.memory.usage")
+        .with_unit(Unit::new("By"))
+        .with_description("The used and free memory")
+        .with_callback(move |gauge| {
+            let mut memory = system.lock().unwrap_or_else(PoisonError::into_inner);
+            memory.refresh_memory();
+            gauge.observe(memory.used_memory(), &[KeyValue::new(STATE, "used")]);
+            gauge.observe(memory.free_memory(), &[KeyValue::new(STATE, "free")]);
+        })
+        .init();
+
+    let disks = Mutex::new(Disks::new());
+    meter
+        .u64_observable_gauge("system.filesystem.usage")
+        .with_unit(Unit::new("By"))
+        .with_description("The used and free space of every mounted filesystem")
+        .with_callback(move |gauge| {
+            let mut disks = disks.lock().unwrap_or_else(PoisonError::into_inner);
+            // Picks up filesystems that were mounted since the last export
+            disks.refresh_list();
+            for disk in disks.list() {
+                let mountpoint = KeyValue::new(
+                    "system.filesystem.mountpoint",
+                    disk.mount_point().display().to_string(),
+                );
+                let free = disk.available_space();
+                gauge.observe(
+                    disk.total_space().saturating_sub(free),
+                    &[mountpoint.clone(), KeyValue::new(STATE, "used")],
+                );
+                gauge.observe(free, &[mountpoint, KeyValue::new(STATE, "free")]);
+            }
+        })
+        .init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::metrics::Result;
+    use opentelemetry_sdk::{
+        metrics::{
+            data::{ResourceMetrics, Temporality},
+            reader::{AggregationSelector, MetricReader, TemporalitySelector},
+            Aggregation, InstrumentKind, ManualReader, Pipeline,
+        },
+        Resource,
+    };
+    use std::sync::Weak;
+
+    /// A manual reader shared with the provider, to collect the gauges.
+    #[derive(Debug, Clone)]
+    struct SharedReader(Arc<ManualReader>);
+
+    impl TemporalitySelector for SharedReader {
+        fn temporality(&self, kind: InstrumentKind) -> Temporality {
+            self.0.temporality(kind)
+        }
+    }
+
+    impl AggregationSelector for SharedReader {
+        fn aggregation(&self, kind: InstrumentKind) -> Aggregation {
+            self.0.aggregation(kind)
+        }
+    }
+
+    impl MetricReader for SharedReader {
+        fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+            self.0.register_pipeline(pipeline);
+        }
+
+        fn collect(&self, metrics: &mut ResourceMetrics) -> Result<()> {
+            self.0.collect(metrics)
+        }
+
+        fn force_flush(&self) -> Result<()> {
+            self.0.force_flush()
+        }
+
+        fn shutdown(&self) -> Result<()> {
+            self.0.shutdown()
+        }
+    }
+
+    #[test]
+    fn test_register() -> Result<()> {
+        let reader = SharedReader(Arc::new(ManualReader::builder().build()));
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        register(&provider);
+
+        let mut metrics = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: Vec::new(),
+        };
+        reader.collect(&mut metrics)?;
+        let mut gauges = metrics
+            .scope_metrics
+            .iter()
+            .flat_map(|scope| &scope.metrics)
+            .map(|metric| (metric.name.to_string(), metric.unit.as_str().to_string()))
+            .collect::<Vec<_>>();
+        gauges.sort();
+        let mut expected = vec![
+            ("system.cpu.utilization", "1"),
+            ("system.memory.usage", "By"),
+        ];
+        // Gauges without observations are not collected, e.g. in containers without mounts
+        if !Disks::new_with_refreshed_list().list().is_empty() {
+            expected.insert(1, ("system.filesystem.usage", "By"));
+        }
+        assert_eq!(
+            gauges,
+            expected
+                .into_iter()
+                .map(|(name, unit)| (name.to_string(), unit.to_string()))
+                .collect::<Vec<_>>()
+        );
+        provider.shutdown()
+    }
+}
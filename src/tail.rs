@@ -0,0 +1,448 @@
+use crate::{fields::is_start_record, sampling::forced};
+use opentelemetry::{
+    trace::{Span as _, SpanId, Status, TraceContextExt as _, TraceId, TraceResult},
+    Context,
+};
+use opentelemetry_sdk::{
+    export::trace::SpanData,
+    trace::{Span, SpanProcessor},
+};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+/// The number of traces buffered until they end, once more are pending the
+/// decision is made for all of them with the spans that ended so far.
+const MAX_PENDING: usize = 4096;
+/// How often the traces that waited longer than the limit are looked for.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Which traces are kept by the [`TailSampler`], a trace matching any of the
+/// rules is kept unless the `axiom.sample` field forces the decision.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct TailPolicy {
+    /// Keep traces with a failed span, see
+    /// [`Builder::errors_only`](crate::Builder::errors_only).
+    pub(crate) errors: bool,
+    /// Keep traces whose root span took at least this long, see
+    /// [`Builder::with_latency_sampling`](crate::Builder::with_latency_sampling).
+    pub(crate) min_duration: Option<Duration>,
+    /// The ratio of the remaining traces to keep.
+    pub(crate) ratio: f64,
+}
+
+impl TailPolicy {
+    /// Whether to keep the spans of the trace, `root` is missing if the trace
+    /// didn't end.
+    fn keep(self, spans: &[SpanData], root: Option<&SpanData>) -> bool {
+        if let Some(keep) = spans
+            .iter()
+            .chain(root)
+            .find_map(|span| forced(&span.attributes))
+        {
+            return keep;
+        }
+        let failed = || {
+            spans
+                .iter()
+                .chain(root)
+                .any(|span| matches!(span.status, Status::Error { .. }))
+        };
+        let slow = |min_duration| {
+            root.is_some_and(|root| {
+                root.end_time
+                    .duration_since(root.start_time)
+                    .is_ok_and(|duration| duration >= min_duration)
+            })
+        };
+        let trace_id = spans
+            .iter()
+            .chain(root)
+            .next()
+            .map(|span| span.span_context.trace_id());
+        (self.errors && failed())
+            || self.min_duration.is_some_and(slow)
+            || trace_id.is_some_and(|trace_id| sampled(trace_id, self.ratio))
+    }
+}
+
+/// How long and how many spans of a trace the [`TailSampler`] buffers before
+/// the decision is made with the spans that ended so far, see
+/// [`Builder::with_tail_sampling_limits`](crate::Builder::with_tail_sampling_limits).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TailLimits {
+    pub(crate) max_wait: Duration,
+    pub(crate) max_spans: usize,
+}
+
+impl Default for TailLimits {
+    fn default() -> Self {
+        Self {
+            max_wait: Duration::from_secs(300),
+            max_spans: 10_000,
+        }
+    }
+}
+
+/// Whether the trace is within the ratio, decided like the `TraceIdRatioBased`
+/// sampler so all services of a trace agree.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn sampled(trace_id: TraceId, ratio: f64) -> bool {
+    if ratio >= 1.0 {
+        return true;
+    }
+    let upper_bound = (ratio.max(0.0) * (1_u64 << 63) as f64) as u64;
+    let random = (u128::from_be_bytes(trace_id.to_bytes()) as u64) >> 1;
+    random < upper_bound
+}
+
+/// Buffers the spans of every trace until its local root span ends and only
+/// hands the traces the policy keeps to the processor that exports them,
+/// without a policy all of them.
+///
+/// A trace that buffered too many spans or waited too long, see [`TailLimits`],
+/// is decided with the spans that ended so far and its later spans are
+/// buffered again.
+#[derive(Debug)]
+pub(crate) struct TailSampler {
+    processor: Box<dyn SpanProcessor>,
+    policy: Option<TailPolicy>,
+    limits: TailLimits,
+    traces: Mutex<Traces>,
+}
+
+#[derive(Debug, Default)]
+struct Traces {
+    pending: HashMap<TraceId, Pending>,
+    /// The spans started without a parent in this process, with when they
+    /// started.
+    local_roots: HashMap<SpanId, SystemTime>,
+    /// When the traces that waited too long were last looked for.
+    last_sweep: Option<SystemTime>,
+}
+
+/// The spans of a trace that ended before its local root span.
+#[derive(Debug)]
+struct Pending {
+    since: SystemTime,
+    spans: Vec<SpanData>,
+}
+
+impl Traces {
+    /// Removes the traces that waited at least `max_wait` and forgets the
+    /// local root spans running that long, at most once per sweep interval.
+    fn take_expired(&mut self, now: SystemTime, max_wait: Duration) -> Vec<Vec<SpanData>> {
+        let elapsed = |since: SystemTime| now.duration_since(since).unwrap_or_default();
+        if self
+            .last_sweep
+            .is_some_and(|last_sweep| elapsed(last_sweep) < SWEEP_INTERVAL)
+        {
+            return Vec::new();
+        }
+        self.last_sweep = Some(now);
+        self.local_roots
+            .retain(|_, started| elapsed(*started) < max_wait);
+        let expired = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| elapsed(pending.since) >= max_wait)
+            .map(|(trace_id, _)| *trace_id)
+            .collect::<Vec<_>>();
+        expired
+            .iter()
+            .filter_map(|trace_id| self.pending.remove(trace_id))
+            .map(|pending| pending.spans)
+            .collect()
+    }
+}
+
+impl TailSampler {
+    pub(crate) fn new(
+        processor: Box<dyn SpanProcessor>,
+        policy: Option<TailPolicy>,
+        limits: TailLimits,
+    ) -> Self {
+        Self {
+            processor,
+            policy,
+            limits,
+            traces: Mutex::default(),
+        }
+    }
+
+    fn decide(&self, spans: Vec<SpanData>, root: Option<SpanData>) {
+        if self
+            .policy
+            .map_or(true, |policy| policy.keep(&spans, root.as_ref()))
+        {
+            for span in spans.into_iter().chain(root) {
+                self.processor.on_end(span);
+            }
+        }
+    }
+
+    fn decide_pending(&self) {
+        let pending = std::mem::take(
+            &mut self
+                .traces
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pending,
+        );
+        for pending in pending.into_values() {
+            self.decide(pending.spans, None);
+        }
+    }
+}
+
+impl SpanProcessor for TailSampler {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        let parent = cx.span();
+        let parent = parent.span_context();
+        if !parent.is_valid() || parent.is_remote() {
+            self.traces
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .local_roots
+                .insert(span.span_context().span_id(), opentelemetry::time::now());
+        }
+        self.processor.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        let now = opentelemetry::time::now();
+        let mut traces = self.traces.lock().unwrap_or_else(PoisonError::into_inner);
+        let trace_id = span.span_context.trace_id();
+        // Root spans running longer than the limit were forgotten, but the ones
+        // without a parent are still known. The start record of a root span
+        // is held back with the rest of its trace.
+        let root = !is_start_record(&span)
+            && (traces
+                .local_roots
+                .remove(&span.span_context.span_id())
+                .is_some()
+                || span.parent_span_id == SpanId::INVALID);
+        let mut decided = Vec::new();
+        let full = if root {
+            let spans = traces
+                .pending
+                .remove(&trace_id)
+                .map(|pending| pending.spans);
+            decided.push((spans.unwrap_or_default(), Some(span)));
+            false
+        } else {
+            let full =
+                traces.pending.len() >= MAX_PENDING && !traces.pending.contains_key(&trace_id);
+            let pending = traces.pending.entry(trace_id).or_insert_with(|| Pending {
+                since: now,
+                spans: Vec::new(),
+            });
+            pending.spans.push(span);
+            if pending.spans.len() >= self.limits.max_spans {
+                let spans = traces
+                    .pending
+                    .remove(&trace_id)
+                    .map(|pending| pending.spans);
+                decided.extend(spans.map(|spans| (spans, None)));
+            }
+            full
+        };
+        let expired = traces.take_expired(now, self.limits.max_wait);
+        drop(traces);
+        for (spans, root) in decided {
+            self.decide(spans, root);
+        }
+        for spans in expired {
+            self.decide(spans, None);
+        }
+        if full {
+            self.decide_pending();
+        }
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        // Traces that didn't end stay buffered until they do or hit a limit,
+        // deciding them now would decide the rest of them without the root
+        self.processor.force_flush()
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        // Traces that didn't end by now never will
+        self.decide_pending();
+        self.processor.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Recording;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_errors_only() {
+        let recording = Recording::default();
+        let sampler = TailSampler::new(
+            Box::new(recording.clone()),
+            Some(TailPolicy {
+                errors: true,
+                ..TailPolicy::default()
+            }),
+            TailLimits::default(),
+        );
+        let (layer, _spans) = crate::builder("tail")
+            .with_span_processor(sampler)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("ok").in_scope(|| {
+                tracing::info_span!("ok child").in_scope(|| {});
+            });
+            tracing::info_span!("failed").in_scope(|| {
+                tracing::info_span!("failed child").in_scope(|| tracing::error!("boom"));
+            });
+            tracing::info_span!("forced", axiom.sample = true).in_scope(|| {});
+        });
+
+        assert_eq!(recording.names(), ["failed", "failed child", "forced"]);
+    }
+
+    #[test]
+    fn test_force_flush_keeps_pending() {
+        let recording = Recording::default();
+        let sampler = TailSampler::new(
+            Box::new(recording.clone()),
+            Some(TailPolicy {
+                errors: true,
+                ..TailPolicy::default()
+            }),
+            TailLimits::default(),
+        );
+        let (layer, spans) = crate::builder("tail")
+            .with_span_processor(sampler)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("running").in_scope(|| {
+                tracing::info_span!("failed").in_scope(|| tracing::error!("boom"));
+                tracing::info_span!("ok").in_scope(|| {});
+                for result in spans.provider().force_flush() {
+                    assert!(result.is_ok());
+                }
+                assert!(recording.names().is_empty());
+            });
+        });
+
+        // The whole trace is decided once the root span ends
+        assert_eq!(recording.names(), ["failed", "ok", "running"]);
+    }
+
+    #[test]
+    fn test_latency_sampling() {
+        let recording = Recording::default();
+        let sampler = TailSampler::new(
+            Box::new(recording.clone()),
+            Some(TailPolicy {
+                min_duration: Some(Duration::from_millis(50)),
+                ..TailPolicy::default()
+            }),
+            TailLimits::default(),
+        );
+        let (layer, _spans) = crate::builder("tail")
+            .with_span_processor(sampler)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("fast").in_scope(|| {});
+            tracing::info_span!("slow").in_scope(|| {
+                tracing::info_span!("slow child").in_scope(|| {});
+                std::thread::sleep(Duration::from_millis(60));
+            });
+        });
+
+        assert_eq!(recording.names(), ["slow", "slow child"]);
+    }
+
+    #[test]
+    fn test_sampled() {
+        let trace_id = TraceId::from(u128::from(u64::MAX / 4));
+        assert!(sampled(trace_id, 1.0));
+        assert!(sampled(trace_id, 0.5));
+        assert!(!sampled(trace_id, 0.1));
+        assert!(!sampled(trace_id, 0.0));
+    }
+
+    /// Records the spans the sampler with the policy and limits handed on
+    /// while the root span was still running, and all of them once it ended.
+    fn run_with_limits(
+        policy: Option<TailPolicy>,
+        limits: TailLimits,
+        run: impl FnOnce(),
+    ) -> (Vec<String>, Vec<String>) {
+        let recording = Recording::default();
+        let sampler = TailSampler::new(Box::new(recording.clone()), policy, limits);
+        let (layer, _spans) = crate::builder("tail")
+            .with_span_processor(sampler)
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let running = tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("long running").in_scope(|| {
+                run();
+                recording.names()
+            })
+        });
+        (running, recording.names())
+    }
+
+    /// Keeps the traces with a failed span.
+    fn errors_only() -> Option<TailPolicy> {
+        Some(TailPolicy {
+            errors: true,
+            ..TailPolicy::default()
+        })
+    }
+
+    #[test]
+    fn test_whole_traces() {
+        let (running, ended) = run_with_limits(None, TailLimits::default(), || {
+            tracing::info_span!("first").in_scope(|| {});
+            tracing::info_span!("second").in_scope(|| {});
+        });
+        assert!(running.is_empty());
+        assert_eq!(ended, ["first", "long running", "second"]);
+    }
+
+    #[test]
+    fn test_max_spans() {
+        let limits = TailLimits {
+            max_spans: 2,
+            ..TailLimits::default()
+        };
+        let (running, _) = run_with_limits(errors_only(), limits, || {
+            tracing::info_span!("ok").in_scope(|| {});
+            tracing::info_span!("failed").in_scope(|| tracing::error!("boom"));
+            tracing::info_span!("later").in_scope(|| {});
+        });
+        assert_eq!(running, ["failed", "ok"]);
+    }
+
+    #[test]
+    fn test_max_wait() {
+        let limits = TailLimits {
+            max_wait: Duration::ZERO,
+            ..TailLimits::default()
+        };
+        let (running, _) = run_with_limits(errors_only(), limits, || {
+            tracing::info_span!("failed").in_scope(|| tracing::error!("boom"));
+            // Within the sweep interval of the first span
+            tracing::info_span!("later").in_scope(|| {});
+        });
+        assert_eq!(running, ["failed"]);
+    }
+}
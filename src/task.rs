@@ -0,0 +1,67 @@
+use opentelemetry::{trace::FutureExt as _, Context};
+use std::future::Future;
+use tokio::task::JoinHandle;
+use tracing::Instrument as _;
+
+/// Spawns a tokio task that continues the current span and `OpenTelemetry`
+/// context, like `tokio::spawn(future.instrument(Span::current()))`. Spans
+/// created in the task are children of the current span instead of starting
+/// new traces, and baggage stays available.
+///
+/// ```rust
+/// # #[tokio::main]
+/// # async fn main() {
+/// #[tracing::instrument]
+/// async fn handle_order(id: u64) {
+///     tracing_axiom::spawn_instrumented(async move {
+///         // Part of the trace of `handle_order`
+///         tracing::info!(id, "sending confirmation");
+///     });
+/// }
+/// # handle_order(42).await;
+/// # }
+/// ```
+///
+/// # Panics
+///
+/// If called outside of a tokio runtime.
+pub fn spawn_instrumented<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(
+        future
+            .with_context(Context::current())
+            .instrument(tracing::Span::current()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[tokio::test]
+    async fn test_spawn_instrumented() {
+        let (layer, spans) = crate::builder("test").build_for_test();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+        let parent = tracing::info_span!("parent");
+        let task = parent.in_scope(|| {
+            spawn_instrumented(async {
+                tracing::info_span!("child").in_scope(|| {});
+            })
+        });
+        task.await.expect("task finished");
+        drop(parent);
+
+        let parent = spans.span("parent").expect("parent span");
+        let child = spans.span("child").expect("child span");
+        assert_eq!(
+            child.span_context.trace_id(),
+            parent.span_context.trace_id()
+        );
+        assert_eq!(child.parent_span_id, parent.span_context.span_id());
+    }
+}
@@ -0,0 +1,242 @@
+//! Capture spans in memory to assert on them in unit tests, without network
+//! access or an Axiom token.
+//!
+//! ```rust
+//! use tracing_subscriber::layer::SubscriberExt as _;
+//!
+//! let (axiom_layer, spans) = tracing_axiom::builder("tests").build_for_test();
+//! let subscriber = tracing_subscriber::registry().with(axiom_layer);
+//! tracing::subscriber::with_default(subscriber, || {
+//!     tracing::info_span!("checkout", user.id = 42).in_scope(|| {});
+//! });
+//!
+//! let span = spans.span("checkout").expect("span was recorded");
+//! assert_eq!(
+//!     tracing_axiom::testing::attribute(&span, "user.id"),
+//!     Some(&opentelemetry::Value::I64(42))
+//! );
+//! ```
+//!
+//! Integration tests can wait for a trace to arrive in Axiom with
+//! [`wait_for_trace`].
+
+#[cfg(not(target_arch = "wasm32"))]
+mod query;
+
+use crate::export::Transforms;
+use opentelemetry::{trace::TraceResult, Context, Key, Value};
+#[cfg(test)]
+use opentelemetry_sdk::export::trace::{ExportResult, SpanExporter};
+use opentelemetry_sdk::{
+    export::trace::SpanData,
+    trace::{Span, SpanProcessor, TracerProvider},
+};
+use std::sync::{Arc, Mutex, PoisonError};
+#[cfg(test)]
+use std::{future::Future, pin::Pin};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use query::{wait_for_trace, QueryClient};
+
+/// A handle to the spans recorded by a layer created with
+/// [`Builder::build_for_test`](crate::Builder::build_for_test).
+///
+/// Spans are recorded as soon as they are closed. The handle keeps the tracer
+/// provider alive, spans closed after it was dropped are not recorded.
+#[derive(Debug, Clone)]
+pub struct CapturedSpans {
+    spans: Arc<Mutex<Vec<SpanData>>>,
+    provider: TracerProvider,
+}
+
+impl CapturedSpans {
+    pub(crate) fn new(provider: opentelemetry_sdk::trace::Builder, transforms: Transforms) -> Self {
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let provider = provider
+            .with_span_processor(Recorder {
+                spans: spans.clone(),
+                transforms,
+            })
+            .build();
+        Self { spans, provider }
+    }
+
+    pub(crate) fn provider(&self) -> &TracerProvider {
+        &self.provider
+    }
+
+    /// All spans that were closed so far, in the order they were closed.
+    #[must_use]
+    pub fn finished_spans(&self) -> Vec<SpanData> {
+        self.spans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// The first closed span with the given name.
+    #[must_use]
+    pub fn span(&self, name: &str) -> Option<SpanData> {
+        self.spans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .find(|span| span.name == name)
+            .cloned()
+    }
+
+    /// Forget all spans recorded so far.
+    pub fn clear(&self) {
+        self.spans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+/// The value of the attribute `key` of a recorded span.
+#[must_use]
+pub fn attribute<'a>(span: &'a SpanData, key: &str) -> Option<&'a Value> {
+    span.attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == key)
+        .map(|kv| &kv.value)
+}
+
+/// The value of the resource attribute `key` of a recorded span, e.g. `service.name`.
+#[must_use]
+pub fn resource_attribute(span: &SpanData, key: &'static str) -> Option<Value> {
+    span.resource.get(Key::from_static_str(key))
+}
+
+#[derive(Debug)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<SpanData>>>,
+    transforms: Transforms,
+}
+
+impl SpanProcessor for Recorder {
+    fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+    fn on_end(&self, mut span: SpanData) {
+        self.transforms.apply(&mut span);
+        self.spans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(span);
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        Ok(())
+    }
+}
+
+/// Records the spans it gets as span processor or exporter, for the tests of
+/// the processors and exporters that hand spans on.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Recording(Arc<Mutex<Vec<SpanData>>>);
+
+#[cfg(test)]
+impl Recording {
+    /// The recorded spans, in the order they were handed on.
+    pub(crate) fn spans(&self) -> Vec<SpanData> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// The names of the recorded spans, sorted.
+    pub(crate) fn names(&self) -> Vec<String> {
+        let mut names = self
+            .spans()
+            .iter()
+            .map(|span| span.name.to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    fn record(&self, spans: impl IntoIterator<Item = SpanData>) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(spans);
+    }
+}
+
+#[cfg(test)]
+impl SpanProcessor for Recording {
+    fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+    fn on_end(&self, span: SpanData) {
+        self.record([span]);
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl SpanExporter for Recording {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        self.record(batch);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::Status;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_captures_spans() {
+        let (layer, spans) = crate::builder("testing")
+            .with_service_version("1.2.3")
+            .build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("outer", otel.status_code = "ERROR");
+            outer.in_scope(|| {
+                tracing::info_span!("inner", answer = 42).in_scope(|| {});
+            });
+        });
+
+        let finished = spans.finished_spans();
+        assert_eq!(finished.len(), 2);
+        assert_eq!(finished[0].name, "inner");
+
+        let inner = spans.span("inner").expect("inner span");
+        let outer = spans.span("outer").expect("outer span");
+        assert_eq!(inner.parent_span_id, outer.span_context.span_id());
+        assert_eq!(attribute(&inner, "answer"), Some(&Value::I64(42)));
+        assert_eq!(attribute(&inner, "missing"), None);
+        assert!(matches!(outer.status, Status::Error { .. }));
+        assert_eq!(
+            resource_attribute(&outer, "service.name"),
+            Some(Value::from("testing"))
+        );
+        assert_eq!(
+            resource_attribute(&outer, "service.version"),
+            Some(Value::from("1.2.3"))
+        );
+
+        spans.clear();
+        assert!(spans.finished_spans().is_empty());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{builder::CLOUD_URL, Error};
+use opentelemetry::trace::TraceId;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// The delay before the first retry, doubled after every attempt.
+const FIRST_DELAY: Duration = Duration::from_millis(250);
+const MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// A client for the query API of Axiom, to check in integration tests that
+/// traces arrived, see [`wait_for_trace`].
+#[derive(Debug, Clone)]
+pub struct QueryClient {
+    url: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+impl QueryClient {
+    /// A client querying Axiom cloud with the token, which needs permission
+    /// to query the dataset.
+    #[must_use]
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            url: CLOUD_URL.to_string(),
+            token: token.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Queries the Axiom instance at the URL instead of Axiom cloud.
+    #[must_use]
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// A client with the token of `AXIOM_TOKEN` and the URL of `AXIOM_URL`,
+    /// if it is set.
+    ///
+    /// # Errors
+    ///
+    /// Errors if `AXIOM_TOKEN` is not set or either variable isn't unicode.
+    pub fn from_env() -> Result<Self, Error> {
+        let var = |name: &'static str| match std::env::var(name) {
+            Ok(value) => Ok(Some(value)),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(std::env::VarError::NotUnicode(_)) => {
+                Err(Error::EnvVarNotUnicode(name.to_string()))
+            }
+        };
+        let token = var("AXIOM_TOKEN")?.ok_or(Error::EnvVarMissing("AXIOM_TOKEN"))?;
+        let client = Self::new(token);
+        Ok(match var("AXIOM_URL")? {
+            Some(url) => client.with_url(url),
+            None => client,
+        })
+    }
+
+    /// The rows the APL query returns.
+    async fn query(&self, apl: &str) -> Result<Vec<Value>, Error> {
+        let uri = format!(
+            "{}/v1/datasets/_apl?format=legacy",
+            self.url.trim_end_matches('/')
+        );
+        let response = self
+            .client
+            .post(&uri)
+            .bearer_auth(&self.token)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(json!({ "apl": apl }).to_string())
+            .send()
+            .await
+            .map_err(|e| Error::Query(format!("{uri}: {e}")))?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Query(format!("{uri}: {e}")))?;
+        if !status.is_success() {
+            return Err(Error::Query(format!(
+                "{uri} responded with {status}: {}",
+                String::from_utf8_lossy(&body)
+            )));
+        }
+        let result = serde_json::from_slice(&body).map_err(|e| Error::Query(e.to_string()))?;
+        Ok(rows(result))
+    }
+}
+
+/// The fields of the matches of a query result, with their `_time`.
+fn rows(result: Value) -> Vec<Value> {
+    let Value::Object(mut result) = result else {
+        return Vec::new();
+    };
+    let Some(Value::Array(matches)) = result.remove("matches") else {
+        return Vec::new();
+    };
+    matches
+        .into_iter()
+        .filter_map(|row| {
+            let Value::Object(mut row) = row else {
+                return None;
+            };
+            let mut data = row.remove("data")?;
+            if let (Some(fields), Some(time)) = (data.as_object_mut(), row.remove("_time")) {
+                fields.insert("_time".to_string(), time);
+            }
+            Some(data)
+        })
+        .collect()
+}
+
+/// Flushes the spans of the most recently built layer and polls the dataset
+/// until spans of the trace can be queried, then returns them as rows with
+/// the fields Axiom shows, e.g. `name`, `duration` and `attributes`.
+///
+/// It returns as soon as the first spans arrived, spans exported in later
+/// batches may be missing.
+///
+/// ```rust,no_run
+/// # use std::time::Duration;
+/// # async fn test() -> Result<(), tracing_axiom::Error> {
+/// # let trace_id = opentelemetry::trace::TraceId::INVALID;
+/// let client = tracing_axiom::testing::QueryClient::from_env()?;
+/// let rows = tracing_axiom::testing::wait_for_trace(
+///     &client,
+///     "traces",
+///     trace_id,
+///     Duration::from_secs(30),
+/// )
+/// .await?;
+/// assert!(rows.iter().any(|row| row["name"] == "checkout"));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Errors if the dataset name contains a quote or backslash, if flushing or a
+/// query failed, or no span of the trace arrived within the timeout.
+pub async fn wait_for_trace(
+    client: &QueryClient,
+    dataset: &str,
+    trace_id: TraceId,
+    timeout: Duration,
+) -> Result<Vec<Value>, Error> {
+    // The batch processor blocks until it exported, which must not block the runtime driving it
+    tokio::task::spawn_blocking(crate::force_flush)
+        .await
+        .map_err(|_| Error::Query("flushing panicked".to_string()))??;
+    let apl = trace_query(dataset, trace_id)?;
+    let deadline = Instant::now() + timeout;
+    let mut delay = FIRST_DELAY;
+    loop {
+        let rows = client.query(&apl).await?;
+        if !rows.is_empty() {
+            return Ok(rows);
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Error::Query(format!(
+                "no span of trace {trace_id} arrived in {dataset} within {timeout:?}"
+            )));
+        }
+        tokio::time::sleep(delay.min(remaining)).await;
+        delay = (delay * 2).min(MAX_DELAY);
+    }
+}
+
+/// The APL query for the spans of the trace.
+fn trace_query(dataset: &str, trace_id: TraceId) -> Result<String, Error> {
+    // The name is quoted in the query
+    if dataset.contains(['\'', '\\']) {
+        return Err(Error::Query(format!("invalid dataset {dataset:?}")));
+    }
+    Ok(format!("['{dataset}'] | where trace_id == '{trace_id}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows() {
+        let result = json!({
+            "status": { "rowsMatched": 2 },
+            "matches": [
+                {
+                    "_time": "2024-03-01T12:00:00Z",
+                    "_rowId": "1",
+                    "data": { "name": "checkout", "trace_id": "4bf92f3577b34da6a3ce929d0e0e4736" }
+                },
+                { "_time": "2024-03-01T12:00:01Z", "_rowId": "2" }
+            ]
+        });
+        assert_eq!(
+            rows(result),
+            [json!({
+                "_time": "2024-03-01T12:00:00Z",
+                "name": "checkout",
+                "trace_id": "4bf92f3577b34da6a3ce929d0e0e4736"
+            })]
+        );
+        assert!(rows(json!({ "status": {} })).is_empty());
+    }
+
+    #[test]
+    fn test_trace_query() -> Result<(), Error> {
+        let trace_id =
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").expect("valid trace id");
+        assert_eq!(
+            trace_query("traces-prod", trace_id)?,
+            "['traces-prod'] | where trace_id == '4bf92f3577b34da6a3ce929d0e0e4736'"
+        );
+        assert!(trace_query("traces'] | take 1 //", trace_id).is_err());
+        assert!(trace_query("traces\\", trace_id).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+//! [`tonic`](https://docs.rs/tonic) server middleware creating a server span for
+//! every gRPC request. Enable with the `tonic` feature.
+//!
+//! ```rust,ignore
+//! use tonic::transport::Server;
+//! use tracing_axiom::tonic::GrpcServerLayer;
+//!
+//! Server::builder()
+//!     .layer(GrpcServerLayer::new())
+//!     .add_service(GreeterServer::new(greeter))
+//!     .serve(addr)
+//!     .await?;
+//! ```
+
+use crate::{propagation::extract_context, spans};
+use http::{Request, Response};
+use pin_project_lite::pin_project;
+use std::{
+    fmt::Display,
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+/// A [`Layer`] that wraps gRPC services in [`GrpcServerService`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GrpcServerLayer;
+
+impl GrpcServerLayer {
+    /// Create a new layer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for GrpcServerLayer {
+    type Service = GrpcServerService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcServerService { inner }
+    }
+}
+
+/// A [`Service`] that creates a server span for every gRPC request, continuing the
+/// trace of the caller if the request metadata carries trace context.
+///
+/// The span records the `rpc.system`, `rpc.service`, `rpc.method` and
+/// `rpc.grpc.status_code` attributes. The status code is read from the response
+/// headers, which is where tonic puts it for failed calls.
+#[derive(Debug, Clone)]
+pub struct GrpcServerService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for GrpcServerService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Error: Display,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let (service, method) = parse_path(request.uri().path());
+        let span = spans::grpc_server_span(service, method);
+        span.set_parent(extract_context(request.headers()));
+        let inner = span.in_scope(|| self.inner.call(request));
+        ResponseFuture { inner, span }
+    }
+}
+
+/// Splits a gRPC path `/package.Service/Method` into service and method.
+fn parse_path(path: &str) -> (&str, &str) {
+    path.trim_start_matches('/')
+        .split_once('/')
+        .unwrap_or((path, ""))
+}
+
+pin_project! {
+    /// The response future of [`GrpcServerService`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        span: Span,
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+    E: Display,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let _guard = this.span.enter();
+        let result = ready!(this.inner.poll(cx));
+        match &result {
+            Ok(response) => {
+                if let Some(code) = response
+                    .headers()
+                    .get("grpc-status")
+                    .and_then(|code| code.to_str().ok())
+                    .and_then(|code| code.parse().ok())
+                {
+                    spans::record_grpc_status(this.span, code);
+                }
+            }
+            Err(error) => spans::record_error(this.span, error),
+        }
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path("/helloworld.Greeter/SayHello"),
+            ("helloworld.Greeter", "SayHello")
+        );
+        assert_eq!(parse_path("/invalid"), ("/invalid", ""));
+    }
+}
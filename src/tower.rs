@@ -0,0 +1,226 @@
+//! [`tower`](https://docs.rs/tower) middleware creating a server span for every
+//! HTTP request. Enable with the `tower` feature.
+//!
+//! ```rust,ignore
+//! use axum::{extract::MatchedPath, routing::get, Router};
+//! use tracing_axiom::tower::HttpServerLayer;
+//!
+//! let app = Router::new()
+//!     .route("/users/:id", get(get_user))
+//!     .layer(HttpServerLayer::new().with_route(|extensions| {
+//!         extensions
+//!             .get::<MatchedPath>()
+//!             .map(|path| path.as_str().to_string())
+//!     }));
+//! ```
+
+use crate::{propagation::extract_context, spans};
+use http::{Extensions, Request, Response};
+use pin_project_lite::pin_project;
+use std::{
+    fmt::Display,
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+type RouteFn = fn(&Extensions) -> Option<String>;
+
+/// A [`Layer`] that wraps services in [`HttpServerService`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpServerLayer {
+    route: Option<RouteFn>,
+}
+
+impl HttpServerLayer {
+    /// Create a new layer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a function that looks up the matched route template (e.g. `/users/:id`)
+    /// in the request extensions, it is used for the span name and the
+    /// `http.route` attribute.
+    #[must_use]
+    pub fn with_route(mut self, route: RouteFn) -> Self {
+        self.route = Some(route);
+        self
+    }
+}
+
+impl<S> Layer<S> for HttpServerLayer {
+    type Service = HttpServerService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpServerService {
+            inner,
+            route: self.route,
+        }
+    }
+}
+
+/// A [`Service`] that creates a server span for every request, continuing the
+/// trace of the caller if the request carries trace context headers.
+///
+/// The span records the `http.request.method`, `http.route`, `url.path` and
+/// `http.response.status_code` attributes, server errors and errors returned by
+/// the inner service mark the span as failed.
+#[derive(Debug, Clone)]
+pub struct HttpServerService<S> {
+    inner: S,
+    route: Option<RouteFn>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for HttpServerService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Error: Display,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let route = self.route.and_then(|route| route(request.extensions()));
+        let span = spans::http_server_span(
+            request.method().as_str(),
+            route.as_deref(),
+            request.uri().path(),
+        );
+        span.set_parent(extract_context(request.headers()));
+        let inner = span.in_scope(|| self.inner.call(request));
+        ResponseFuture { inner, span }
+    }
+}
+
+pin_project! {
+    /// The response future of [`HttpServerService`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        span: Span,
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+    E: Display,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let _guard = this.span.enter();
+        let result = ready!(this.inner.poll(cx));
+        match &result {
+            Ok(response) => spans::record_http_status(this.span, response.status().as_u16()),
+            Err(error) => spans::record_error(this.span, error),
+        }
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::attribute;
+    use http::StatusCode;
+    use opentelemetry::{
+        global,
+        trace::{SpanId, Status, TraceId},
+        Value,
+    };
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use std::convert::Infallible;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<Request<&'static str>> for Echo {
+        type Response = Response<&'static str>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<&'static str>) -> Self::Future {
+            let mut response = Response::new(request.into_body());
+            if *response.body() == "fail" {
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            }
+            std::future::ready(Ok(response))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_passes_through_response() {
+        let mut service = HttpServerLayer::new()
+            .with_route(|_| Some("/echo".to_string()))
+            .layer(Echo);
+        let response = service
+            .call(Request::new("hello"))
+            .await
+            .expect("infallible");
+        assert_eq!(response.into_body(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_records_server_span() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let (layer, spans) = crate::builder("tower").build_for_test();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+        let mut service = HttpServerLayer::new()
+            .with_route(|_| Some("/users/:id".to_string()))
+            .layer(Echo);
+        let request = Request::get("/users/42")
+            .header(
+                "traceparent",
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            )
+            .body("fail")
+            .expect("valid request");
+        let response = service.call(request).await.expect("infallible");
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let span = spans.span("GET /users/:id").expect("span was recorded");
+        assert_eq!(
+            attribute(&span, "http.request.method"),
+            Some(&Value::from("GET"))
+        );
+        assert_eq!(
+            attribute(&span, "http.route"),
+            Some(&Value::from("/users/:id"))
+        );
+        assert_eq!(
+            attribute(&span, "url.path"),
+            Some(&Value::from("/users/42"))
+        );
+        assert_eq!(
+            attribute(&span, "http.response.status_code"),
+            Some(&Value::I64(503))
+        );
+        assert!(matches!(span.status, Status::Error { .. }));
+        assert_eq!(
+            span.span_context.trace_id(),
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").expect("valid trace id")
+        );
+        assert_eq!(
+            span.parent_span_id,
+            SpanId::from_hex("b7ad6b7169203331").expect("valid span id")
+        );
+    }
+}
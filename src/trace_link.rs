@@ -0,0 +1,79 @@
+use opentelemetry::trace::{TraceContextExt as _, TraceId};
+use std::env;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+/// The URL of the Axiom app.
+const APP_URL: &str = "https://app.axiom.co";
+
+/// The ID of the trace of the current span, `None` outside of a span or if it
+/// is not recorded by the Axiom layer.
+///
+/// ```rust
+/// #[tracing::instrument]
+/// fn error_message() -> String {
+///     match tracing_axiom::current_trace_id() {
+///         Some(trace_id) => {
+///             let url = tracing_axiom::trace_url("traces", trace_id);
+///             format!("something went wrong, see {url}")
+///         }
+///         None => "something went wrong".to_string(),
+///     }
+/// }
+/// ```
+#[must_use]
+pub fn current_trace_id() -> Option<TraceId> {
+    let context = tracing::Span::current().context();
+    let span_context = context.span().span_context().clone();
+    span_context.is_valid().then(|| span_context.trace_id())
+}
+
+/// The URL of the trace in the dataset in the Axiom app, e.g. to link it from
+/// error responses or support tickets. The organization is read from the
+/// `AXIOM_ORG_ID` environment variable, without it the trace opens in the
+/// organization last used in the app.
+#[must_use]
+pub fn trace_url(dataset: &str, trace_id: TraceId) -> String {
+    let org = env::var("AXIOM_ORG_ID").ok();
+    format_trace_url(org.as_deref(), dataset, trace_id)
+}
+
+fn format_trace_url(org: Option<&str>, dataset: &str, trace_id: TraceId) -> String {
+    let path = match org {
+        Some(org) => format!("{org}/trace"),
+        None => "trace".to_string(),
+    };
+    format!("{APP_URL}/{path}?traceId={trace_id}&traceDataset={dataset}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    #[test]
+    fn test_current_trace_id() {
+        let (layer, spans) = crate::builder("trace-link").build_for_test();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let trace_id = tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(current_trace_id(), None);
+            tracing::info_span!("request").in_scope(current_trace_id)
+        });
+        assert_eq!(
+            trace_id,
+            Some(spans.finished_spans()[0].span_context.trace_id())
+        );
+    }
+
+    #[test]
+    fn test_trace_url() {
+        let trace_id = TraceId::from(1);
+        assert_eq!(
+            format_trace_url(Some("acme-1234"), "traces", trace_id),
+            "https://app.axiom.co/acme-1234/trace?traceId=00000000000000000000000000000001&traceDataset=traces"
+        );
+        assert_eq!(
+            format_trace_url(None, "traces", trace_id),
+            "https://app.axiom.co/trace?traceId=00000000000000000000000000000001&traceDataset=traces"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Support for `wasm32-unknown-unknown` in the browser, where there are no threads
+//! and no tokio runtime. Spans are exported with the `fetch` API from a batch
+//! processor driven by the browser event loop.
+
+use bytes::Bytes;
+use gloo_timers::future::{IntervalStream, TimeoutFuture};
+use http::{Request, Response};
+use opentelemetry_http::{HttpClient, HttpError};
+use opentelemetry_sdk::runtime::{Runtime, RuntimeChannel};
+use send_wrapper::SendWrapper;
+use std::{fmt::Debug, future::Future, pin::Pin, time::Duration};
+
+/// Exports over `fetch` using the wasm version of [`reqwest::Client`].
+///
+/// The futures of the browser are not `Send`, which the exporter requires. There
+/// is only a single thread in the browser, so they are wrapped in a
+/// [`SendWrapper`] that would panic if they were ever moved to another thread.
+#[derive(Debug, Default)]
+pub(crate) struct FetchClient(reqwest::Client);
+
+#[async_trait::async_trait]
+impl HttpClient for FetchClient {
+    async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+        let client = self.0.clone();
+        SendWrapper::new(async move {
+            let request = request.try_into()?;
+            let response = client.execute(request).await?.error_for_status()?;
+            let mut http_response = Response::builder().status(response.status());
+            if let Some(headers) = http_response.headers_mut() {
+                *headers = response.headers().clone();
+            }
+            Ok(http_response.body(response.bytes().await?)?)
+        })
+        .await
+    }
+}
+
+/// Drives the batch span processor on the browser event loop.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct WasmRuntime;
+
+fn millis(duration: Duration) -> u32 {
+    u32::try_from(duration.as_millis()).unwrap_or(u32::MAX)
+}
+
+impl Runtime for WasmRuntime {
+    type Interval = SendWrapper<IntervalStream>;
+    type Delay = SendWrapper<TimeoutFuture>;
+
+    fn interval(&self, duration: Duration) -> Self::Interval {
+        SendWrapper::new(IntervalStream::new(millis(duration)))
+    }
+
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send + 'static>>) {
+        wasm_bindgen_futures::spawn_local(future);
+    }
+
+    fn delay(&self, duration: Duration) -> Self::Delay {
+        SendWrapper::new(TimeoutFuture::new(millis(duration)))
+    }
+}
+
+impl RuntimeChannel for WasmRuntime {
+    type Receiver<T: Debug + Send> = tokio_stream::wrappers::ReceiverStream<T>;
+    type Sender<T: Debug + Send> = tokio::sync::mpsc::Sender<T>;
+
+    fn batch_message_channel<T: Debug + Send>(
+        &self,
+        capacity: usize,
+    ) -> (Self::Sender<T>, Self::Receiver<T>) {
+        let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+        (
+            sender,
+            tokio_stream::wrappers::ReceiverStream::new(receiver),
+        )
+    }
+}